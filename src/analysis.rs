@@ -0,0 +1,168 @@
+use crate::command::CommandDesc;
+use crate::durations::format_duration;
+use crate::stats::RunStats;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+/// Wall time this much longer than the critical path suggests that commands waited for a slot.
+const SKEW_THRESHOLD: f64 = 1.25;
+/// A command taking at least this share of the critical path is worth calling out.
+const BOTTLENECK_SHARE: f64 = 0.5;
+
+/// Where a run's time went, for `--analyze`: the wall time against the critical path (the
+/// longest chain of commands through `@needs`), and what that suggests.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    pub wall_time: Duration,
+    /// The sum of every attempt's duration.
+    pub command_time: Duration,
+    /// How long the run would have taken with unlimited parallelism.
+    pub critical_path: Duration,
+    /// The commands on the critical path, in the order they ran.
+    pub critical_commands: Vec<String>,
+}
+
+impl Analysis {
+    pub(crate) fn from_commands(commands: &[CommandDesc], stats: &RunStats) -> Self {
+        let mut by_label: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, command) in commands.iter().enumerate() {
+            by_label.entry(command.label()).or_default().push(i);
+        }
+        let mut paths = PathFinder {
+            commands,
+            by_label,
+            longest: vec![None; commands.len()],
+            visiting: vec![false; commands.len()],
+        };
+        let end = (0..commands.len()).max_by_key(|&i| paths.longest(i).0);
+        let mut critical_commands = Vec::new();
+        let mut next = end;
+        while let Some(i) = next {
+            critical_commands.push(commands[i].label());
+            next = paths.longest(i).1;
+        }
+        critical_commands.reverse();
+        return Self {
+            wall_time: stats.wall_time,
+            command_time: stats.command_time,
+            critical_path: end.map_or(Duration::ZERO, |i| paths.longest(i).0),
+            critical_commands,
+        };
+    }
+
+    /// The command on the critical path that took the longest, and its share of the path.
+    pub fn bottleneck(&self, commands: &[CommandDesc]) -> Option<(String, f64)> {
+        if self.critical_path.is_zero() {
+            return None;
+        }
+        let slowest = (self.critical_commands.iter())
+            .filter_map(|label| {
+                let command = commands.iter().find(|c| c.label() == *label)?;
+                Some((label, time_taken(command)))
+            })
+            .max_by_key(|(_, took)| *took)?;
+        let share = slowest.1.as_secs_f64() / self.critical_path.as_secs_f64();
+        return Some((slowest.0.clone(), share));
+    }
+
+    /// What would most likely make the run faster, if anything stands out.
+    pub fn hint(&self, commands: &[CommandDesc]) -> Option<String> {
+        let critical = self.critical_path.as_secs_f64();
+        let skew = self.wall_time.as_secs_f64() / critical;
+        if critical > 0.0 && skew > SKEW_THRESHOLD {
+            return Some(format!(
+                "the wall time is {:.1}× the critical path — consider raising --jobs",
+                skew
+            ));
+        }
+        match self.bottleneck(commands) {
+            Some((label, share)) if share >= BOTTLENECK_SHARE => Some(format!(
+                "{} alone is {:.0}% of the critical path",
+                label,
+                share * 100.0
+            )),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_json(&self, commands: &[CommandDesc]) -> Value {
+        json!({
+            "wall_time_secs": self.wall_time.as_secs_f64(),
+            "command_time_secs": self.command_time.as_secs_f64(),
+            "critical_path_secs": self.critical_path.as_secs_f64(),
+            "critical_path": self.critical_commands,
+            "hint": self.hint(commands),
+        })
+    }
+
+    /// The one-line analysis, with the hint if there is one.
+    pub fn summary(&self, commands: &[CommandDesc]) -> String {
+        let mut line = self.to_string();
+        if let Some(hint) = self.hint(commands) {
+            line.push_str(&format!(" — {}", hint));
+        }
+        return line;
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wall time {}, command time {}, critical path {}",
+            format_duration(self.wall_time),
+            format_duration(self.command_time),
+            format_duration(self.critical_path)
+        )?;
+        if self.critical_commands.len() > 1 {
+            write!(f, " ({})", self.critical_commands.join(" → "))?;
+        }
+        Ok(())
+    }
+}
+
+/// How long a command held up the commands that need it: all of its attempts.
+fn time_taken(command: &CommandDesc) -> Duration {
+    let earlier = command.attempts().iter().filter_map(|a| a.duration());
+    return earlier.chain(command.duration()).sum();
+}
+
+/// Finds the longest chain of commands ending at each command, following `@needs`.
+struct PathFinder<'a> {
+    commands: &'a [CommandDesc],
+    by_label: HashMap<String, Vec<usize>>,
+    /// For each command, the length of the longest chain ending at it, and the command before it
+    /// in that chain.
+    longest: Vec<Option<(Duration, Option<usize>)>>,
+    visiting: Vec<bool>,
+}
+
+impl PathFinder<'_> {
+    fn longest(&mut self, i: usize) -> (Duration, Option<usize>) {
+        if let Some(longest) = self.longest[i] {
+            return longest;
+        }
+        if self.visiting[i] {
+            // a cycle, which can't have run; stop following it
+            return (Duration::ZERO, None);
+        }
+        self.visiting[i] = true;
+        let dependencies: Vec<usize> = (self.commands[i].depends_on.iter())
+            .flat_map(|label| self.by_label.get(label).cloned().unwrap_or_default())
+            .collect();
+        let mut before = (Duration::ZERO, None);
+        for dependency in dependencies {
+            let length = self.longest(dependency).0;
+            if length > before.0 || before.1.is_none() {
+                before = (length, Some(dependency));
+            }
+        }
+        self.visiting[i] = false;
+        let longest = (before.0 + time_taken(&self.commands[i]), before.1);
+        self.longest[i] = Some(longest);
+        return longest;
+    }
+}
@@ -0,0 +1,107 @@
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Annotation {
+    pub(crate) key: String,
+    pub(crate) value: Option<String>,
+}
+
+impl Annotation {
+    pub(crate) fn value(&self) -> Result<&str, String> {
+        self.value
+            .as_deref()
+            .ok_or_else(|| format!("@{} requires a value", self.key))
+    }
+
+    pub(crate) fn parse_value<T: std::str::FromStr>(&self) -> Result<T, String> {
+        let value = self.value()?;
+        value
+            .parse()
+            .map_err(|_| format!("invalid value for @{}: {:?}", self.key, value))
+    }
+}
+
+/// Splits leading `@key` / `@key=value` tokens off of a command line. A value ends at whitespace,
+/// unless it's in single or double quotes (`@key='a value'`), in which case it ends at the
+/// closing quote.
+pub(crate) fn split_annotations(line: &str) -> (Vec<Annotation>, &str) {
+    let mut annotations = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(token_start) = rest.strip_prefix('@') {
+        let token_end = token_start
+            .find(char::is_whitespace)
+            .unwrap_or(token_start.len());
+        let token = &token_start[..token_end];
+        let (annotation, token_end) = match token.split_once('=') {
+            Some((key, value)) => match quoted(&token_start[key.len() + 1..]) {
+                Some((value, len)) => (
+                    Annotation {
+                        key: key.to_string(),
+                        value: Some(value.to_string()),
+                    },
+                    key.len() + 1 + len,
+                ),
+                None => (
+                    Annotation {
+                        key: key.to_string(),
+                        value: Some(value.to_string()),
+                    },
+                    token_end,
+                ),
+            },
+            None => (
+                Annotation {
+                    key: token.to_string(),
+                    value: None,
+                },
+                token_end,
+            ),
+        };
+        annotations.push(annotation);
+        rest = token_start[token_end..].trim_start();
+    }
+    return (annotations, rest);
+}
+
+/// If `text` starts with a quoted string that's followed by whitespace (or nothing), what's inside
+/// the quotes, and the length of the whole thing with its quotes.
+fn quoted(text: &str) -> Option<(&str, usize)> {
+    let quote = text.chars().next().filter(|&c| c == '\'' || c == '"')?;
+    let len = text[1..].find(quote)?;
+    let after = &text[len + 2..];
+    if !after.is_empty() && !after.starts_with(char::is_whitespace) {
+        return None;
+    }
+    return Some((&text[1..1 + len], len + 2));
+}
+
+pub(crate) fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+/// Annotations that don't take a value, which can also be written as a bare `# key` comment.
+const FLAGS: &[&str] = &[
+    "require-output",
+    "fail-empty-output",
+    "allow-failure",
+    "confirm",
+];
+
+/// Parses a `# key: value` comment line, which annotates the command on the next line.
+pub(crate) fn parse_comment(line: &str) -> Option<Annotation> {
+    let comment = line.trim_start().strip_prefix('#')?.trim();
+    if FLAGS.contains(&comment) {
+        return Some(Annotation {
+            key: comment.to_string(),
+            value: None,
+        });
+    }
+    let (key, value) = comment.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    let value = value.trim();
+    Some(Annotation {
+        key: key.to_string(),
+        value: (!value.is_empty()).then(|| value.to_string()),
+    })
+}
@@ -0,0 +1,94 @@
+use crate::command::CommandStatus;
+use crate::durations::format_duration;
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+/// The timings of one command's benchmark runs, from [crate::Commands::bench].
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub label: String,
+    /// The duration of each run that passed, in the order they ran.
+    pub durations: Vec<Duration>,
+    /// If a run failed, how; the command isn't run again after that.
+    pub failure: Option<CommandStatus>,
+    /// If the command wasn't run at all, why not.
+    pub skipped: Option<String>,
+}
+
+impl BenchResult {
+    pub fn min(&self) -> Option<Duration> {
+        self.durations.iter().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.durations.iter().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let total: Duration = self.durations.iter().sum();
+        Some(
+            total
+                / u32::try_from(self.durations.len())
+                    .ok()
+                    .filter(|&n| n > 0)?,
+        )
+    }
+
+    pub fn median(&self) -> Option<Duration> {
+        let mut sorted = self.durations.clone();
+        sorted.sort();
+        let mid = sorted.len() / 2;
+        return match sorted.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(sorted[mid]),
+            _ => Some((sorted[mid - 1] + sorted[mid]) / 2),
+        };
+    }
+}
+
+/// Every command's timings, which display as a table.
+#[derive(Clone, Debug, Default)]
+pub struct BenchReport {
+    pub results: Vec<BenchResult>,
+}
+
+impl BenchReport {
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|r| r.failure.is_none())
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let format = |d: Option<Duration>| d.map_or("-".to_string(), format_duration);
+        writeln!(
+            f,
+            "{:>4} {:>8} {:>8} {:>8} {:>8}  command",
+            "runs", "min", "median", "max", "mean"
+        )?;
+        for result in &self.results {
+            write!(
+                f,
+                "{:>4} {:>8} {:>8} {:>8} {:>8}  {}",
+                result.durations.len(),
+                format(result.min()),
+                format(result.median()),
+                format(result.max()),
+                format(result.mean()),
+                result.label
+            )?;
+            match (&result.failure, &result.skipped) {
+                (Some(status), _) => writeln!(
+                    f,
+                    " (failed on run {}: {})",
+                    result.durations.len() + 1,
+                    status
+                )?,
+                (None, Some(reason)) => writeln!(f, " (skipped: {})", reason)?,
+                (None, None) => writeln!(f)?,
+            }
+        }
+        return Ok(());
+    }
+}
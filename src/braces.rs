@@ -0,0 +1,82 @@
+/// The most commands a single line can expand into.
+const MAX_EXPANSIONS: usize = 1000;
+
+/// Expands shell-style brace patterns: `cargo test -p {foo,bar}` becomes `cargo test -p foo` and
+/// `cargo test -p bar`. Multiple and nested patterns expand to every combination. Braces without
+/// a comma, braces inside quotes, and `${...}` are left alone.
+pub(crate) fn expand(line: &str) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    expand_into(line, &mut expanded)?;
+    return Ok(expanded);
+}
+
+fn expand_into(line: &str, expanded: &mut Vec<String>) -> Result<(), String> {
+    let Some((open, alternatives, close)) = find_group(line) else {
+        if expanded.len() >= MAX_EXPANSIONS {
+            return Err(format!(
+                "braces expand to more than {} commands",
+                MAX_EXPANSIONS
+            ));
+        }
+        expanded.push(line.to_string());
+        return Ok(());
+    };
+    for alternative in alternatives {
+        let line = format!("{}{}{}", &line[..open], alternative, &line[close + 1..]);
+        expand_into(&line, expanded)?;
+    }
+    return Ok(());
+}
+
+/// Finds the first expandable `{...}`, returning the positions of its braces and its
+/// comma-separated alternatives.
+fn find_group(line: &str) -> Option<(usize, Vec<&str>, usize)> {
+    let bytes = line.as_bytes();
+    let mut quote = None;
+    for (open, &b) in bytes.iter().enumerate() {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(b),
+            (None, b'{') if open == 0 || bytes[open - 1] != b'$' => {
+                if let Some(group) = match_group(line, open) {
+                    return Some(group);
+                }
+            }
+            _ => {}
+        }
+    }
+    return None;
+}
+
+fn match_group(line: &str, open: usize) -> Option<(usize, Vec<&str>, usize)> {
+    let bytes = line.as_bytes();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = open + 1;
+    let mut alternatives = Vec::new();
+    for (i, &b) in bytes.iter().enumerate().skip(open) {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(b),
+            (None, b'{') => depth += 1,
+            (None, b'}') => {
+                depth -= 1;
+                if depth == 0 {
+                    if alternatives.is_empty() {
+                        return None; // no commas, so not a pattern
+                    }
+                    alternatives.push(&line[start..i]);
+                    return Some((open, alternatives, i));
+                }
+            }
+            (None, b',') if depth == 1 => {
+                alternatives.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    return None;
+}
@@ -0,0 +1,56 @@
+use crate::durations::format_bytes;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A cap on the output captured across all commands together, from `--total-output-budget`.
+/// Capture threads charge what they read against it; once it's spent, they drop what they read
+/// until the run frees some up by dropping passed commands' output.
+#[derive(Debug)]
+pub(crate) struct OutputBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl OutputBudget {
+    /// The most a capture reads at once.
+    const HEADROOM: usize = 8192;
+
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes `bytes` from the budget if there's room for them, or returns `false` if there isn't.
+    pub(crate) fn try_charge(&self, bytes: usize) -> bool {
+        return self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used + bytes).filter(|&total| total <= self.limit)
+            })
+            .is_ok();
+    }
+
+    /// Gives `bytes` back, once the output they were charged for has been dropped.
+    pub(crate) fn release(&self, bytes: usize) {
+        _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+
+    /// Whether captures are close enough to the limit that output should be dropped to make room:
+    /// within a read's worth of it.
+    pub(crate) fn is_nearly_spent(&self) -> bool {
+        self.used.load(Ordering::Relaxed) + Self::HEADROOM > self.limit
+    }
+}
+
+/// What's left of output that was dropped to stay within the budget.
+pub(crate) fn dropped_note(bytes: usize) -> String {
+    format!(
+        "[{} of output dropped to stay within --total-output-budget]\n",
+        format_bytes(bytes)
+    )
+}
@@ -0,0 +1,141 @@
+use crate::budget::{dropped_note, OutputBudget};
+use crate::encoding;
+use crate::progress::ProgressPattern;
+use encoding_rs::Encoding;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the output budget to have room (the run frees some up each tick, if it
+/// can) before dropping what was read.
+const BUDGET_WAIT: Duration = Duration::from_millis(300);
+
+#[derive(Default)]
+struct Captured {
+    bytes: Vec<u8>,
+    /// When each newline was read, so that streams can be merged line by line.
+    newline_times: Vec<Instant>,
+    /// Where the line that hasn't ended yet starts, in `bytes`.
+    line_start: usize,
+    /// The latest progress a line reported, and when.
+    progress: Option<(Instant, f64)>,
+    /// How much was read but not kept, because the output budget was spent.
+    dropped: usize,
+    /// How much of what was kept was charged to the output budget.
+    charged: usize,
+}
+
+impl Captured {
+    /// Checks each line that has ended since the last call for progress. A `\r` ends a line
+    /// too, since that's how progress bars redraw themselves.
+    fn scan_progress(&mut self, pattern: &ProgressPattern, now: Instant) {
+        while let Some(len) = self.bytes[self.line_start..]
+            .iter()
+            .position(|&b| b == b'\n' || b == b'\r')
+        {
+            let line = String::from_utf8_lossy(&self.bytes[self.line_start..self.line_start + len]);
+            if let Some(progress) = pattern.progress(&line) {
+                self.progress = Some((now, progress));
+            }
+            self.line_start += len + 1;
+        }
+    }
+}
+
+/// Charges `bytes` to the budget, waiting a little for room if there isn't any. Returns `false` if
+/// there still isn't.
+fn wait_for_room(budget: &OutputBudget, bytes: usize) -> bool {
+    let deadline = Instant::now() + BUDGET_WAIT;
+    while !budget.try_charge(bytes) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    return true;
+}
+
+/// Reads a child's stream on a background thread, so that the child never blocks on a full pipe.
+pub(crate) struct Capture {
+    captured: Arc<Mutex<Captured>>,
+    reader: JoinHandle<()>,
+}
+
+impl Capture {
+    /// Starts reading `source`. With a pattern, each line is also checked for progress as it's
+    /// read. With a budget, what's read is charged to it, and dropped if it's spent.
+    pub(crate) fn spawn<R: Read + Send + 'static>(
+        mut source: R,
+        pattern: Option<ProgressPattern>,
+        budget: Option<Arc<OutputBudget>>,
+    ) -> Self {
+        let captured = Arc::new(Mutex::new(Captured::default()));
+        let reader_captured = Arc::clone(&captured);
+        let reader = thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            loop {
+                match source.read(&mut chunk) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        // wait before locking, so that progress can still be read meanwhile
+                        let keep = budget.as_ref().is_none_or(|b| wait_for_room(b, n));
+                        let Ok(mut captured) = reader_captured.lock() else {
+                            break;
+                        };
+                        if !keep {
+                            captured.dropped += n;
+                            continue;
+                        }
+                        if budget.is_some() {
+                            captured.charged += n;
+                        }
+                        let now = Instant::now();
+                        let newlines = chunk[..n].iter().filter(|&&b| b == b'\n').count();
+                        captured.newline_times.extend((0..newlines).map(|_| now));
+                        captured.bytes.extend_from_slice(&chunk[..n]);
+                        if let Some(pattern) = &pattern {
+                            captured.scan_progress(pattern, now);
+                        }
+                    }
+                }
+            }
+        });
+        Capture { captured, reader }
+    }
+
+    /// Whether the stream has closed (or couldn't be read any further), so that everything that
+    /// will be read from it has been.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.reader.is_finished()
+    }
+
+    /// The latest progress that a line reported, and when, if any has.
+    pub(crate) fn progress(&self) -> Option<(Instant, f64)> {
+        self.captured.lock().ok()?.progress
+    }
+
+    /// Everything read from the stream so far (decoded from `encoding`, or UTF-8 by default), along
+    /// with when each of its lines ended. It doesn't wait for the stream to close; see
+    /// [Capture::is_closed]. If some was dropped to stay within the output budget, a note at the end
+    /// says how much. Also returns how many bytes were charged to the budget, for giving them back
+    /// when the output is dropped.
+    pub(crate) fn finish(
+        self,
+        encoding: Option<&'static Encoding>,
+    ) -> (String, Vec<Instant>, usize) {
+        let captured = match self.captured.lock() {
+            Ok(captured) => captured,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut text = encoding::decode(&captured.bytes, encoding);
+        if captured.dropped > 0 {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&dropped_note(captured.dropped));
+        }
+        return (text, captured.newline_times.clone(), captured.charged);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::theme::Theme;
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+lazy_static! {
+    pub(crate) static ref COLORS_REGEX: Regex =
+        Regex::new("\x1b\\[(\\d+)m").expect("Couldn't compile pattern for ASCII color sequences");
+    pub(crate) static ref SGR_REGEX: Regex =
+        Regex::new("\x1b\\[[0-9;]*m").expect("Couldn't compile pattern for SGR sequences");
+    pub(crate) static ref ESCAPES_REGEX: Regex =
+        Regex::new("\x1b(\\[[0-9;?]*[A-Za-z]|\\][^\x07]*\x07)")
+            .expect("Couldn't compile pattern for ANSI escape sequences");
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Color {
+    Normal,
+    Gray,
+    Green,
+    Yellow,
+    Red,
+    Other(i32),
+}
+
+impl Color {
+    pub fn find_all(text: &str) -> Vec<Color> {
+        let mut results = Vec::new();
+        for captures in COLORS_REGEX.captures_iter(text) {
+            let color = match &captures[1] {
+                "0" => Color::Normal,
+                "90" => Color::Gray,
+                "32" => Color::Green,
+                "31" => Color::Red,
+                "33" => Color::Yellow,
+                code => match i32::from_str(code) {
+                    Ok(c) => Color::Other(c),
+                    Err(_) => Color::Normal,
+                },
+            };
+            results.push(color);
+        }
+        return results;
+    }
+
+    pub fn strip_all(text: &str) -> String {
+        COLORS_REGEX.replace_all(text, "").into_owned()
+    }
+
+    /// Strips every color and style sequence, including ones with several parameters (like
+    /// `ESC[1;31m`) that [Color::strip_all] leaves, but no other escape sequences.
+    pub fn strip_sgr(text: &str) -> String {
+        SGR_REGEX.replace_all(text, "").into_owned()
+    }
+
+    /// Strips colors, cursor movement, titles and any other escape sequences.
+    pub fn strip_escapes(text: &str) -> String {
+        ESCAPES_REGEX.replace_all(text, "").into_owned()
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(theme) = Theme::current() {
+            let rgb = match self {
+                Color::Gray => Some(theme.muted),
+                Color::Green => Some(theme.passed),
+                Color::Red => Some(theme.failed),
+                Color::Yellow => Some(theme.warning),
+                Color::Normal | Color::Other(_) => None,
+            };
+            if let Some((r, g, b)) = rgb {
+                return write!(f, "\x1b[38;2;{};{};{}m", r, g, b);
+            }
+        }
+        let code = match self {
+            Color::Normal => 0,
+            Color::Gray => 90,
+            Color::Green => 32,
+            Color::Red => 31,
+            Color::Yellow => 33,
+            Color::Other(n) => *n,
+        };
+        write!(f, "\x1b[{}m", code)
+    }
+}
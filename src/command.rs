@@ -0,0 +1,1301 @@
+use crate::annotations::Annotation;
+use crate::budget::{dropped_note, OutputBudget};
+use crate::clock::Clock;
+use crate::color::Color;
+use crate::container;
+use crate::details_filter;
+use crate::durations::{format_duration, parse_duration};
+use crate::encoding;
+use crate::icons::Icons;
+use crate::isolation::Isolation;
+use crate::labels::SourceLocation;
+use crate::process::{Launcher, ProcessHandle, ProcessLauncher};
+use crate::progress::{progress_bar, ProgressPattern};
+use crate::readiness::ReadinessProbe;
+use crate::retry::RetryBackoff;
+use crate::rules::OutputRules;
+use crate::spec::CommandSpec;
+use crate::structured::{self, ChildFormat};
+use crate::terminal::Terminal;
+use crate::words::{quote_word, split_words};
+use crate::wrap::wrap;
+use encoding_rs::Encoding;
+use std::fmt;
+use std::fmt::{Formatter, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CommandStatus {
+    Unstarted,
+    Running,
+    Finished(i32),
+    Error(String),
+    Skipped(String),
+}
+
+impl CommandStatus {
+    pub fn is_terminal_state(&self) -> bool {
+        match self {
+            CommandStatus::Unstarted | CommandStatus::Running => false,
+            CommandStatus::Finished(_) | CommandStatus::Error(_) | CommandStatus::Skipped(_) => {
+                true
+            }
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            CommandStatus::Unstarted
+                | CommandStatus::Running
+                | CommandStatus::Finished(0)
+                | CommandStatus::Skipped(_)
+        )
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, CommandStatus::Finished(0))
+    }
+}
+
+impl fmt::Display for CommandStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandStatus::Unstarted => write!(f, "not started"),
+            CommandStatus::Running => write!(f, "running"),
+            CommandStatus::Finished(0) => write!(f, "passed"),
+            CommandStatus::Finished(code) => write!(f, "exit {}", code),
+            CommandStatus::Error(e) => write!(f, "error: {}", e),
+            CommandStatus::Skipped(reason) => write!(f, "skipped ({})", reason),
+        }
+    }
+}
+
+/// Why a command that's ready to run hasn't started, as of the last tick. If more than one
+/// applies, it's the one that will resolve last: a command waiting on its dependencies will
+/// still need a job slot after that.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WaitReason {
+    /// Some of the commands it `@needs` haven't passed yet.
+    Dependencies(Vec<String>),
+    /// Nothing is listening yet at this `@wait-for` address.
+    Readiness(String),
+    /// There's no room for it under `--jobs`.
+    Jobs,
+    /// Its `@delay` holds it back until this time.
+    Delay(SystemTime),
+    /// A running command holds this `@mutex`.
+    Mutex(String),
+    /// With staged concurrency, commands in this earlier `@stage` haven't all finished.
+    Stage(usize),
+}
+
+impl WaitReason {
+    /// A short name for the reason, for machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WaitReason::Dependencies(_) => "deps",
+            WaitReason::Readiness(_) => "wait-for",
+            WaitReason::Jobs => "jobs",
+            WaitReason::Delay(_) => "delay",
+            WaitReason::Mutex(_) => "mutex",
+            WaitReason::Stage(_) => "stage",
+        }
+    }
+
+    /// What the summary shows for the reason, like `waiting (deps: build)`. A delay counts down
+    /// to its start by `clock`.
+    pub fn describe(&self, clock: &dyn Clock) -> String {
+        match self {
+            WaitReason::Dependencies(labels) => format!("waiting (deps: {})", labels.join(", ")),
+            WaitReason::Readiness(address) => format!("waiting for {}", address),
+            WaitReason::Jobs => "queued (jobs)".to_string(),
+            WaitReason::Mutex(name) => format!("waiting (mutex: {})", name),
+            WaitReason::Stage(stage) => format!("waiting (stage {})", stage),
+            WaitReason::Delay(until) => {
+                let remaining = until.duration_since(clock.now()).unwrap_or_default();
+                let remaining = Duration::from_secs(remaining.as_secs_f64().ceil() as u64);
+                match remaining.as_secs() {
+                    secs if secs < 60 => format!("starts in {}s", secs),
+                    _ => format!("starts in {}", format_duration(remaining)),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DetailsOptions {
+    pub(crate) fold_repeats: bool,
+    pub(crate) show_all_attempts: bool,
+    /// The width to wrap output lines to, if any.
+    pub(crate) wrap_width: Option<usize>,
+    pub(crate) merge_streams: bool,
+    /// Strip colors from commands' output, though not from our own decorations.
+    pub(crate) strip_child_colors: bool,
+}
+
+/// Everything a command wrote, available once it has finished.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// When each line of stdout and stderr was written, if known.
+    pub(crate) line_times: Option<(Vec<Instant>, Vec<Instant>)>,
+    /// How many bytes capturing it charged to the output budget, which is what dropping it gives
+    /// back. That's the raw bytes read, not the length of the decoded text.
+    pub(crate) charged: usize,
+}
+
+impl CommandOutput {
+    pub fn is_empty(&self) -> bool {
+        self.stdout.is_empty() && self.stderr.is_empty()
+    }
+
+    /// Stdout and stderr interleaved line by line, in the order they were written (as best as we
+    /// can tell, since each stream is read separately). If that's not known, it's stdout and then
+    /// stderr.
+    pub fn merged(&self) -> String {
+        let Some((stdout_times, stderr_times)) = &self.line_times else {
+            return format!("{}{}", self.stdout, self.stderr);
+        };
+        let mut stdout = self
+            .stdout
+            .split_inclusive('\n')
+            .zip(stdout_times)
+            .peekable();
+        let mut stderr = self
+            .stderr
+            .split_inclusive('\n')
+            .zip(stderr_times)
+            .peekable();
+        let mut merged = String::with_capacity(self.stdout.len() + self.stderr.len());
+        loop {
+            let next = match (stdout.peek(), stderr.peek()) {
+                (Some((_, out_time)), Some((_, err_time))) if err_time < out_time => stderr.next(),
+                (Some(_), _) => stdout.next(),
+                (None, _) => stderr.next(),
+            };
+            let Some((line, _)) = next else {
+                break;
+            };
+            merged.push_str(line);
+        }
+        // zip drops each stream's last line if it didn't end in a newline
+        for text in [&self.stdout, &self.stderr] {
+            if !text.ends_with('\n') {
+                merged.push_str(text.rsplit('\n').next().unwrap_or(""));
+            }
+        }
+        return merged;
+    }
+
+    /// How many bytes were captured from stdout and stderr together.
+    pub fn byte_count(&self) -> usize {
+        self.stdout.len() + self.stderr.len()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.stdout.lines().count() + self.stderr.lines().count()
+    }
+}
+
+/// How a command line is turned into a process.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ShellMode {
+    /// Split on whitespace, and run the first word with the rest as arguments.
+    #[default]
+    Direct,
+    /// `sh -c '<line>'`
+    Shell,
+    /// `$SHELL -lc '<line>'`, so that the user's login environment (PATH, etc.) is set up.
+    LoginShell,
+}
+
+/// A finished attempt of a command that was then retried.
+#[derive(Clone, Debug)]
+pub struct Attempt {
+    pub started_at: Option<SystemTime>,
+    pub finished_at: Option<SystemTime>,
+    pub status: CommandStatus,
+    pub output: Option<CommandOutput>,
+}
+
+impl Attempt {
+    pub fn duration(&self) -> Option<Duration> {
+        match (self.started_at, self.finished_at) {
+            (Some(started), Some(finished)) => {
+                Some(finished.duration_since(started).unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct CommandDesc {
+    pub(crate) command_strs: Vec<String>,
+    pub(crate) line: String,
+    pub(crate) shell: ShellMode,
+    /// The image to run the command in with `docker run`, from `--docker`.
+    pub(crate) container: Option<String>,
+    launcher: Arc<dyn Launcher>,
+    process: Option<Box<dyn ProcessHandle>>,
+    pub(crate) status: CommandStatus,
+    started_at: Option<SystemTime>,
+    finished_at: Option<SystemTime>,
+    output: Option<CommandOutput>,
+    /// Shared by every command, with `--total-output-budget`.
+    pub(crate) output_budget: Option<Arc<OutputBudget>>,
+    /// Whether the output was dropped to make room in the budget.
+    output_dropped: bool,
+    pub(crate) weight: usize,
+    pub(crate) retries: usize,
+    pub(crate) retry_backoff: RetryBackoff,
+    attempts: Vec<Attempt>,
+    retry_at: Option<SystemTime>,
+    /// From `@delay`: how long to hold the command back once it could otherwise start.
+    pub(crate) delay: Option<Duration>,
+    delay_until: Option<SystemTime>,
+    pub(crate) wait_reason: Option<WaitReason>,
+    pub(crate) rules: OutputRules,
+    rule_note: Option<String>,
+    pub(crate) group: Option<String>,
+    /// Set by `@timeout`, or when the command starts. Zero means it never times out.
+    pub(crate) timeout: Option<Duration>,
+    /// Once the command has run this long, show how long in yellow (and at twice this, red).
+    pub(crate) warn_slow: Option<Duration>,
+    /// Show the process ID while the command runs.
+    pub(crate) show_pid: bool,
+    /// Prefixes to leave off the label in the summary and progress lines, from `--strip-prefix`.
+    pub(crate) strip_prefixes: Vec<String>,
+    pub(crate) depends_on: Vec<String>,
+    pub(crate) abort_if: Vec<String>,
+    /// Which batch it runs in with staged concurrency, from `@stage`.
+    pub(crate) stage: usize,
+    /// Names of resources it needs to itself while it runs, from `@mutex`, in order.
+    pub(crate) mutexes: Vec<String>,
+    /// Checked when the command is about to start; if any holds, it's skipped instead.
+    skip_if: Vec<fn() -> bool>,
+    /// `host:port` addresses that have to accept connections before the command starts.
+    pub(crate) wait_for: Vec<String>,
+    readiness: Option<ReadinessProbe>,
+    pub(crate) env: Vec<(String, String)>,
+    /// A shell command whose `KEY=VALUE` output lines are added to the environment at each start.
+    pub(crate) env_from: Option<String>,
+    /// The command's own `HOME` and/or `TMPDIR`, from `@isolate`.
+    pub(crate) isolation: Option<Isolation>,
+    /// What `env_from` printed, for the current attempt.
+    sourced_env: Vec<(String, String)>,
+    pub(crate) cwd: Option<PathBuf>,
+    pub(crate) allow_failure: bool,
+    /// Only run the command if the user says so, from `@confirm`.
+    pub(crate) confirm: bool,
+    /// Where it was defined, if it came from an input like stdin or a config file.
+    pub(crate) source: Option<SourceLocation>,
+    /// What the line looks like, if the safety check flagged it as destructive.
+    pub(crate) destructive: Option<String>,
+    /// Why it was skipped instead of confirmed, if it was. That holds for every later run too.
+    pub(crate) declined: Option<String>,
+    /// A shell pipeline that its output goes through before it's shown in the details, from
+    /// `@details-filter`.
+    pub(crate) details_filter: Option<String>,
+    /// How to find the command's progress in its output, from `@progress`.
+    pub(crate) progress_pattern: Option<ProgressPattern>,
+    /// What its output is encoded in, from `@encoding`, if not UTF-8.
+    pub(crate) encoding: Option<&'static Encoding>,
+    /// What its output is, from `@output-format`, so that JSON can be shown readably.
+    pub(crate) child_format: ChildFormat,
+    /// What its status cycles through in the summary, from `@icon`.
+    pub(crate) icons: Icons,
+    /// How long it's expected to take, from `@expect`, for a progress bar when there's no
+    /// `@progress`.
+    pub(crate) expected: Option<Duration>,
+    /// The latest progress it reported while running, from 0 to 1.
+    progress: Option<f64>,
+    /// For a command from a heredoc, its whole script (which is also its `line`).
+    pub(crate) script: Option<String>,
+    /// What to call the command instead of its command line, as from `--label`.
+    pub(crate) label: Option<String>,
+}
+
+impl CommandDesc {
+    const SHELL_METACHARACTERS: [char; 6] = ['|', '>', '<', '&', '$', '*'];
+
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            line: command.join(" "),
+            shell: ShellMode::Direct,
+            container: None,
+            command_strs: command,
+            launcher: Arc::new(ProcessLauncher),
+            process: None,
+            status: CommandStatus::Unstarted,
+            started_at: None,
+            finished_at: None,
+            output: None,
+            output_budget: None,
+            output_dropped: false,
+            weight: 1,
+            retries: 0,
+            retry_backoff: RetryBackoff::default(),
+            attempts: Vec::new(),
+            retry_at: None,
+            delay: None,
+            delay_until: None,
+            wait_reason: None,
+            rules: OutputRules::default(),
+            rule_note: None,
+            group: None,
+            timeout: None,
+            warn_slow: None,
+            show_pid: false,
+            strip_prefixes: Vec::new(),
+            depends_on: Vec::new(),
+            mutexes: Vec::new(),
+            stage: 0,
+            abort_if: Vec::new(),
+            skip_if: Vec::new(),
+            wait_for: Vec::new(),
+            readiness: None,
+            env: Vec::new(),
+            env_from: None,
+            isolation: None,
+            sourced_env: Vec::new(),
+            cwd: None,
+            allow_failure: false,
+            confirm: false,
+            destructive: None,
+            declined: None,
+            source: None,
+            details_filter: None,
+            expected: None,
+            child_format: ChildFormat::default(),
+            encoding: None,
+            icons: Icons::default(),
+            progress_pattern: None,
+            progress: None,
+            script: None,
+            label: None,
+        }
+    }
+
+    /// Applies an annotation to this command. Returns `Ok(false)` if the annotation isn't one
+    /// that commands understand.
+    pub(crate) fn apply_annotation(&mut self, annotation: &Annotation) -> Result<bool, String> {
+        match annotation.key.as_str() {
+            "weight" => {
+                self.weight = annotation.parse_value()?;
+                if self.weight == 0 {
+                    return Err("@weight must be at least 1".to_string());
+                }
+            }
+            "retries" => self.retries = annotation.parse_value()?,
+            "require-output" => self.rules.require_output = true,
+            "fail-empty-output" => self.rules.fail_empty_output = true,
+            "allow-failure" => self.allow_failure = true,
+            "confirm" => self.confirm = true,
+            "progress" => self.progress_pattern = Some(annotation.value()?.parse()?),
+            "env" => {
+                for pair in split_words(annotation.value()?)? {
+                    let Some((key, value)) = pair.split_once('=') else {
+                        return Err(format!("@env needs KEY=VALUE, not {:?}", pair));
+                    };
+                    self.env.push((key.to_string(), value.to_string()));
+                }
+            }
+            "cwd" => self.cwd = Some(PathBuf::from(annotation.value()?)),
+            "env-from" => self.env_from = Some(annotation.value()?.to_string()),
+            "details-filter" => self.details_filter = Some(annotation.value()?.to_string()),
+            "isolate" => self.isolation = Some(Isolation::parse(annotation.value()?)?),
+            "group" => self.group = Some(annotation.value()?.to_string()),
+            "needs" => {
+                let labels = annotation.value()?.split(',').map(str::trim);
+                self.depends_on.extend(labels.map(str::to_string));
+            }
+            "stage" => self.stage = annotation.parse_value()?,
+            "mutex" => {
+                let names = annotation.value()?.split(',').map(str::trim);
+                self.mutexes.extend(names.map(str::to_string));
+                // always claimed in the same order, so two commands can't each hold what the other
+                // is waiting for
+                self.mutexes.sort();
+                self.mutexes.dedup();
+            }
+            "wait-for" => {
+                let addresses = annotation.value()?.split(',').map(str::trim);
+                self.wait_for.extend(addresses.map(str::to_string));
+            }
+            "abort-if" => {
+                let labels = annotation.value()?.split(',').map(str::trim);
+                self.abort_if.extend(labels.map(str::to_string));
+            }
+            "retry-backoff" => self.retry_backoff = annotation.parse_value()?,
+            "timeout" => self.timeout = Some(parse_duration(annotation.value()?)?),
+            "warn-slow" => self.warn_slow = Some(parse_duration(annotation.value()?)?),
+            "delay" => self.delay = Some(parse_duration(annotation.value()?)?),
+            "expect" => self.expected = Some(parse_duration(annotation.value()?)?),
+            "output-format" => self.child_format = annotation.parse_value()?,
+            "encoding" => self.encoding = Some(encoding::parse(annotation.value()?)?),
+            "icon" => self.icons.set_running(annotation.value()?)?,
+            "success-when" => {
+                self.rules.success_when = Some(OutputRules::compile(annotation.value()?)?)
+            }
+            "expect-output" => self.rules.expected_stdout = Some(annotation.value()?.to_string()),
+            "expect-output-file" => {
+                let path = annotation.value()?;
+                let expected = std::fs::read_to_string(path)
+                    .map_err(|e| format!("@expect-output-file {}: {}", path, e))?;
+                self.rules.expected_stdout = Some(expected);
+            }
+            "failure-when" => {
+                self.rules.failure_when = Some(OutputRules::compile(annotation.value()?)?)
+            }
+            _ => return Ok(false),
+        }
+        return Ok(true);
+    }
+
+    /// Starts this command's attempts with the given launcher, instead of as child processes.
+    pub fn with_launcher(mut self, launcher: Arc<dyn Launcher>) -> Self {
+        self.launcher = launcher;
+        self
+    }
+
+    /// Before each start, runs `command` in a shell and adds each `KEY=VALUE` line it prints
+    /// (`export KEY=VALUE` works too) to this command's environment. Variables set with `@env`
+    /// take precedence. If the helper fails, so does this command, without being started.
+    pub fn with_env_from_command(mut self, command: &str) -> Self {
+        self.env_from = Some(command.to_string());
+        self
+    }
+
+    /// Only starts this command once the commands with these labels have all passed. If any of
+    /// them fails or is skipped, this command is skipped.
+    pub fn depends_on(&mut self, labels: &[&str]) -> &mut Self {
+        self.depends_on
+            .extend(labels.iter().map(|label| label.to_string()));
+        self
+    }
+
+    /// If any of the commands with these labels fails, skips this command, killing it if it's
+    /// already running.
+    pub fn abort_if(&mut self, labels: &[&str]) -> &mut Self {
+        self.abort_if
+            .extend(labels.iter().map(|label| label.to_string()));
+        self
+    }
+
+    /// Skips the command, rather than starting it, if `condition` holds when it's about to start:
+    /// for instance, `skip_if(|| !Path::new("/usr/bin/docker").exists())`.
+    pub fn skip_if(&mut self, condition: fn() -> bool) -> &mut Self {
+        self.skip_if.push(condition);
+        self
+    }
+
+    /// The labels of the commands this one needs, from `@needs` or [CommandDesc::depends_on].
+    pub fn dependencies(&self) -> &[String] {
+        &self.depends_on
+    }
+
+    /// How this command will be run.
+    pub fn spec(&self) -> CommandSpec {
+        CommandSpec {
+            args: self.command_strs.clone(),
+            shell: self.shell,
+            container: self.container.clone(),
+            cwd: self.cwd.clone(),
+            env: (self.isolation.iter().flat_map(Isolation::env))
+                .chain(self.env.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// The group set with `@group`, if any.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    /// Earlier attempts of this command, if it was retried. The current (or final) attempt is
+    /// reflected by [CommandDesc::status] and friends.
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// Whether the scheduler may start this command now, as far as the command itself is
+    /// concerned.
+    pub(crate) fn ready_to_start(&self, now: SystemTime) -> bool {
+        self.status == CommandStatus::Unstarted && self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    /// What the command is called everywhere: in the dashboard and reports, and by `@needs`, the
+    /// history and so on. It's the command line, unless [CommandDesc::with_label] set one.
+    pub fn label(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => self.command_strs.join(" "),
+        }
+    }
+
+    /// The label with newlines and other control characters escaped (as `\n`, `\t` and so on), so
+    /// that it stays on one line.
+    pub(crate) fn one_line_label(&self) -> String {
+        let label = self.label();
+        if !label.contains(char::is_control) {
+            return label;
+        }
+        return label
+            .chars()
+            .map(|c| match c {
+                c if c.is_control() => c.escape_default().to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+    }
+
+    /// The shell metacharacters in the line (like `>` or `*`), if it isn't run by a shell, so they
+    /// will be passed to the command as is. Empty if there aren't any, or it is run by a shell.
+    pub fn shell_metacharacters(&self) -> Vec<char> {
+        if self.shell != ShellMode::Direct {
+            return Vec::new();
+        }
+        return (Self::SHELL_METACHARACTERS.iter())
+            .copied()
+            .filter(|&c| self.line.contains(c))
+            .collect();
+    }
+
+    /// The one-line label, without the longest of `strip_prefixes` that it starts with, for the
+    /// summary. Only what's shown changes; the command still runs as it's written.
+    pub(crate) fn summary_label(&self) -> String {
+        let label = self.one_line_label();
+        let prefix = (self.strip_prefixes.iter())
+            .filter(|p| label.starts_with(p.as_str()) && label.len() > p.len())
+            .max_by_key(|p| p.len());
+        match prefix {
+            Some(prefix) => label[prefix.len()..].to_string(),
+            None => label,
+        }
+    }
+
+    /// Calls the command something other than its command line.
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn status(&self) -> &CommandStatus {
+        &self.status
+    }
+
+    /// Why the command hasn't started yet, if something is holding it up.
+    pub fn wait_reason(&self) -> Option<&WaitReason> {
+        self.wait_reason.as_ref()
+    }
+
+    pub fn output(&self) -> Option<&CommandOutput> {
+        self.output.as_ref()
+    }
+
+    pub(crate) fn stdout(&self) -> &str {
+        self.output.as_ref().map_or("", |o| o.stdout.as_str())
+    }
+
+    pub(crate) fn stderr(&self) -> &str {
+        self.output.as_ref().map_or("", |o| o.stderr.as_str())
+    }
+
+    /// For a command from a heredoc, the whole script; its label is just the first line.
+    pub fn script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+
+    /// Whether this command failed, but was marked with `@allow-failure`.
+    pub fn is_allowed_failure(&self) -> bool {
+        self.allow_failure && self.status.is_error()
+    }
+
+    /// How far along the command said it was the last time its output matched its `@progress`
+    /// pattern, from 0 to 1. It stays at that until the output matches again.
+    pub fn progress(&self) -> Option<f64> {
+        self.progress
+    }
+
+    /// The process ID of the command's latest attempt, if it has started one.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref()?.pid()
+    }
+
+    /// The `@wait-for` address that isn't accepting connections yet, if any. The first call
+    /// starts probing them in the background.
+    pub(crate) fn unready_address(&mut self) -> Option<String> {
+        if self.wait_for.is_empty() {
+            return None;
+        }
+        let wait_for = &self.wait_for;
+        let probe = self
+            .readiness
+            .get_or_insert_with(|| ReadinessProbe::spawn(wait_for.clone()));
+        return probe.pending();
+    }
+
+    /// If `@delay` is still holding the command back, until when. The delay counts from the first
+    /// time this is called, which is when nothing else is holding it back.
+    pub(crate) fn delayed_until(&mut self, now: SystemTime) -> Option<SystemTime> {
+        let until = *self.delay_until.get_or_insert(now + self.delay?);
+        return (until > now).then_some(until);
+    }
+
+    /// Whether the user has to confirm this command before it runs, from `@confirm` or because
+    /// it looks destructive.
+    pub fn needs_confirmation(&self) -> bool {
+        self.confirm || self.destructive.is_some()
+    }
+
+    /// If the safety check flagged this command, what it looks like (such as `rm -rf /`).
+    pub fn destructive_match(&self) -> Option<&str> {
+        self.destructive.as_deref()
+    }
+
+    /// Whether this command failed in a way that fails the whole run.
+    pub fn fails_run(&self) -> bool {
+        self.status.is_error() && !self.allow_failure
+    }
+
+    /// If a `success-when` or `failure-when` rule overrode the exit code, which one and why.
+    pub fn rule_note(&self) -> Option<&str> {
+        self.rule_note.as_deref()
+    }
+
+    pub fn started_at(&self) -> Option<SystemTime> {
+        self.started_at
+    }
+
+    pub fn finished_at(&self) -> Option<SystemTime> {
+        self.finished_at
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        match (self.started_at, self.finished_at) {
+            (Some(started), Some(finished)) => {
+                Some(finished.duration_since(started).unwrap_or_default())
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn check(&mut self, clock: &dyn Clock) {
+        if self.status.is_terminal_state() {
+            return;
+        }
+        let Some(process) = &mut self.process else {
+            return;
+        };
+        match process.try_wait() {
+            Ok(Some(code)) => {
+                self.finished_at = Some(clock.now());
+                self.finish_output();
+                self.status = match code {
+                    None => CommandStatus::Error("Error reading status code".to_string()),
+                    Some(code) => CommandStatus::Finished(code),
+                };
+                if let Some((status, note)) = self.rules.apply(&self.status, self.output.as_ref()) {
+                    self.status = status;
+                    self.rule_note = Some(note);
+                }
+            }
+            Ok(None) => {
+                if let Some(progress) = process.progress() {
+                    self.progress = Some(progress);
+                }
+                let now = clock.now();
+                let elapsed = self
+                    .started_at
+                    .and_then(|started| now.duration_since(started).ok());
+                let timeout = self.timeout.filter(|t| !t.is_zero());
+                if let (Some(timeout), Some(elapsed)) = (timeout, elapsed) {
+                    if elapsed >= timeout {
+                        process.kill();
+                        self.finished_at = Some(now);
+                        self.finish_output();
+                        self.status = CommandStatus::Error(format!(
+                            "timed out after {}",
+                            format_duration(timeout)
+                        ));
+                    }
+                }
+            }
+            Err(e) => {
+                self.finished_at = Some(clock.now());
+                self.finish_output();
+                self.status = CommandStatus::Error(e.to_string());
+            }
+        }
+        if self.status.is_error() && self.attempts.len() < self.retries {
+            self.schedule_retry(clock);
+        }
+    }
+
+    fn schedule_retry(&mut self, clock: &dyn Clock) {
+        self.attempts.push(Attempt {
+            started_at: self.started_at.take(),
+            finished_at: self.finished_at.take(),
+            status: std::mem::replace(&mut self.status, CommandStatus::Unstarted),
+            output: self.output.take(),
+        });
+        self.rule_note = None;
+        self.process = None;
+        self.retry_at = Some(clock.now() + self.retry_backoff.delay(self.attempts.len()));
+    }
+
+    fn finish_output(&mut self) {
+        if let Some(process) = &mut self.process {
+            self.output = Some(process.finish_output());
+        }
+    }
+
+    /// Replaces the command's output with a note that it was dropped, to make room in the output
+    /// budget. Returns how much that freed up.
+    pub(crate) fn drop_output(&mut self) -> usize {
+        let Some(output) = self.output.as_mut().filter(|_| !self.output_dropped) else {
+            return 0;
+        };
+        let freed = output.charged;
+        if freed > 0 {
+            *output = CommandOutput {
+                stdout: dropped_note(freed),
+                ..CommandOutput::default()
+            };
+            self.output_dropped = true;
+        }
+        return freed;
+    }
+
+    /// Forgets any previous run, so that the command can start again from scratch. A command that
+    /// wasn't confirmed stays skipped.
+    pub(crate) fn reset(&mut self) {
+        if let Some(budget) = &self.output_budget {
+            // the output about to be forgotten no longer counts against the budget
+            let outputs = self.attempts.iter().filter_map(|a| a.output.as_ref());
+            let current = self.output.as_ref().filter(|_| !self.output_dropped);
+            budget.release(outputs.chain(current).map(|o| o.charged).sum());
+        }
+        self.process = None;
+        self.status = CommandStatus::Unstarted;
+        self.started_at = None;
+        self.finished_at = None;
+        self.output = None;
+        self.output_dropped = false;
+        self.attempts.clear();
+        self.retry_at = None;
+        self.delay_until = None;
+        self.wait_reason = None;
+        self.progress = None;
+        self.rule_note = None;
+        if let Some(reason) = &self.declined {
+            self.status = CommandStatus::Skipped(reason.clone());
+        }
+    }
+
+    /// Moves a command that hasn't started straight to `Error`, without running it.
+    pub(crate) fn fail(&mut self, error: String, clock: &dyn Clock) {
+        self.started_at = Some(clock.now());
+        self.finished_at = self.started_at;
+        self.status = CommandStatus::Error(error);
+    }
+
+    /// Moves a command that hasn't finished to `Skipped`, killing it if it's running.
+    pub(crate) fn skip(&mut self, reason: String, clock: &dyn Clock) {
+        if self.status.is_terminal_state() {
+            return;
+        }
+        if let Some(process) = &mut self.process {
+            process.kill();
+            self.finished_at = Some(clock.now());
+            self.finish_output();
+        }
+        self.status = CommandStatus::Skipped(reason);
+    }
+
+    /// If the command is running and has passed its `warn_slow` threshold, how long it has been
+    /// running, and the color to show that in.
+    fn slowness(&self, clock: &dyn Clock) -> Option<(Duration, Color)> {
+        let threshold = self.warn_slow?;
+        if self.status != CommandStatus::Running {
+            return None;
+        }
+        let elapsed = clock.now().duration_since(self.started_at?).ok()?;
+        return match elapsed {
+            e if e >= threshold * 2 => Some((elapsed, Color::Red)),
+            e if e >= threshold => Some((elapsed, Color::Yellow)),
+            _ => None,
+        };
+    }
+
+    /// How far along a running command is by the clock, if it has an `@expect`: the time it's
+    /// been running over the time it's expected to take, but never more than 99% until it's done.
+    fn expected_progress(&self, clock: &dyn Clock) -> Option<(f64, Duration)> {
+        let expected = self.expected.filter(|e| !e.is_zero())?;
+        let elapsed = clock.now().duration_since(self.started_at?).ok()?;
+        let fraction = elapsed.as_secs_f64() / expected.as_secs_f64();
+        return Some((fraction.min(0.99), expected));
+    }
+
+    /// The command's line on the dashboard and in the details, with how long it's been running
+    /// (and how long until it's retried or starts) by `clock`.
+    pub(crate) fn print_summary(&self, tick: usize, clock: &dyn Clock, out: &mut impl Write) {
+        let retry_wait = self
+            .retry_at
+            .and_then(|at| at.duration_since(clock.now()).ok());
+        let (status, color) = match &self.status {
+            CommandStatus::Unstarted => match retry_wait {
+                Some(wait) => (
+                    format!("retrying in {}", format_duration(wait)),
+                    Color::Yellow,
+                ),
+                None => (self.icons.unstarted(tick).to_string(), Color::Gray),
+            },
+            CommandStatus::Running => {
+                let spinner = self.icons.running(tick);
+                match (self.progress, self.expected_progress(clock)) {
+                    (Some(progress), _) => (
+                        format!("{} {}", spinner, progress_bar(progress)),
+                        Color::Normal,
+                    ),
+                    (None, Some((progress, expected))) => (
+                        format!(
+                            "{} {} [~{}]",
+                            spinner,
+                            progress_bar(progress),
+                            format_duration(expected)
+                        ),
+                        Color::Normal,
+                    ),
+                    (None, None) => (spinner.to_string(), Color::Normal),
+                }
+            }
+            CommandStatus::Finished(0) => ("OK".to_string(), Color::Green),
+            _ if self.is_allowed_failure() => ("FAILED (allowed)".to_string(), Color::Yellow),
+            CommandStatus::Finished(_) => ("FAILED".to_string(), Color::Red),
+            CommandStatus::Error(_) => ("FAILED".to_string(), Color::Red),
+            CommandStatus::Skipped(_) => ("SKIPPED".to_string(), Color::Gray),
+        };
+        _ = write!(out, "{}: {}{}\x1b[0m", self.summary_label(), color, status);
+        if self.show_pid && self.status == CommandStatus::Running {
+            if let Some(pid) = self.pid() {
+                _ = write!(out, " {}[pid {}]{}", Color::Gray, pid, Color::Normal);
+            }
+        }
+        if let Some((elapsed, color)) = self.slowness(clock) {
+            _ = write!(
+                out,
+                " {}({}){}",
+                color,
+                format_duration(elapsed),
+                Color::Normal
+            );
+        }
+        if let Some(reason) = &self.wait_reason {
+            let reason = reason.describe(clock);
+            _ = write!(out, " {}{}{}", Color::Gray, reason, Color::Normal);
+        }
+        if self.status.is_success() && self.rule_note.is_some() {
+            _ = write!(out, " {}(via success-when){}", Color::Gray, Color::Normal);
+        }
+        let lines = self.output.as_ref().map_or(0, |o| o.line_count());
+        if self.status.is_success() && lines > 0 {
+            let plural = if lines == 1 { "" } else { "s" };
+            _ = write!(
+                out,
+                " {}({} line{}){}",
+                Color::Gray,
+                lines,
+                plural,
+                Color::Normal
+            );
+        }
+        if !self.attempts.is_empty() {
+            _ = write!(
+                out,
+                " {}(attempt {}/{}){}",
+                Color::Gray,
+                self.attempts.len() + 1,
+                self.retries + 1,
+                Color::Normal
+            );
+        }
+    }
+
+    pub(crate) fn print_details(&self, options: &DetailsOptions, out: &mut Terminal) {
+        if !self.status.is_error() {
+            return;
+        }
+        let total = self.attempts.len() + 1;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            if options.show_all_attempts {
+                _ = writeln!(
+                    out,
+                    "{}attempt {}/{}: {}{}",
+                    Color::Gray,
+                    i + 1,
+                    total,
+                    attempt.status,
+                    Color::Normal
+                );
+                if let Some(output) = &attempt.output {
+                    self.print_streams(output, options, out);
+                }
+            } else {
+                let lines = attempt.output.as_ref().map_or(0, |o| o.line_count());
+                _ = writeln!(
+                    out,
+                    "{}attempt {}/{}: {}, {} line{} — pass --show-all-attempts to expand{}",
+                    Color::Gray,
+                    i + 1,
+                    total,
+                    attempt.status,
+                    lines,
+                    if lines == 1 { "" } else { "s" },
+                    Color::Normal
+                );
+            }
+        }
+        if let Some(note) = &self.rule_note {
+            _ = writeln!(out, "{}{}{}", Color::Gray, note, Color::Normal);
+            let diff = (self.output.as_ref()).and_then(|o| self.rules.expected_output_diff(o));
+            if let (Some(diff), false) = (diff, self.status.is_success()) {
+                _ = write!(out, "{}", diff);
+            }
+        }
+        if total > 1 {
+            _ = writeln!(
+                out,
+                "{}attempt {}/{}: {}{}",
+                Color::Gray,
+                total,
+                total,
+                self.status,
+                Color::Normal
+            );
+        }
+        match &self.process {
+            None => {
+                _ = write!(
+                    out,
+                    "{}!{} Failed to start process",
+                    Color::Red,
+                    Color::Normal
+                );
+                match &self.status {
+                    CommandStatus::Error(e) => _ = writeln!(out, ": {}", e),
+                    _ => _ = writeln!(out),
+                }
+            }
+            Some(_) => {
+                if let Some(output) = &self.output {
+                    self.print_streams(output, options, out);
+                }
+            }
+        }
+    }
+
+    /// Stdout in full and then stderr in full, or both merged if `merge_streams` is set. With a
+    /// `@details-filter`, what's printed is what the filter makes of that, unless it fails.
+    fn print_streams(&self, output: &CommandOutput, options: &DetailsOptions, out: &mut Terminal) {
+        if let Some(filter) = &self.details_filter {
+            let text = if options.merge_streams || output.stdout.is_empty() {
+                output.merged()
+            } else if output.stdout.ends_with('\n') {
+                format!("{}{}", output.stdout, output.stderr)
+            } else {
+                format!("{}\n{}", output.stdout, output.stderr)
+            };
+            match details_filter::apply(filter, &text, self.cwd.as_deref()) {
+                Ok(filtered) => return self.print_text(&filtered, options, out),
+                Err(e) => {
+                    _ = writeln!(
+                        out,
+                        "{}! details filter {:?} failed ({}), so this is the unfiltered output{}",
+                        Color::Yellow,
+                        filter,
+                        e,
+                        Color::Normal
+                    );
+                }
+            }
+        }
+        if options.merge_streams {
+            self.print_text(&output.merged(), options, out);
+        } else {
+            self.print_text(&output.stdout, options, out);
+            self.print_text(&output.stderr, options, out);
+        }
+    }
+
+    /// Prints output, with JSON lines rendered readably (see [structured::render]).
+    fn print_text(&self, text: &str, options: &DetailsOptions, out: &mut Terminal) {
+        match structured::render(text, self.child_format) {
+            Some(rendered) => CommandDesc::print_output(&rendered, options, out),
+            None => CommandDesc::print_output(text, options, out),
+        }
+    }
+
+    fn print_output(str: &str, options: &DetailsOptions, out: &mut Terminal) {
+        let last_color = Color::Normal;
+        let stripped;
+        let str = if options.strip_child_colors {
+            stripped = Color::strip_sgr(str);
+            stripped.as_str()
+        } else {
+            str
+        };
+        if !str.is_empty() {
+            let lines: Vec<(&str, usize)> = if options.fold_repeats {
+                Self::fold_repeats(str.split('\n'))
+            } else {
+                str.split('\n').map(|line| (line, 1)).collect()
+            };
+            for (line, repeats) in lines {
+                let colors = Color::find_all(line);
+                let quote_color = match colors.len() {
+                    0 => Color::Normal,
+                    1 => colors[0],
+                    _ => Color::Yellow,
+                };
+                let rows = match options.wrap_width {
+                    Some(width) => wrap(line, width.saturating_sub(2), width.saturating_sub(4)),
+                    None => vec![line.to_string()],
+                };
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        _ = writeln!(out);
+                    }
+                    let indent = if i == 0 { "" } else { "  " };
+                    _ = write!(out, "{}│{} {}{}", quote_color, last_color, indent, row);
+                }
+                if repeats > 1 {
+                    _ = write!(out, "{} (×{}){}", Color::Gray, repeats, Color::Normal);
+                }
+                _ = writeln!(out);
+            }
+        }
+    }
+
+    fn fold_repeats<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<(&'a str, usize)> {
+        let mut folded: Vec<(&str, usize)> = Vec::new();
+        for line in lines {
+            match folded.last_mut() {
+                Some((previous, count)) if *previous == line => *count += 1,
+                _ => folded.push((line, 1)),
+            }
+        }
+        return folded;
+    }
+
+    /// The process to run. With `--docker`, that's a `docker run` of the line in a container
+    /// named `container_name`.
+    pub(crate) fn build_command(&self, container_name: Option<&str>) -> Option<Command> {
+        let (command_name, command_args) = self.command_strs.split_first()?;
+        if let (Some(image), Some(name)) = (&self.container, container_name) {
+            return Some(self.build_docker_command(image, Some(name)));
+        }
+        let mut command = match self.shell {
+            ShellMode::Direct => {
+                let mut command = Command::new(command_name);
+                command.args(command_args);
+                command
+            }
+            ShellMode::Shell => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(&self.line);
+                command
+            }
+            ShellMode::LoginShell => {
+                let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+                let mut command = Command::new(shell);
+                command.arg("-lc").arg(&self.line);
+                command
+            }
+        };
+        if let Some(isolation) = &self.isolation {
+            command.envs(isolation.env());
+        }
+        command.envs(self.sourced_env.iter().map(|(key, value)| (key, value)));
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        return Some(command);
+    }
+
+    fn build_docker_command(&self, image: &str, name: Option<&str>) -> Command {
+        let env: Vec<(String, String)> = (self.isolation.iter().flat_map(Isolation::env))
+            .chain(self.sourced_env.iter().cloned())
+            .chain(self.env.iter().cloned())
+            .collect();
+        let here = std::env::current_dir().unwrap_or_default();
+        let workdir = match &self.cwd {
+            Some(cwd) => here.join(cwd),
+            None => here.clone(),
+        };
+        let mut mounts = vec![here.as_path()];
+        if !workdir.starts_with(&here) {
+            mounts.push(&workdir);
+        }
+        if let Some(isolation) = &self.isolation {
+            mounts.push(isolation.dir());
+        }
+        return container::docker_run(image, name, &self.line, &env, &workdir, &mounts);
+    }
+
+    /// A line of shell script that runs the command the way it last ran here: in its directory,
+    /// with its environment (including what `@env-from` gave it), and in its shell or container.
+    pub(crate) fn script_line(&self) -> Option<String> {
+        let command = match &self.container {
+            Some(image) if !self.command_strs.is_empty() => self.build_docker_command(image, None),
+            _ => self.build_command(None)?,
+        };
+        let mut steps = Vec::new();
+        if let Some(isolation) = &self.isolation {
+            // the run's own directories are usually gone by now
+            let dirs = isolation.env().into_iter().map(|(_, dir)| quote_word(&dir));
+            steps.push(format!("mkdir -p {}", dirs.collect::<Vec<_>>().join(" ")));
+        }
+        if let Some(dir) = command.get_current_dir() {
+            steps.push(format!("cd {}", quote_word(&dir.to_string_lossy())));
+        }
+        let mut words = Vec::new();
+        let env: Vec<String> = command
+            .get_envs()
+            .filter_map(|(key, value)| Some((key, value?)))
+            .map(|(key, value)| {
+                quote_word(&format!(
+                    "{}={}",
+                    key.to_string_lossy(),
+                    value.to_string_lossy()
+                ))
+            })
+            .collect();
+        if !env.is_empty() {
+            words.push("env".to_string());
+            words.extend(env);
+        }
+        words.push(quote_word(&command.get_program().to_string_lossy()));
+        words.extend(command.get_args().map(|a| quote_word(&a.to_string_lossy())));
+        steps.push(words.join(" "));
+        return Some(format!("({})", steps.join(" && ")));
+    }
+
+    /// Runs the `env_from` helper, and parses what it prints.
+    fn source_env(&self, helper: &str) -> Result<Vec<(String, String)>, String> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(helper);
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        let output = command
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("env-from {:?}: {}", helper, e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let mut error = format!("env-from {:?} failed ({})", helper, output.status);
+            if let Some(reason) = stderr.lines().find(|l| !l.trim().is_empty()) {
+                error = format!("{}: {}", error, reason.trim());
+            }
+            return Err(error);
+        }
+        return parse_env_lines(&String::from_utf8_lossy(&output.stdout))
+            .map_err(|e| format!("env-from {:?}: {}", helper, e));
+    }
+
+    /// Runs the command again in the foreground, with its stdio inherited from ours, and replaces
+    /// the previous outcome with this one's.
+    pub(crate) fn rerun_attached(&mut self, clock: &dyn Clock) {
+        let container_name = self.container.as_ref().map(|_| container::unique_name());
+        let Some(mut command) = self.build_command(container_name.as_deref()) else {
+            return;
+        };
+        self.process = None;
+        self.output = None;
+        self.rule_note = None;
+        self.started_at = Some(clock.now());
+        self.status = match command.status() {
+            Ok(status) => match status.code() {
+                Some(code) => CommandStatus::Finished(code),
+                None => CommandStatus::Error("Error reading status code".to_string()),
+            },
+            Err(e) => CommandStatus::Error(e.to_string()),
+        };
+        self.finished_at = Some(clock.now());
+    }
+
+    pub(crate) fn start(&mut self, clock: &dyn Clock) {
+        if self.skip_if.iter().any(|condition| condition()) {
+            self.skip("its skip_if condition held".to_string(), clock);
+            return;
+        }
+        if self.command_strs.is_empty() {
+            self.fail("empty command line".to_string(), clock);
+            return;
+        }
+        self.retry_at = None;
+        self.progress = None;
+        self.started_at = Some(clock.now());
+        if let Some(isolation) = &self.isolation {
+            if let Err(e) = isolation.create() {
+                let error = format!("couldn't create {}: {}", isolation.dir().display(), e);
+                self.fail(error, clock);
+                return;
+            }
+        }
+        if let Some(helper) = &self.env_from {
+            match self.source_env(helper) {
+                Ok(env) => self.sourced_env = env,
+                Err(e) => {
+                    self.fail(e, clock);
+                    return;
+                }
+            }
+        }
+        let launcher = Arc::clone(&self.launcher);
+        self.process = match launcher.launch(self) {
+            Ok(process) => {
+                self.status = CommandStatus::Running;
+                Some(process)
+            }
+            Err(e) => {
+                self.finished_at = self.started_at;
+                self.status = CommandStatus::Error(e.to_string());
+                None
+            }
+        }
+    }
+}
+
+/// Parses `KEY=VALUE` lines (optionally starting with `export`, and with the value optionally
+/// quoted), skipping blank lines and `#` comments.
+fn parse_env_lines(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut env = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("expected KEY=VALUE, not {:?}", line));
+        };
+        let unquoted = ['"', '\'']
+            .iter()
+            .find_map(|&q| value.strip_prefix(q)?.strip_suffix(q));
+        env.push((key.to_string(), unquoted.unwrap_or(value).to_string()));
+    }
+    return Ok(env);
+}
@@ -0,0 +1,1808 @@
+use crate::analysis::Analysis;
+use crate::annotations;
+use crate::annotations::Annotation;
+use crate::bench::{BenchReport, BenchResult};
+use crate::braces;
+use crate::budget::OutputBudget;
+use crate::clock::{Clock, SystemClock};
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus, DetailsOptions, ShellMode, WaitReason};
+use crate::config;
+use crate::config::ConfigOptions;
+use crate::durations::{format_bytes, format_duration, spoken_duration};
+use crate::encoding;
+use crate::exit::{Exit, ExitCodeMode};
+use crate::heredoc::Heredoc;
+use crate::history::History;
+use crate::icons::Icons;
+use crate::labels;
+use crate::labels::SourceLocation;
+use crate::last_run::{Change, LastRun};
+use crate::output::github::GithubActionsReport;
+use crate::output::gitlab::GitlabCiReport;
+use crate::output::json::JsonReport;
+use crate::output::junit::JUnitReport;
+use crate::output::log::LogReporter;
+use crate::output::markdown::MarkdownReport;
+use crate::output::ndjson::NdjsonReporter;
+use crate::output::plain::PlainReport;
+use crate::output::tap::TapReport;
+use crate::output::{log, ndjson, OutputFormat};
+use crate::parallelism::{ConcurrencyMode, LoadMonitor, Occupancy, ParallelismPolicy};
+use crate::reporter::{
+    AccessibleReporter, DefaultProgressReporter, ProgressReporter, SilentReporter,
+};
+use crate::retry::RetryBackoff;
+use crate::rules::OutputRules;
+use crate::safety::SafetyCheck;
+use crate::spec;
+use crate::spec::{CommandSpec, SpecFields};
+use crate::stats::RunStats;
+use crate::terminal::Terminal;
+use crate::waiter::Waiter;
+use encoding_rs::Encoding;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::mem;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use strum::{EnumString, EnumVariantNames};
+
+/// The order in which commands are considered for starting. Commands are always displayed and
+/// reported in the order they were added.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, EnumString, EnumVariantNames, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StartOrder {
+    #[default]
+    Input,
+    /// Historically fastest first, so that cheap failures surface early.
+    ShortestFirst,
+}
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+/// Benchmarks check on their command more often than a normal run's tick, for better timings.
+const BENCH_POLL_INTERVAL: Duration = Duration::from_millis(2);
+/// Adaptive timeouts are never shorter than this, so that quick commands have room for jitter.
+const MIN_ADAPTIVE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The environment variable with a timeout, in seconds, for commands that don't have their own.
+const DEFAULT_TIMEOUT_VAR: &str = "MULTICHECKS_DEFAULT_TIMEOUT_SECS";
+
+/// The order commands are shown in on the live dashboard. It doesn't affect when they run, or the
+/// order of the final details.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, EnumString, EnumVariantNames, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum DisplayOrder {
+    #[default]
+    Insertion,
+    Alphabetical,
+    /// Each `@group` together, in the order the groups first appear.
+    ByGroup,
+    /// Failed commands at the top.
+    FailuresFirst,
+    /// Sections of running, failed, passed and queued commands (and skipped ones, if any), each
+    /// under a header; commands move between them as they progress.
+    ByStatus,
+}
+
+impl DisplayOrder {
+    /// Indexes into `commands`, in display order.
+    pub(crate) fn arrange(self, commands: &[CommandDesc]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..commands.len()).collect();
+        match self {
+            DisplayOrder::Insertion => {}
+            DisplayOrder::Alphabetical => order.sort_by_key(|&i| commands[i].label()),
+            DisplayOrder::ByGroup => order.sort_by_key(|&i| {
+                let group = commands[i].group();
+                commands.iter().position(|c| c.group() == group)
+            }),
+            DisplayOrder::FailuresFirst => order.sort_by_key(|&i| !commands[i].status.is_error()),
+            DisplayOrder::ByStatus => order.sort_by_key(|&i| StatusSection::of(&commands[i])),
+        }
+        return order;
+    }
+}
+
+/// The sections of [DisplayOrder::ByStatus], in display order.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum StatusSection {
+    Running,
+    Failed,
+    Passed,
+    Queued,
+    Skipped,
+}
+
+impl StatusSection {
+    pub(crate) fn of(command: &CommandDesc) -> Self {
+        match &command.status {
+            CommandStatus::Running => StatusSection::Running,
+            CommandStatus::Unstarted => StatusSection::Queued,
+            CommandStatus::Skipped(_) => StatusSection::Skipped,
+            status if status.is_success() => StatusSection::Passed,
+            _ => StatusSection::Failed,
+        }
+    }
+
+    /// The section's header, and its color.
+    pub(crate) fn header(self) -> (&'static str, Color) {
+        match self {
+            StatusSection::Running => ("RUNNING", Color::Normal),
+            StatusSection::Failed => ("FAILED", Color::Red),
+            StatusSection::Passed => ("PASSED", Color::Green),
+            StatusSection::Queued => ("QUEUED", Color::Gray),
+            StatusSection::Skipped => ("SKIPPED", Color::Gray),
+        }
+    }
+}
+
+pub type CompletionCallback = Box<dyn FnMut(&str, &CommandStatus) + Send>;
+
+/// How one complete run of every command went.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunResult {
+    /// Whether every command passed (or failed in a way that's allowed).
+    pub succeeded: bool,
+    /// The labels of the commands that failed the run.
+    pub failed: Vec<String>,
+}
+
+pub struct Commands {
+    commands: Vec<CommandDesc>,
+    tick: usize,
+    output_format: OutputFormat,
+    show_durations: bool,
+    concurrency: ConcurrencyMode,
+    load_monitor: LoadMonitor,
+    start_order: StartOrder,
+    fail_fast: bool,
+    history: Option<History>,
+    last_run: Option<LastRun>,
+    diff_previous: bool,
+    clock: Arc<dyn Clock>,
+    set_title: bool,
+    reporter: Option<Box<dyn ProgressReporter>>,
+    details_options: DetailsOptions,
+    pending_annotations: Vec<Annotation>,
+    /// A heredoc that's still being read, and how many lines of the current input have been read.
+    heredoc: Option<Heredoc>,
+    input_line: usize,
+    /// What to call the current input in messages, like `stdin`.
+    input_name: String,
+    retries: usize,
+    retry_backoff: RetryBackoff,
+    shell: ShellMode,
+    container: Option<String>,
+    safety_check: Option<SafetyCheck>,
+    warn_shell_syntax: bool,
+    /// Things about the added commands that are probably mistakes, but not errors.
+    warnings: Vec<String>,
+    on_complete: Option<CompletionCallback>,
+    /// Where to write `done/total` each time another command finishes, from `--progress-fd`.
+    progress_out: Option<Box<dyn io::Write + Send>>,
+    completed: Vec<bool>,
+    waiter: Waiter,
+    wrap: bool,
+    display_order: DisplayOrder,
+    adaptive_timeout: Option<f64>,
+    default_timeout: Option<Duration>,
+    warn_slow: Option<Duration>,
+    show_pids: bool,
+    strip_prefixes: Vec<String>,
+    ascii: bool,
+    accessible: bool,
+    analyze: bool,
+    output_budget: Option<Arc<OutputBudget>>,
+    require_output: bool,
+    fail_empty_output: bool,
+    show_full_spec: bool,
+    summary_interval: Option<Duration>,
+    columns: bool,
+    column_headers: Option<(String, String)>,
+    fail_on_regex: Option<Regex>,
+    details_filter: Option<String>,
+    encoding: Option<&'static Encoding>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            tick: 0,
+            output_format: OutputFormat::default(),
+            show_durations: true,
+            concurrency: ConcurrencyMode::default(),
+            load_monitor: LoadMonitor::default(),
+            start_order: StartOrder::default(),
+            fail_fast: false,
+            history: None,
+            last_run: None,
+            diff_previous: false,
+            clock: Arc::new(SystemClock),
+            set_title: false,
+            reporter: None,
+            details_options: DetailsOptions::default(),
+            pending_annotations: Vec::new(),
+            heredoc: None,
+            input_line: 0,
+            input_name: "input".to_string(),
+            retries: 0,
+            retry_backoff: RetryBackoff::default(),
+            shell: ShellMode::default(),
+            container: None,
+            safety_check: None,
+            warn_shell_syntax: true,
+            warnings: Vec::new(),
+            on_complete: None,
+            progress_out: None,
+            completed: Vec::new(),
+            waiter: Waiter::default(),
+            wrap: true,
+            display_order: DisplayOrder::default(),
+            adaptive_timeout: None,
+            // an invalid value is for the caller to report, with [Commands::default_timeout_from_env]
+            default_timeout: Self::default_timeout_from_env().unwrap_or(None),
+            warn_slow: None,
+            show_pids: false,
+            strip_prefixes: Vec::new(),
+            ascii: false,
+            accessible: false,
+            analyze: false,
+            output_budget: None,
+            require_output: false,
+            fail_empty_output: false,
+            show_full_spec: false,
+            summary_interval: None,
+            columns: false,
+            column_headers: None,
+            fail_on_regex: None,
+            details_filter: None,
+            encoding: None,
+        }
+    }
+
+    pub fn with_output_format(mut self, fmt: OutputFormat) -> Self {
+        self.output_format = fmt;
+        self
+    }
+
+    pub fn with_durations(mut self, show_durations: bool) -> Self {
+        self.show_durations = show_durations;
+        self
+    }
+
+    /// Limits how many commands run at once: a command only starts if the total `@weight` of the
+    /// running commands, plus its own, fits within the capacity. Each command's weight defaults
+    /// to 1, so this is also a plain job count.
+    pub fn with_job_capacity(self, capacity: usize) -> Self {
+        self.with_parallelism(ParallelismPolicy::Fixed(capacity))
+    }
+
+    /// Like [Commands::with_job_capacity], but the capacity can also float within a range
+    /// according to the system load.
+    pub fn with_parallelism(self, policy: ParallelismPolicy) -> Self {
+        self.with_concurrency_mode(ConcurrencyMode::Capped(policy))
+    }
+
+    /// How to decide whether a ready command may start, given what's running. Defaults to
+    /// [ConcurrencyMode::Parallel].
+    pub fn with_concurrency_mode(mut self, mode: ConcurrencyMode) -> Self {
+        self.concurrency = mode;
+        self
+    }
+
+    pub fn with_start_order(mut self, order: StartOrder) -> Self {
+        self.start_order = order;
+        self
+    }
+
+    /// As soon as any command fails, kill the running commands and skip the ones that haven't
+    /// started.
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Uses past durations to estimate costs, and records this run's durations when it's done.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Uses whether each command passed last time for [Commands::with_diff_previous], and records
+    /// this run's results when it's done.
+    pub fn with_last_run(mut self, last_run: LastRun) -> Self {
+        self.last_run = Some(last_run);
+        self
+    }
+
+    /// In the pretty details, marks each command that passed last time and fails now as `NEW
+    /// FAIL`, and each one that failed last time and passes now as `FIXED`.
+    pub fn with_diff_previous(mut self, diff_previous: bool) -> Self {
+        self.diff_previous = diff_previous;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.set_clock(Arc::clone(&clock));
+        }
+        self.clock = clock;
+        self
+    }
+
+    /// Shows the run's progress in the terminal's title while it's running (pretty format only).
+    pub fn with_terminal_title(mut self, set_title: bool) -> Self {
+        self.set_title = set_title;
+        self
+    }
+
+    /// Retries each failed command up to `retries` times (unless it has its own `@retries`),
+    /// waiting according to `backoff` between attempts. Applies to commands added afterwards.
+    pub fn with_retries(mut self, retries: usize, backoff: RetryBackoff) -> Self {
+        self.retries = retries;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Fails commands that exit 0 without writing anything, as if they all had `@require-output`.
+    /// Applies to commands added afterwards.
+    pub fn with_require_output(mut self, require_output: bool) -> Self {
+        self.require_output = require_output;
+        self
+    }
+
+    /// Fails commands that exit 0 with nothing on stdout, as if they all had `@fail-empty-output`.
+    /// Applies to commands added afterwards.
+    pub fn with_fail_on_empty_output(mut self, fail_empty_output: bool) -> Self {
+        self.fail_empty_output = fail_empty_output;
+        self
+    }
+
+    /// In the dry-run plan and JSON report, spell out every setting of every command, rather than
+    /// listing the settings they share once and then only what differs.
+    pub fn with_show_full_spec(mut self, show_full_spec: bool) -> Self {
+        self.show_full_spec = show_full_spec;
+        self
+    }
+
+    /// When stdout isn't a terminal, prints a one-line summary of the run this often while
+    /// commands are running, in addition to a line for each command that starts or finishes.
+    pub fn with_summary_interval(mut self, interval: Option<Duration>) -> Self {
+        self.summary_interval = interval;
+        self
+    }
+
+    /// Shows the live dashboard in two columns, when it fits.
+    pub fn with_columns(mut self, columns: bool) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Bold headers for the two columns of the live dashboard. Implies [Commands::with_columns].
+    pub fn with_column_headers(mut self, headers: Option<(String, String)>) -> Self {
+        self.column_headers = headers;
+        self
+    }
+
+    /// Fails commands whose output matches `pattern`, whatever their exit code, as if they all had
+    /// `@failure-when` (unless they have their own). Applies to commands added afterwards.
+    pub fn with_fail_on_regex(mut self, pattern: &str) -> Result<Self, String> {
+        self.fail_on_regex = Some(OutputRules::compile(pattern)?);
+        Ok(self)
+    }
+
+    /// Decodes commands' output from `label` (like `latin1` or `shift_jis`) instead of UTF-8, as
+    /// if they all had `@encoding` (unless they have their own). Applies to commands added
+    /// afterwards.
+    pub fn with_encoding(mut self, label: &str) -> Result<Self, String> {
+        self.encoding = Some(encoding::parse(label)?);
+        Ok(self)
+    }
+
+    /// How command lines are run. Applies to commands added afterwards.
+    pub fn with_shell(mut self, shell: ShellMode) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    /// Runs each command in a fresh container of `image`, as `docker run --rm IMAGE sh -c LINE`,
+    /// with the working directory mounted at the same path. Applies to commands added afterwards.
+    pub fn with_docker(mut self, image: Option<String>) -> Self {
+        self.container = image;
+        self
+    }
+
+    /// Warns about commands that use shell syntax, like `>` or `|`, when they won't be run by a
+    /// shell (see [Commands::warnings]). On by default. Applies to commands added afterwards.
+    pub fn with_shell_syntax_warnings(mut self, warn: bool) -> Self {
+        self.warn_shell_syntax = warn;
+        self
+    }
+
+    /// Flags commands that look destructive, so that [Commands::confirm] asks about them even
+    /// without `@confirm`. Applies to commands added afterwards.
+    pub fn with_safety_check(mut self, check: Option<SafetyCheck>) -> Self {
+        self.safety_check = check;
+        self
+    }
+
+    /// In the details of a failed command that was retried, shows every attempt's output rather
+    /// than just the last one's.
+    pub fn with_show_all_attempts(mut self, show_all_attempts: bool) -> Self {
+        self.details_options.show_all_attempts = show_all_attempts;
+        self
+    }
+
+    /// Writes a line like `3/10` (how many commands are done, out of how many there are) to `out`
+    /// each time another command finishes or is skipped, for a wrapping tool's progress bar.
+    pub fn with_progress_output(mut self, out: Option<Box<dyn io::Write + Send>>) -> Self {
+        self.progress_out = out;
+        self
+    }
+
+    /// Calls `callback` with a command's label and final status each time a command finishes
+    /// (or is skipped). It's called on the thread that's driving the run.
+    pub fn with_on_complete(
+        mut self,
+        callback: impl FnMut(&str, &CommandStatus) + Send + 'static,
+    ) -> Self {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+
+    /// Renders progress with a custom reporter, instead of the output format's default.
+    pub fn with_reporter(mut self, mut reporter: Box<dyn ProgressReporter>) -> Self {
+        reporter.set_clock(Arc::clone(&self.clock));
+        self.reporter = Some(reporter);
+        self
+    }
+
+    /// In the details, interleaves each command's stdout and stderr lines in the order they were
+    /// written, rather than showing all of stdout and then all of stderr.
+    pub fn with_merge_streams(mut self, merge_streams: bool) -> Self {
+        self.details_options.merge_streams = merge_streams;
+        self
+    }
+
+    /// In the details, strips the colors from commands' output, leaving only our own.
+    pub fn with_strip_child_colors(mut self, strip_child_colors: bool) -> Self {
+        self.details_options.strip_child_colors = strip_child_colors;
+        self
+    }
+
+    /// Collapses runs of identical consecutive output lines into one `line (×N)` in the details.
+    pub fn with_fold_repeats(mut self, fold_repeats: bool) -> Self {
+        self.details_options.fold_repeats = fold_repeats;
+        self
+    }
+
+    /// Kills commands that run for more than `multiplier` times their median duration in the
+    /// history (but at least 10s). Commands with no history don't time out.
+    pub fn with_adaptive_timeout(mut self, multiplier: f64) -> Self {
+        self.adaptive_timeout = Some(multiplier);
+        self
+    }
+
+    /// Kills commands that run for longer than `timeout`, unless they have a `@timeout` (or an
+    /// adaptive timeout) of their own. Defaults to `$MULTICHECKS_DEFAULT_TIMEOUT_SECS`, if that's
+    /// set to a whole number of seconds other than 0.
+    pub fn with_default_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.default_timeout = timeout;
+        self
+    }
+
+    /// Pipes each failure's output through `filter`, a shell pipeline, before showing it in the
+    /// details, unless the command has a `@details-filter` of its own. Applies to commands added
+    /// afterwards.
+    pub fn with_details_filter(mut self, filter: Option<String>) -> Self {
+        self.details_filter = filter;
+        self
+    }
+
+    /// Shows how long running commands have been running once that's more than `threshold` (in
+    /// yellow, or red at twice that), unless they have a `@warn-slow` of their own. Applies to
+    /// commands added afterwards.
+    pub fn with_warn_slow(mut self, threshold: Option<Duration>) -> Self {
+        self.warn_slow = threshold;
+        self
+    }
+
+    /// Shows each running command's process ID, and each failed one's in its details header.
+    /// Applies to commands added afterwards.
+    pub fn with_show_pids(mut self, show_pids: bool) -> Self {
+        self.show_pids = show_pids;
+        self
+    }
+
+    /// Leaves the longest of `prefixes` that a command starts with off of it in the summary, like a
+    /// checkout's path that every command shares. Only the display changes. Applies to commands
+    /// added afterwards.
+    pub fn with_strip_prefixes(mut self, prefixes: Vec<String>) -> Self {
+        self.strip_prefixes = prefixes;
+        self
+    }
+
+    /// Draws the summary's spinners with ASCII characters only, overriding any `@icon`. Applies to
+    /// commands added afterwards.
+    pub fn with_ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Caps the output kept from all commands together at about `bytes`. Once that's nearly
+    /// used up, passed commands' output is dropped, oldest first, to make room; failed commands'
+    /// output is kept. If that's not enough, running commands' further output is dropped until
+    /// there's room again. Applies to commands added afterwards.
+    pub fn with_total_output_budget(mut self, bytes: Option<usize>) -> Self {
+        self.output_budget = bytes.map(|bytes| Arc::new(OutputBudget::new(bytes)));
+        self
+    }
+
+    /// In the pretty format, says what's happening in plain English instead, a line at a time and
+    /// without colors or symbols, for screen readers. See [AccessibleReporter].
+    pub fn with_accessible(mut self, accessible: bool) -> Self {
+        self.accessible = accessible;
+        self
+    }
+
+    /// Ends the details with an [Analysis] of where the run's time went (or, in the JSON format,
+    /// adds it to the report). Other formats get it on stderr.
+    pub fn with_analyze(mut self, analyze: bool) -> Self {
+        self.analyze = analyze;
+        self
+    }
+
+    /// The timeout that `$MULTICHECKS_DEFAULT_TIMEOUT_SECS` sets, if it's set to something other
+    /// than 0. An error if it isn't a whole number of seconds; [Commands::new] ignores it then.
+    pub fn default_timeout_from_env() -> Result<Option<Duration>, String> {
+        let Some(value) = std::env::var_os(DEFAULT_TIMEOUT_VAR) else {
+            return Ok(None);
+        };
+        let value = value.to_string_lossy();
+        let secs: u64 = value.trim().parse().map_err(|_| {
+            format!(
+                "${}: expected a whole number of seconds, not {:?}",
+                DEFAULT_TIMEOUT_VAR, value
+            )
+        })?;
+        return Ok(Some(Duration::from_secs(secs)).filter(|t| !t.is_zero()));
+    }
+
+    fn adaptive_timeout(&self, command: &CommandDesc) -> Option<Duration> {
+        let multiplier = self.adaptive_timeout?;
+        let median = self.history.as_ref()?.median(&command.label())?;
+        Some(median.mul_f64(multiplier).max(MIN_ADAPTIVE_TIMEOUT))
+    }
+
+    /// Changes the order commands are shown in on the live dashboard. Can be changed mid-run.
+    pub fn set_display_order(&mut self, order: DisplayOrder) {
+        self.display_order = order;
+        if let Some(reporter) = &mut self.reporter {
+            reporter.set_display_order(order);
+        }
+    }
+
+    /// Wraps long output lines in the details to the terminal's width, with continuation rows
+    /// indented under the quote bar. On by default.
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// What to call the input that the following lines come from, like `stdin`, in messages about
+    /// where commands were defined.
+    pub fn set_input_name(&mut self, name: &str) {
+        self.input_name = name.to_string();
+    }
+
+    pub fn add_command(&mut self, text: String) -> Result<(), String> {
+        self.input_line += 1;
+        if let Some(heredoc) = &mut self.heredoc {
+            if heredoc.push(text)? {
+                let heredoc = self.heredoc.take().expect("checked above");
+                return self.add_heredoc(heredoc);
+            }
+            return Ok(());
+        }
+        if annotations::is_comment(&text) {
+            self.pending_annotations
+                .extend(annotations::parse_comment(&text));
+            return Ok(());
+        }
+        if let Some(heredoc) = Heredoc::start(&text, self.input_line)? {
+            self.heredoc = Some(heredoc);
+            return Ok(());
+        }
+        // expand before splitting off annotations, so that `@env=MODE={debug,release}` works
+        let variants = braces::expand(&text)?
+            .iter()
+            .map(|line| {
+                let (inline, command_text) = annotations::split_annotations(line);
+                (inline, self.new_command(command_text))
+            })
+            .collect();
+        return self.add_variants(variants);
+    }
+
+    /// Call after adding the last line from a source, such as stdin. It's an error if that left a
+    /// heredoc open; otherwise the next line will count as line 1 of a new input.
+    pub fn finish_input(&mut self) -> Result<(), String> {
+        self.input_line = 0;
+        return match self.heredoc.take() {
+            Some(heredoc) => Err(format!(
+                "the <<{} from line {} is never closed by a line with just {}",
+                heredoc.terminator, heredoc.started_on, heredoc.terminator
+            )),
+            None => Ok(()),
+        };
+    }
+
+    fn add_heredoc(&mut self, heredoc: Heredoc) -> Result<(), String> {
+        let mut command = self.new_command(&heredoc.label());
+        if let Some(source) = &mut command.source {
+            source.line = heredoc.started_on;
+        }
+        command.line = heredoc.script();
+        command.script = Some(command.line.clone());
+        if command.shell == ShellMode::Direct {
+            command.shell = ShellMode::Shell;
+        }
+        return self.add_variants(vec![(heredoc.annotations, command)]);
+    }
+
+    /// Runs `command` (according to [Commands::with_shell]) and adds each line it writes to stdout,
+    /// as with [Commands::add_command]. Its stderr goes to ours. Returns once it has exited.
+    pub fn add_commands_from_process_stdout(&mut self, command: &str) -> Result<(), String> {
+        let mut generator =
+            CommandDesc::new(command.split_whitespace().map(str::to_string).collect());
+        generator.line = command.trim().to_string();
+        generator.shell = self.shell;
+        let Some(mut process) = generator.build_command(None) else {
+            return Err("empty command".to_string());
+        };
+        let mut child = process
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("couldn't run {}: {}", command, e))?;
+        let stdout = child.stdout.take().expect("stdout was piped");
+        let input_name = mem::replace(&mut self.input_name, format!("the output of {}", command));
+        let mut added = Ok(());
+        for line in BufReader::new(stdout).lines() {
+            match line {
+                Ok(line) => added = added.and_then(|_| self.add_command(line)),
+                Err(e) => added = added.and(Err(e.to_string())),
+            }
+        }
+        // keep reading even after an error, so the generator doesn't block on a full pipe
+        let waited = child.wait();
+        // restored before anything can return, so that later lines aren't blamed on this command
+        self.input_name = input_name;
+        let status = waited.map_err(|e| e.to_string())?;
+        added?;
+        self.finish_input()?;
+        if !status.success() {
+            return Err(format!("{} failed ({})", command, status));
+        }
+        return Ok(());
+    }
+
+    /// Adds a command for each item, by replacing `{}` in the template with it (or appending it,
+    /// if there's no `{}`). The commands are added as with [Commands::add_command], so they can
+    /// have annotations and brace expansions.
+    ///
+    /// If there's a label template, each command is labeled with it, with `{}` (or `{item}`)
+    /// replaced by the item and `{n}` by its position, counting from 1. Since labels identify
+    /// commands, one that's already taken gets a suffix like ` (2)`.
+    pub fn add_template(
+        &mut self,
+        template: &str,
+        label_template: Option<&str>,
+        items: &[String],
+    ) -> Result<(), String> {
+        let input_name = mem::replace(&mut self.input_name, "the template".to_string());
+        let added = self.add_template_items(template, label_template, items);
+        self.input_name = input_name;
+        return added;
+    }
+
+    fn add_template_items(
+        &mut self,
+        template: &str,
+        label_template: Option<&str>,
+        items: &[String],
+    ) -> Result<(), String> {
+        for (i, item) in items.iter().enumerate() {
+            let line = if template.contains("{}") {
+                template.replace("{}", item)
+            } else {
+                format!("{} {}", template, item)
+            };
+            let label = match label_template {
+                Some(label_template) => Some(expand_label(label_template, item, i + 1)?),
+                None => None,
+            };
+            let first_added = self.commands.len();
+            self.add_command(line)
+                .map_err(|e| format!("{}: {}", item, e))?;
+            if let Some(label) = label {
+                for added in first_added..self.commands.len() {
+                    let label = self.unique_label(&label);
+                    self.commands[added].label = Some(label);
+                }
+            }
+        }
+        return self.finish_input();
+    }
+
+    /// The label, or if a command already has it, the label with the first suffix like ` (2)`
+    /// that no command has.
+    fn unique_label(&self, label: &str) -> String {
+        let taken = |label: &str| self.commands.iter().any(|c| c.label() == label);
+        if !taken(label) {
+            return label.to_string();
+        }
+        return (2..)
+            .map(|n| format!("{} ({})", label, n))
+            .find(|candidate| !taken(candidate))
+            .expect("some suffix is free");
+    }
+
+    /// Adds the commands from a TOML config (see the README for its format), and returns its
+    /// options. Errors with a command say which line its `[[command]]` is on.
+    pub fn add_config(&mut self, text: &str) -> Result<ConfigOptions, String> {
+        let config = config::parse(text)?;
+        let mut headers = (text.lines().enumerate())
+            .filter(|(_, line)| line.trim() == "[[command]]")
+            .map(|(i, _)| i + 1);
+        let mut added = Ok(());
+        for (annotations, line) in config.commands {
+            self.input_line = headers.next().unwrap_or(0);
+            added = braces::expand(&line).and_then(|variants| {
+                let variants = (variants.into_iter())
+                    .map(|line| (annotations.clone(), self.new_command(&line)))
+                    .collect();
+                self.add_variants(variants)
+            });
+            if let Err(e) = added {
+                added = Err(match self.input_line {
+                    0 => e,
+                    line => format!("line {}: {}", line, e),
+                });
+                break;
+            }
+        }
+        self.input_line = 0;
+        return added.map(|_| config.options);
+    }
+
+    /// A command for a line, with the defaults for added commands.
+    fn new_command(&self, line: &str) -> CommandDesc {
+        let splits = line.split_whitespace().map(|s| s.to_string()).collect();
+        let mut command = CommandDesc::new(splits);
+        command.line = line.trim().to_string();
+        command.shell = self.shell;
+        command.container = self.container.clone();
+        command.retries = self.retries;
+        command.retry_backoff = self.retry_backoff;
+        command.rules.require_output = self.require_output;
+        command.rules.fail_empty_output = self.fail_empty_output;
+        command.rules.failure_when = self.fail_on_regex.clone();
+        command.warn_slow = self.warn_slow;
+        command.details_filter = self.details_filter.clone();
+        command.encoding = self.encoding;
+        command.show_pid = self.show_pids;
+        command.strip_prefixes = self.strip_prefixes.clone();
+        command.output_budget = self.output_budget.clone();
+        command.source = (self.input_line > 0).then(|| SourceLocation {
+            input: self.input_name.clone(),
+            line: self.input_line,
+        });
+        return command;
+    }
+
+    /// Adds each variant (from brace expansion) of a command after applying its annotations, or
+    /// none of them if any has a bad annotation.
+    fn add_variants(
+        &mut self,
+        variants: Vec<(Vec<Annotation>, CommandDesc)>,
+    ) -> Result<(), String> {
+        let comments: Vec<Annotation> = self.pending_annotations.drain(..).collect();
+        let mut expanded = Vec::new();
+        for (inline, mut command) in variants {
+            for annotation in &comments {
+                command.apply_annotation(annotation)?; // unknown keys in comments are just comments
+            }
+            for annotation in &inline {
+                if !command.apply_annotation(annotation)? {
+                    return Err(format!("unknown annotation @{}", annotation.key));
+                }
+            }
+            if self.ascii {
+                command.icons = Icons::ascii();
+            }
+            if let Some(check) = &self.safety_check {
+                command.destructive = check.check(&command.line).map(str::to_string);
+            }
+            expanded.push(command);
+        }
+        // the variants of a line all have the same syntax, so once is enough
+        let warning = expanded.first().and_then(shell_syntax_warning);
+        if let (true, Some(warning)) = (self.warn_shell_syntax, warning) {
+            self.warnings.push(warning);
+        }
+        for command in expanded {
+            self.add_desc(command);
+        }
+        return Ok(());
+    }
+
+    /// Adds an already-built command, as is: the defaults for added commands (such as
+    /// [Commands::with_retries]) don't apply to it.
+    pub fn add_desc(&mut self, command: CommandDesc) {
+        self.waiter.added(command.label());
+        self.commands.push(command);
+    }
+
+    /// A handle for waiting on commands from other threads, while this one runs them.
+    pub fn waiter(&self) -> Waiter {
+        self.waiter.clone()
+    }
+
+    /// What's wrong with the commands' labels: ones that more than one command has, if they were
+    /// set with [CommandDesc::with_label] or `@needs` or `@abort-if` refer to them, and ones that
+    /// those refer to but no command has. Commands with those would fail (or never be aborted)
+    /// once the run started, or be impossible to tell apart in the reports, so it's better not to
+    /// start it.
+    pub fn label_errors(&self) -> Vec<String> {
+        labels::check(&self.commands)
+    }
+
+    /// Things about the added commands that are probably mistakes, though they may not be, like
+    /// `echo hi > out` without a shell. Each is a sentence, without a trailing period.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn commands(&self) -> &[CommandDesc] {
+        &self.commands
+    }
+
+    /// Where the run's timestamps come from.
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// The first command, in the order they were added (not the order they failed in), that has
+    /// failed. That includes failures allowed with `@allow-failure`.
+    pub fn first_failure(&self) -> Option<&CommandDesc> {
+        self.commands.iter().find(|c| c.status.is_error())
+    }
+
+    /// The commands in each `@group`, in the order they were added. Commands without a group are
+    /// under `""`.
+    pub fn partition_by_group(&self) -> HashMap<String, Vec<&CommandDesc>> {
+        let mut groups: HashMap<String, Vec<&CommandDesc>> = HashMap::new();
+        for command in &self.commands {
+            let group = command.group().unwrap_or_default().to_string();
+            groups.entry(group).or_default().push(command);
+        }
+        return groups;
+    }
+
+    /// A point-in-time copy of each command's label and status, in insertion order.
+    pub fn snapshot(&self) -> Vec<(String, CommandStatus)> {
+        self.commands
+            .iter()
+            .map(|c| (c.label(), c.status.clone()))
+            .collect()
+    }
+
+    pub fn all_done(&self) -> bool {
+        self.commands.iter().all(|c| c.status.is_terminal_state())
+    }
+
+    pub fn all_succeeded(&self) -> bool {
+        self.commands
+            .iter()
+            .all(|c| c.status.is_terminal_state() && !c.fails_run())
+    }
+
+    /// How the run went, as an exit code. With [ExitCodeMode::Passthrough], a failed run exits
+    /// with the exit code of the command that failed first.
+    pub fn exit(&self, mode: ExitCodeMode) -> Exit {
+        if self.all_succeeded() {
+            return Exit::Passed;
+        }
+        if mode == ExitCodeMode::Passthrough {
+            let first = self
+                .commands
+                .iter()
+                .filter(|c| c.fails_run())
+                .min_by_key(|c| c.finished_at());
+            if let Some(CommandStatus::Finished(code)) = first.map(|c| &c.status) {
+                return Exit::CommandExit(*code);
+            }
+        }
+        return Exit::Failed;
+    }
+
+    /// The bytes of stdout and stderr captured from every command (and retry) so far.
+    pub fn total_output_bytes(&self) -> usize {
+        self.stats().output_bytes
+    }
+
+    /// Concurrency statistics for the run so far.
+    pub fn stats(&self) -> RunStats {
+        RunStats::from_commands(&self.commands)
+    }
+
+    /// How the run's wall time compares to its critical path, and what that suggests.
+    pub fn analysis(&self) -> Analysis {
+        Analysis::from_commands(&self.commands, &self.stats())
+    }
+
+    /// The estimated duration of each command, from the history. Commands with no history are
+    /// assumed to be median-cost.
+    fn estimates(&self) -> Vec<Option<Duration>> {
+        let Some(history) = &self.history else {
+            return vec![None; self.commands.len()];
+        };
+        let fallback = history.overall_median();
+        self.commands
+            .iter()
+            .map(|c| history.median(&c.label()).or(fallback))
+            .collect()
+    }
+
+    /// Indexes into `self.commands`, in the order they should be started.
+    fn start_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.commands.len()).collect();
+        if self.start_order == StartOrder::ShortestFirst {
+            let estimates = self.estimates();
+            order.sort_by_key(|&i| estimates[i].unwrap_or(Duration::ZERO));
+        }
+        return order;
+    }
+
+    /// `Ok(true)` if everything the command needs has passed, `Ok(false)` if it's still waiting,
+    /// or an error if it never can start because a dependency failed or was skipped.
+    fn dependencies_met(&self, command: &CommandDesc) -> Result<bool, String> {
+        let mut met = true;
+        for label in &command.depends_on {
+            let mut found = false;
+            for dependency in self.commands.iter().filter(|c| c.label() == *label) {
+                found = true;
+                match &dependency.status {
+                    status if status.is_success() => {}
+                    CommandStatus::Skipped(_) => {
+                        return Err(format!("needs {}, which was skipped", label))
+                    }
+                    status if status.is_terminal_state() => {
+                        return Err(format!("needs {}, which failed", label))
+                    }
+                    _ => met = false,
+                }
+            }
+            if !found {
+                return Err(format!("needs {}, but there's no such command", label));
+            }
+        }
+        return Ok(met);
+    }
+
+    /// Every command should either be able to start, be waiting on something that will happen
+    /// (another command finishing, or a retry's delay), or be done. If nothing is running and
+    /// nothing just started, the commands still waiting will wait forever, as with a dependency
+    /// cycle; so they fail, rather than the run hanging.
+    fn break_deadlock(&mut self, now: SystemTime, clock: &dyn Clock) {
+        let can_progress = |c: &CommandDesc| {
+            c.status == CommandStatus::Running
+                || (c.status == CommandStatus::Unstarted && !c.ready_to_start(now))
+                || matches!(
+                    c.wait_reason,
+                    Some(WaitReason::Readiness(_) | WaitReason::Delay(_))
+                )
+        };
+        if self.commands.iter().any(can_progress) {
+            return;
+        }
+        for command in self.commands.iter_mut() {
+            if command.status != CommandStatus::Unstarted {
+                continue;
+            }
+            let reason = match command.wait_reason.take() {
+                Some(WaitReason::Dependencies(labels)) => format!(
+                    "needs {}, which can never start either (is there a cycle?)",
+                    labels.join(", ")
+                ),
+                _ => "nothing it's waiting for can happen".to_string(),
+            };
+            command.fail(reason, clock);
+        }
+    }
+
+    /// If the output budget is nearly spent, drops passed (and skipped) commands' output, those
+    /// that finished first going first, until it isn't.
+    fn enforce_output_budget(&mut self) {
+        let Some(budget) = self.output_budget.clone() else {
+            return;
+        };
+        if !budget.is_nearly_spent() {
+            return;
+        }
+        let mut droppable: Vec<&mut CommandDesc> = self
+            .commands
+            .iter_mut()
+            .filter(|c| c.status.is_terminal_state() && !c.status.is_error())
+            .collect();
+        droppable.sort_by_key(|c| c.finished_at());
+        for command in droppable {
+            if !budget.is_nearly_spent() {
+                break;
+            }
+            budget.release(command.drop_output());
+        }
+    }
+
+    /// The labels of a command's dependencies that haven't finished yet, in the order it lists
+    /// them.
+    fn pending_dependencies(&self, command: &CommandDesc) -> Vec<String> {
+        let finished = |label: &String| {
+            self.commands
+                .iter()
+                .filter(|c| c.label() == *label)
+                .all(|c| c.status.is_terminal_state())
+        };
+        return command
+            .depends_on
+            .iter()
+            .filter(|label| !finished(label))
+            .cloned()
+            .collect();
+    }
+
+    /// Skips unstarted commands whose dependencies failed, and then the ones that needed those.
+    fn skip_blocked(&mut self, clock: &dyn Clock) {
+        loop {
+            let blocked: Vec<(usize, String)> = (0..self.commands.len())
+                .filter(|&i| self.commands[i].status == CommandStatus::Unstarted)
+                .filter_map(|i| match self.dependencies_met(&self.commands[i]) {
+                    Err(reason) => Some((i, reason)),
+                    Ok(_) => None,
+                })
+                .collect();
+            if blocked.is_empty() {
+                return;
+            }
+            for (i, reason) in blocked {
+                self.commands[i].skip(reason, clock);
+            }
+        }
+    }
+
+    /// Skips commands (killing the running ones) that should be aborted because of a failure.
+    fn abort_linked(&mut self, clock: &dyn Clock) {
+        let failed = |label: &String| {
+            self.commands
+                .iter()
+                .any(|c| c.label() == *label && c.status.is_error())
+        };
+        let aborted: Vec<(usize, String)> = (0..self.commands.len())
+            .filter(|&i| !self.commands[i].status.is_terminal_state())
+            .filter_map(|i| {
+                let label = self.commands[i].abort_if.iter().find(|l| failed(l))?;
+                Some((i, format!("aborted after {} failed", label)))
+            })
+            .collect();
+        for (i, reason) in aborted {
+            self.commands[i].skip(reason, clock);
+        }
+    }
+
+    fn advance(&mut self) {
+        let clock = Arc::clone(&self.clock);
+        // before checking, since collecting a finished command's output waits for its capture,
+        // which may be waiting for room
+        self.enforce_output_budget();
+        for command in self.commands.iter_mut() {
+            command.check(&*clock);
+        }
+        if self.fail_fast {
+            if let Some(failed) = self.commands.iter().find(|c| c.fails_run()) {
+                let reason = format!("cancelled after {} failed", failed.label());
+                for command in self.commands.iter_mut() {
+                    command.skip(reason.clone(), &*clock);
+                }
+            }
+        }
+        self.abort_linked(&*clock);
+        self.skip_blocked(&*clock);
+        let running = self
+            .commands
+            .iter()
+            .filter(|c| c.status == CommandStatus::Running);
+        let mut occupancy = Occupancy {
+            running: running.clone().count(),
+            running_weight: running.map(|c| c.weight).sum(),
+            current_stage: (self.commands.iter())
+                .filter(|c| !c.status.is_terminal_state())
+                .map(|c| c.stage)
+                .min(),
+        };
+        let mut held_mutexes: HashSet<String> = self
+            .commands
+            .iter()
+            .filter(|c| c.status == CommandStatus::Running)
+            .flat_map(|c| c.mutexes.iter().cloned())
+            .collect();
+        let now = clock.now();
+        let mut started_any = false;
+        for i in self.start_order() {
+            self.commands[i].wait_reason = None;
+            if !self.commands[i].ready_to_start(now) {
+                continue;
+            }
+            if self.dependencies_met(&self.commands[i]) != Ok(true) {
+                let pending = self.pending_dependencies(&self.commands[i]);
+                self.commands[i].wait_reason = Some(WaitReason::Dependencies(pending));
+                continue;
+            }
+            if let Some(address) = self.commands[i].unready_address() {
+                self.commands[i].wait_reason = Some(WaitReason::Readiness(address));
+                continue;
+            }
+            if let Some(until) = self.commands[i].delayed_until(now) {
+                self.commands[i].wait_reason = Some(WaitReason::Delay(until));
+                continue;
+            }
+            let busy = self.commands[i]
+                .mutexes
+                .iter()
+                .find(|name| held_mutexes.contains(*name));
+            if let Some(name) = busy {
+                self.commands[i].wait_reason = Some(WaitReason::Mutex(name.clone()));
+                continue;
+            }
+            let adaptive_timeout = self.adaptive_timeout(&self.commands[i]);
+            let command = &mut self.commands[i];
+            let hold = self.concurrency.hold(
+                command.weight,
+                command.stage,
+                &occupancy,
+                &mut self.load_monitor,
+            );
+            if let Some(reason) = hold {
+                command.wait_reason = Some(reason);
+                continue;
+            }
+            command.timeout = command
+                .timeout
+                .or(adaptive_timeout)
+                .or(self.default_timeout);
+            command.start(&*clock);
+            started_any = true;
+            // a fast command may already be done; each command is visited once per tick, so even
+            // if this schedules an immediate retry, it won't start again until the next one
+            command.check(&*clock);
+            if command.status == CommandStatus::Running {
+                occupancy.running += 1;
+                occupancy.running_weight += command.weight;
+                held_mutexes.extend(command.mutexes.iter().cloned());
+            }
+        }
+        if !started_any {
+            self.break_deadlock(now, &*clock);
+        }
+        self.notify_completions();
+        self.tick = self.tick.wrapping_add(1);
+    }
+
+    fn notify_completions(&mut self) {
+        self.completed.resize(self.commands.len(), false);
+        let mut any_completed = false;
+        for (command, completed) in self.commands.iter().zip(self.completed.iter_mut()) {
+            if *completed || !command.status.is_terminal_state() {
+                continue;
+            }
+            *completed = true;
+            any_completed = true;
+            self.waiter.completed(&command.label(), &command.status);
+            if let Some(callback) = &mut self.on_complete {
+                callback(&command.label(), &command.status);
+            }
+        }
+        if let (true, Some(out)) = (any_completed, &mut self.progress_out) {
+            let done = self.completed.iter().filter(|&&c| c).count();
+            _ = writeln!(out, "{}/{}", done, self.commands.len());
+            _ = out.flush();
+        }
+    }
+
+    /// Runs the command at `index` again, in the foreground with its output going straight to
+    /// our stdout and stderr, and replaces its status with the new one.
+    pub fn rerun(&mut self, index: usize) {
+        let clock = Arc::clone(&self.clock);
+        if let Some(command) = self.commands.get_mut(index) {
+            command.rerun_attached(&*clock);
+        }
+    }
+
+    /// Prints the order commands would start in, along with the estimates used to pick it.
+    pub fn print_plan(&self, out: &mut impl Write) {
+        let estimates = self.estimates();
+        let specs: Vec<CommandSpec> = self.commands.iter().map(|c| c.spec()).collect();
+        let (common, deltas) = if self.show_full_spec {
+            (
+                SpecFields::new(),
+                specs.iter().map(|s| s.fields(true)).collect(),
+            )
+        } else {
+            spec::diff(&specs)
+        };
+        if !common.is_empty() && self.commands.len() > 1 {
+            _ = writeln!(out, "all commands: {}", spec::format_fields(&common));
+        }
+        for (n, i) in self.start_order().into_iter().enumerate() {
+            let command = &self.commands[i];
+            let estimate = match (&self.history, estimates[i]) {
+                (Some(history), Some(estimate)) if history.median(&command.label()).is_none() => {
+                    format!("~{}, no history", format_duration(estimate))
+                }
+                (_, Some(estimate)) => format!("~{}", format_duration(estimate)),
+                (_, None) => "no history".to_string(),
+            };
+            _ = write!(out, "{:>3}. {}  ({})", n + 1, command.label(), estimate);
+            if !deltas[i].is_empty() {
+                _ = write!(out, "  {}", spec::format_fields(&deltas[i]));
+            }
+            _ = writeln!(out);
+        }
+    }
+
+    /// Asks about each command that [needs confirmation](CommandDesc::needs_confirmation) and
+    /// hasn't run yet, and skips the ones that `ask` doesn't approve. `ask` gives the reason to
+    /// skip a command with, or `None` to run it. Skipped commands stay skipped through
+    /// [Commands::restart_all] and [Commands::bench].
+    pub fn confirm(&mut self, mut ask: impl FnMut(&CommandDesc) -> Option<String>) {
+        let clock = Arc::clone(&self.clock);
+        for command in self.commands.iter_mut() {
+            if command.needs_confirmation() && command.status == CommandStatus::Unstarted {
+                if let Some(reason) = ask(command) {
+                    command.declined = Some(reason.clone());
+                    command.skip(reason, &*clock);
+                }
+            }
+        }
+    }
+
+    /// Deletes every command's `@isolate` directory, except failed commands' if `keep_failed`.
+    /// Returns the first error, after trying them all.
+    pub fn remove_isolated_dirs(&self, keep_failed: bool) -> io::Result<()> {
+        let mut result = Ok(());
+        for command in &self.commands {
+            let Some(isolation) = &command.isolation else {
+                continue;
+            };
+            if keep_failed && command.status.is_error() {
+                continue;
+            }
+            if let Err(e) = isolation.remove() {
+                let error =
+                    io::Error::new(e.kind(), format!("{}: {}", isolation.dir().display(), e));
+                result = result.and(Err(error));
+            }
+        }
+        return result;
+    }
+
+    /// Records how long each finished command took, and saves the history.
+    pub fn save_history(&mut self) -> io::Result<()> {
+        let Some(history) = &mut self.history else {
+            return Ok(());
+        };
+        for command in &self.commands {
+            if let (CommandStatus::Finished(_), Some(duration)) =
+                (&command.status, command.duration())
+            {
+                history.record(&command.label(), duration);
+            }
+        }
+        history.save()
+    }
+
+    /// Records whether each command that ran passed, and saves them for the next run.
+    pub fn save_last_run(&mut self) -> io::Result<()> {
+        let Some(last_run) = &mut self.last_run else {
+            return Ok(());
+        };
+        for command in &self.commands {
+            if let Some(passed) = Self::passed(command) {
+                last_run.record(&command.label(), passed);
+            }
+        }
+        last_run.save()
+    }
+
+    /// Whether a command passed, or `None` if it didn't run (or hasn't finished).
+    fn passed(command: &CommandDesc) -> Option<bool> {
+        if command.status.is_success() {
+            return Some(true);
+        }
+        return command.status.is_error().then_some(false);
+    }
+
+    /// With [Commands::with_diff_previous], how a command's result differs from the last run's.
+    pub fn change(&self, command: &CommandDesc) -> Option<Change> {
+        if !self.diff_previous {
+            return None;
+        }
+        return self
+            .last_run
+            .as_ref()?
+            .change(&command.label(), Self::passed(command)?);
+    }
+
+    /// The dependency graph in Graphviz's DOT format, with an edge from each command to each one
+    /// that `@needs` it (dashed for `@abort-if`), and each command colored by its status.
+    pub fn to_dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = "digraph checks {\n".to_string();
+        for command in &self.commands {
+            let color = match &command.status {
+                CommandStatus::Running => "gold",
+                CommandStatus::Unstarted | CommandStatus::Skipped(_) => "gray",
+                _ if command.is_allowed_failure() => "gold",
+                status if status.is_success() => "green",
+                _ => "red",
+            };
+            let label = quote(&command.label());
+            _ = writeln!(dot, "  {} [color={}];", label, color);
+            for dependency in &command.depends_on {
+                _ = writeln!(dot, "  {} -> {};", quote(dependency), label);
+            }
+            for trigger in &command.abort_if {
+                _ = writeln!(dot, "  {} -> {} [style=dashed];", quote(trigger), label);
+            }
+        }
+        dot.push_str("}\n");
+        return dot;
+    }
+
+    /// A shell script that re-runs each command that failed the run, the way it ran here (see
+    /// [CommandDesc::script_line]), and fails if any of them fails again.
+    pub fn failures_script(&self) -> String {
+        let mut script =
+            "#!/bin/sh\n# Re-runs the commands that failed in a multichecks run.\nstatus=0\n"
+                .to_string();
+        for command in self.commands.iter().filter(|c| c.fails_run()) {
+            let Some(line) = command.script_line() else {
+                continue;
+            };
+            let label = Color::strip_escapes(&command.label()).replace('\n', " ");
+            _ = write!(script, "\n# {}\n{} || status=1\n", label, line);
+        }
+        script.push_str("\nexit $status\n");
+        return script;
+    }
+
+    /// Writes a Markdown summary of the run: a table of the commands, then the output of each
+    /// failed one.
+    pub fn write_markdown_report(&self, out: &mut impl Write) -> std::fmt::Result {
+        write!(
+            out,
+            "{}",
+            MarkdownReport {
+                commands: &self.commands
+            }
+        )
+    }
+
+    fn default_reporter(&self) -> Box<dyn ProgressReporter> {
+        let mut reporter: Box<dyn ProgressReporter> = match self.output_format {
+            OutputFormat::Pretty if self.accessible => Box::new(AccessibleReporter::new()),
+            OutputFormat::Pretty | OutputFormat::Table => Box::new(
+                DefaultProgressReporter::new()
+                    .with_terminal_title(self.set_title)
+                    .with_display_order(self.display_order)
+                    .with_summary_interval(self.summary_interval)
+                    .with_columns(self.columns)
+                    .with_column_headers(self.column_headers.clone()),
+            ),
+            OutputFormat::Ndjson => Box::new(NdjsonReporter::new()),
+            OutputFormat::Log => Box::new(LogReporter::new()),
+            OutputFormat::Plain
+            | OutputFormat::Json
+            | OutputFormat::Tap
+            | OutputFormat::JUnit
+            | OutputFormat::GithubActions
+            | OutputFormat::GitlabCi => Box::new(SilentReporter),
+        };
+        reporter.set_clock(Arc::clone(&self.clock));
+        return reporter;
+    }
+
+    /// Runs until every command has finished, reporting progress along the way.
+    pub fn run(&mut self) {
+        loop {
+            self.summarize_all();
+            if self.all_done() {
+                break;
+            }
+            thread::sleep(TICK_INTERVAL);
+        }
+    }
+
+    /// Like [Commands::run], but on a new thread, so that the caller can get on with other work;
+    /// joining the handle waits for the run to finish and returns how it went. Set up the reporter
+    /// (or let the output format's default draw progress), and take any [Waiter]s, beforehand:
+    /// the commands move to the new thread, so there's no printing their details afterwards.
+    pub fn run_async(mut self) -> JoinHandle<RunResult> {
+        return thread::spawn(move || {
+            self.run();
+            if let Some(reporter) = &mut self.reporter {
+                reporter.finish(&self.commands);
+            }
+            self.run_result()
+        });
+    }
+
+    /// Runs every command again and again, until a run fails or `max_attempts` runs (at least one)
+    /// have passed, for catching flaky checks. Each run starts over with [Commands::restart_all];
+    /// a command's own retries still apply within a run. Returns how many runs there were, and
+    /// how the last one went.
+    pub fn run_until_failure(&mut self, max_attempts: usize) -> (usize, RunResult) {
+        let mut attempts = 0;
+        loop {
+            if attempts > 0 {
+                self.restart_all();
+            }
+            self.run();
+            attempts += 1;
+            let result = self.run_result();
+            if !result.succeeded || attempts >= max_attempts {
+                return (attempts, result);
+            }
+        }
+    }
+
+    /// Forgets how every command went, so that [Commands::run] starts them all from scratch.
+    /// Completion callbacks fire again as they finish, but [Waiter]s keep the first status they
+    /// saw.
+    pub fn restart_all(&mut self) {
+        for command in self.commands.iter_mut() {
+            command.reset();
+        }
+        self.completed.clear();
+    }
+
+    /// How the run went: whether it succeeded, and which commands failed it.
+    pub fn run_result(&self) -> RunResult {
+        RunResult {
+            succeeded: self.all_succeeded(),
+            failed: self
+                .commands
+                .iter()
+                .filter(|c| c.fails_run())
+                .map(|c| c.label())
+                .collect(),
+        }
+    }
+
+    /// Runs each command `runs` times, one run at a time, and times them. A command isn't run again
+    /// after it fails, and it isn't retried. Nothing is reported while the benchmark runs. Commands
+    /// that [Commands::confirm] skipped aren't run at all.
+    pub fn bench(&mut self, runs: usize) -> BenchReport {
+        let clock = Arc::clone(&self.clock);
+        let mut report = BenchReport::default();
+        for command in self.commands.iter_mut() {
+            let mut result = BenchResult {
+                label: command.label(),
+                durations: Vec::with_capacity(runs),
+                failure: None,
+                skipped: None,
+            };
+            let retries = std::mem::take(&mut command.retries);
+            for _ in 0..runs {
+                command.reset();
+                if let CommandStatus::Skipped(reason) = &command.status {
+                    result.skipped = Some(reason.clone());
+                    break;
+                }
+                command.start(&*clock);
+                while command.status == CommandStatus::Running {
+                    thread::sleep(BENCH_POLL_INTERVAL);
+                    command.check(&*clock);
+                }
+                if command.status.is_error() {
+                    result.failure = Some(command.status.clone());
+                    break;
+                }
+                result.durations.extend(command.duration());
+            }
+            command.retries = retries;
+            report.results.push(result);
+        }
+        return report;
+    }
+
+    /// Shows a message while commands are running, through the reporter so that it doesn't
+    /// garble the dashboard. Printing to stdout directly would.
+    pub fn note(&mut self, message: &str) {
+        if self.reporter.is_none() {
+            self.reporter = Some(self.default_reporter());
+        }
+        if let Some(reporter) = &mut self.reporter {
+            reporter.note(message);
+        }
+    }
+
+    /// Starts and checks commands, then reports progress.
+    pub fn summarize_all(&mut self) {
+        let tick = self.tick;
+        self.advance();
+        if self.reporter.is_none() {
+            self.reporter = Some(self.default_reporter());
+        }
+        if let Some(reporter) = &mut self.reporter {
+            reporter.report(&self.commands, tick);
+        }
+    }
+
+    /// Prints the final report in the output format. For the pretty format, that's each command's
+    /// status, and then a section with the output of each failure. Commands are always in the
+    /// order they were added (whatever order they finished in), and each one's stdout is printed
+    /// in full before its stderr, unless [Commands::with_merge_streams] is set.
+    pub fn print_details(&mut self, out: &mut Terminal) {
+        if let Some(reporter) = &mut self.reporter {
+            reporter.finish(&self.commands);
+        }
+        let commands = self.commands.as_slice();
+        _ = match self.output_format {
+            OutputFormat::Pretty if self.accessible => {
+                self.print_accessible_details(out);
+                Ok(())
+            }
+            OutputFormat::Pretty => {
+                self.print_pretty_details(out, false);
+                Ok(())
+            }
+            OutputFormat::Table => {
+                self.print_pretty_details(out, true);
+                Ok(())
+            }
+            OutputFormat::Plain => write!(
+                out,
+                "{}",
+                PlainReport {
+                    commands,
+                    durations: self.show_durations,
+                }
+            ),
+            OutputFormat::Json => write!(
+                out,
+                "{}",
+                JsonReport {
+                    commands,
+                    full_spec: self.show_full_spec,
+                    analysis: self.analyze.then(|| self.analysis().to_json(commands)),
+                }
+            ),
+            OutputFormat::Tap => write!(out, "{}", TapReport { commands }),
+            OutputFormat::Ndjson => ndjson::write_summary(commands, out),
+            OutputFormat::Log => log::write_summary(commands, &*self.clock, out),
+            OutputFormat::JUnit => write!(out, "{}", JUnitReport { commands }),
+            OutputFormat::GithubActions => write!(out, "{}", GithubActionsReport { commands }),
+            OutputFormat::GitlabCi => write!(
+                out,
+                "{}",
+                GitlabCiReport {
+                    commands,
+                    clock: &*self.clock,
+                }
+            ),
+        };
+        let in_details = matches!(
+            self.output_format,
+            OutputFormat::Pretty | OutputFormat::Table | OutputFormat::Json
+        );
+        if self.analyze && !in_details {
+            eprintln!("{}", self.analysis().summary(commands));
+        }
+    }
+
+    /// Each command's status, as a line apiece or `as_table`, then the output of each failure.
+    fn print_pretty_details(&self, out: &mut Terminal, as_table: bool) {
+        out.reset();
+        let options = DetailsOptions {
+            wrap_width: if self.wrap { out.width() } else { None },
+            ..self.details_options.clone()
+        };
+        if as_table {
+            self.print_status_table(out);
+        } else {
+            for command in &self.commands {
+                command.print_summary(0, &*self.clock, out);
+                if let Some(change) = self.change(command) {
+                    let color = match change {
+                        Change::NewFail => Color::Red,
+                        Change::Fixed => Color::Green,
+                    };
+                    _ = write!(out, " {}{}\x1b[0m", color, change);
+                }
+                _ = writeln!(out);
+            }
+        }
+        for command in self.commands.iter().filter(|c| c.status.is_error()) {
+            match command.pid().filter(|_| command.show_pid) {
+                Some(pid) => {
+                    out.print_section_header(&format!("{} [pid {}]", command.label(), pid))
+                }
+                None => out.print_section_header(&command.label()),
+            }
+            if let Some(isolation) = &command.isolation {
+                let dir = isolation.dir().display();
+                _ = writeln!(out, "{}isolated in {}{}", Color::Gray, dir, Color::Normal);
+            }
+            command.print_details(&options, out);
+        }
+        _ = writeln!(out, "\n{}", self.completion_banner());
+        if self.analyze {
+            let analysis = self.analysis().summary(&self.commands);
+            _ = writeln!(out, "{}{}{}", Color::Gray, analysis, Color::Normal);
+        }
+    }
+
+    /// A table of every command's status, exit code and duration.
+    fn print_status_table(&self, out: &mut Terminal) {
+        let rows: Vec<Vec<String>> = self
+            .commands
+            .iter()
+            .map(|command| {
+                let (status, color) = match StatusSection::of(command) {
+                    StatusSection::Failed if command.is_allowed_failure() => {
+                        ("FAILED (allowed)", Color::Yellow)
+                    }
+                    section => section.header(),
+                };
+                let mut status = format!("{}{}{}", color, status, Color::Normal);
+                if let Some(change) = self.change(command) {
+                    let color = match change {
+                        Change::NewFail => Color::Red,
+                        Change::Fixed => Color::Green,
+                    };
+                    _ = write!(status, " {}{}{}", color, change, Color::Normal);
+                }
+                let exit = match &command.status {
+                    CommandStatus::Finished(code) => code.to_string(),
+                    _ => "-".to_string(),
+                };
+                let duration = command.duration().map_or("-".to_string(), format_duration);
+                vec![status, exit, duration, command.label()]
+            })
+            .collect();
+        out.print_table(&["STATUS", "EXIT", "DURATION", "COMMAND"], &rows);
+    }
+
+    /// The output of each failure, then a count of how things went, all in plain text.
+    fn print_accessible_details(&self, out: &mut Terminal) {
+        for command in self.commands.iter().filter(|c| c.status.is_error()) {
+            let label = Color::strip_escapes(&command.label());
+            let output = [command.stdout(), command.stderr()]
+                .map(|text| Color::strip_escapes(text).trim_end().to_string());
+            let lines: Vec<&str> = output.iter().flat_map(|text| text.lines()).collect();
+            _ = match lines.len() {
+                0 => writeln!(out, "\n{} had no output.", label),
+                1 => writeln!(out, "\nOutput of {}, 1 line:", label),
+                n => writeln!(out, "\nOutput of {}, {} lines:", label, n),
+            };
+            if lines.is_empty() {
+                continue;
+            }
+            for line in lines {
+                _ = writeln!(out, "{}", line.trim_end_matches('\r'));
+            }
+            _ = writeln!(out, "End of output of {}.", label);
+        }
+        let count =
+            |pred: fn(&CommandDesc) -> bool| self.commands.iter().filter(|c| pred(c)).count();
+        _ = write!(
+            out,
+            "\nAll done in {}: {} passed, {} failed",
+            spoken_duration(self.stats().wall_time),
+            count(|c| c.status.is_success()),
+            count(|c| c.status.is_error()),
+        );
+        let skipped = count(|c| matches!(c.status, CommandStatus::Skipped(_)));
+        if skipped > 0 {
+            _ = write!(out, ", {} skipped", skipped);
+        }
+        _ = writeln!(out, ".");
+        if self.analyze {
+            _ = writeln!(out, "{}", self.analysis().summary(&self.commands));
+        }
+    }
+
+    /// Like `All done in 4.2s — 18 passed, 3 failed (47 KB output)`.
+    fn completion_banner(&self) -> String {
+        let stats = self.stats();
+        let count =
+            |pred: fn(&CommandDesc) -> bool| self.commands.iter().filter(|c| pred(c)).count();
+        let mut banner = format!(
+            "All done in {} — {} passed, {} failed",
+            format_duration(stats.wall_time),
+            count(|c| c.status.is_success()),
+            count(|c| c.status.is_error()),
+        );
+        let skipped = count(|c| matches!(c.status, CommandStatus::Skipped(_)));
+        if skipped > 0 {
+            banner.push_str(&format!(", {} skipped", skipped));
+        }
+        banner.push_str(&format!(" ({} output)", format_bytes(stats.output_bytes)));
+        return banner;
+    }
+}
+
+/// Fills in a `--label` template for the `n`th item.
+fn expand_label(template: &str, item: &str, n: usize) -> Result<String, String> {
+    let mut label = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        label.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            return Err(format!("unclosed {{ in label {:?}", template));
+        };
+        match &rest[start + 1..start + len] {
+            "" | "item" => label.push_str(item),
+            "n" => label.push_str(&n.to_string()),
+            other => {
+                return Err(format!(
+                    "unknown placeholder {{{}}} in label {:?} (use {{}}, {{item}} or {{n}})",
+                    other, template
+                ))
+            }
+        }
+        rest = &rest[start + len + 1..];
+    }
+    label.push_str(rest);
+    return Ok(label);
+}
+
+impl Drop for Commands {
+    fn drop(&mut self) {
+        self.waiter.close();
+    }
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A warning for a command that uses shell syntax without being run by a shell, if it does.
+fn shell_syntax_warning(command: &CommandDesc) -> Option<String> {
+    let found = command.shell_metacharacters();
+    if found.is_empty() {
+        return None;
+    }
+    let found: Vec<String> = found.iter().map(|c| format!("`{}`", c)).collect();
+    return Some(format!(
+        "'{}' has {}, which {} passed to {} as is, without a shell to interpret {} (see --shell)",
+        command.one_line_label(),
+        found.join(", "),
+        if found.len() == 1 { "is" } else { "are" },
+        command.command_strs.first().map_or("it", String::as_str),
+        if found.len() == 1 { "it" } else { "them" },
+    ));
+}
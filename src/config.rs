@@ -0,0 +1,87 @@
+use crate::annotations::Annotation;
+use toml::{Table, Value};
+
+/// Settings from a config's `[options]` table, for the caller to apply.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigOptions {
+    pub interactive_rerun: bool,
+}
+
+pub(crate) struct Config {
+    pub(crate) commands: Vec<(Vec<Annotation>, String)>,
+    pub(crate) options: ConfigOptions,
+}
+
+/// Parses a TOML config of `[[command]]` tables. Each has a `run` line, and its other keys are the
+/// same as annotations, with `_` or `-` (`retries = 2`, `retry_backoff = "exp:1s..30s"`).
+pub(crate) fn parse(text: &str) -> Result<Config, String> {
+    let table: Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let mut commands = Vec::new();
+    let mut options = ConfigOptions::default();
+    for (key, value) in &table {
+        if key == "options" {
+            options = parse_options(value)?;
+            continue;
+        }
+        if key != "command" {
+            return Err(format!("unknown config section {:?}", key));
+        }
+        let Value::Array(entries) = value else {
+            return Err("commands must be [[command]] tables".to_string());
+        };
+        for entry in entries {
+            let Value::Table(entry) = entry else {
+                return Err("commands must be [[command]] tables".to_string());
+            };
+            commands.push(parse_command(entry)?);
+        }
+    }
+    return Ok(Config { commands, options });
+}
+
+fn parse_options(value: &Value) -> Result<ConfigOptions, String> {
+    let Value::Table(table) = value else {
+        return Err("options must be an [options] table".to_string());
+    };
+    let mut options = ConfigOptions::default();
+    for (key, value) in table {
+        match (key.replace('_', "-").as_str(), value) {
+            ("interactive-rerun", Value::Boolean(b)) => options.interactive_rerun = *b,
+            _ => return Err(format!("unsupported option {} = {}", key, value)),
+        }
+    }
+    return Ok(options);
+}
+
+fn parse_command(entry: &Table) -> Result<(Vec<Annotation>, String), String> {
+    let Some(Value::String(run)) = entry.get("run") else {
+        return Err("each [[command]] needs a run = \"...\" line".to_string());
+    };
+    let mut annotations = Vec::new();
+    for (key, value) in entry {
+        if key == "run" {
+            continue;
+        }
+        let key = key.replace('_', "-");
+        let value = match value {
+            Value::Boolean(false) => continue,
+            Value::Boolean(true) => None,
+            Value::String(s) => Some(s.clone()),
+            Value::Integer(n) => Some(n.to_string()),
+            Value::Float(n) => Some(n.to_string()),
+            // rules: `success_when = { output_matches = "..." }`
+            Value::Table(rule) => match rule.get("output_matches") {
+                Some(Value::String(pattern)) if rule.len() == 1 => Some(pattern.clone()),
+                _ => {
+                    return Err(format!(
+                        "{} only supports {{ output_matches = \"...\" }}",
+                        key
+                    ))
+                }
+            },
+            _ => return Err(format!("unsupported value for {}: {}", key, value)),
+        };
+        annotations.push(Annotation { key, value });
+    }
+    return Ok((annotations, run.clone()));
+}
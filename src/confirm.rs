@@ -0,0 +1,27 @@
+use std::io;
+
+/// Asks a yes-or-no question on the controlling terminal (not stdin, which may well be the
+/// command list), defaulting to no. Fails if there's no terminal to ask on.
+pub fn ask_on_tty(question: &str) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::fs::File;
+        use std::io::{BufRead, BufReader, Write};
+
+        let mut tty = File::options().read(true).write(true).open("/dev/tty")?;
+        write!(tty, "{} [y/N] ", question)?;
+        tty.flush()?;
+        let mut answer = String::new();
+        BufReader::new(tty).read_line(&mut answer)?;
+        let answer = answer.trim().to_ascii_lowercase();
+        return Ok(answer == "y" || answer == "yes");
+    }
+    #[cfg(not(unix))]
+    {
+        _ = question;
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "no terminal to ask on",
+        ));
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A name for one run's container, so that it can be killed by name: killing the `docker run`
+/// client doesn't stop the container.
+pub(crate) fn unique_name() -> String {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    format!("multichecks-{}-{}", std::process::id(), id)
+}
+
+/// `docker run --rm IMAGE sh -c LINE`, with the environment passed through and each of `mounts`
+/// (the working directory, at least) mounted at the same path inside, so the command sees the
+/// same files. The container is named `name`, if given.
+pub(crate) fn docker_run(
+    image: &str,
+    name: Option<&str>,
+    line: &str,
+    env: &[(String, String)],
+    workdir: &Path,
+    mounts: &[&Path],
+) -> Command {
+    let mut command = Command::new("docker");
+    command.args(["run", "--rm"]);
+    if let Some(name) = name {
+        command.args(["--name", name]);
+    }
+    for mount in mounts {
+        let mount = mount.display();
+        command.arg("-v").arg(format!("{}:{}", mount, mount));
+    }
+    command.arg("-w").arg(workdir);
+    for (key, value) in env {
+        // only inside the container: something like an isolated HOME would throw off the docker
+        // client itself, which reads its config from there
+        command.arg("-e").arg(format!("{}={}", key, value));
+    }
+    command.args([image, "sh", "-c", line]);
+    return command;
+}
+
+/// Stops a container started by [docker_run], and waits for `docker kill` to finish.
+pub(crate) fn kill(name: &str) {
+    _ = Command::new("docker")
+        .args(["kill", name])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
@@ -0,0 +1,92 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// Listens for commands injected by `multichecks --attach` while a run is in progress.
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+pub fn default_socket_path() -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    if let Ok(cwd) = std::env::current_dir() {
+        cwd.hash(&mut hasher);
+    }
+    std::env::temp_dir().join(format!("multichecks-{:016x}.sock", hasher.finish()))
+}
+
+impl ControlServer {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        let listener = match UnixListener::bind(path) {
+            Ok(listener) => listener,
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                if UnixStream::connect(path).is_ok() {
+                    return Err(e); // another instance is live
+                }
+                std::fs::remove_file(path)?; // stale socket from a crashed run
+                UnixListener::bind(path)?
+            }
+            Err(e) => return Err(e),
+        };
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Handles any pending requests, passing each added command to `add`.
+    pub fn poll(&self, mut add: impl FnMut(String) -> Result<(), String>) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            Self::handle(stream, &mut add);
+        }
+    }
+
+    fn handle(stream: UnixStream, add: &mut impl FnMut(String) -> Result<(), String>) {
+        if stream.set_nonblocking(false).is_err() {
+            return;
+        }
+        let mut request = String::new();
+        if BufReader::new(&stream).read_line(&mut request).is_err() {
+            return;
+        }
+        let result = match request.trim_end().split_once(' ') {
+            Some(("add", command)) if !command.trim().is_empty() => add(command.to_string()),
+            _ => Err(format!("unrecognized request {:?}", request.trim_end())),
+        };
+        let mut writer = &stream;
+        _ = match result {
+            Ok(()) => writeln!(writer, "ok"),
+            Err(e) => writeln!(writer, "{}", e),
+        };
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub fn attach(path: &Path, action: &str, command: &str) -> Result<(), String> {
+    let stream = UnixStream::connect(path).map_err(|_| {
+        format!(
+            "no multichecks instance is listening on {} (is a run in progress in this directory?)",
+            path.display()
+        )
+    })?;
+    let mut writer = &stream;
+    writeln!(writer, "{} {}", action, command.replace('\n', " ")).map_err(|e| e.to_string())?;
+    let mut response = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut response)
+        .map_err(|e| e.to_string())?;
+    match response.trim_end() {
+        "ok" => Ok(()),
+        other => Err(other.to_string()),
+    }
+}
@@ -0,0 +1,128 @@
+use rustix::io::ioctl_fionread;
+use rustix::termios::{
+    tcgetattr, tcsetattr, LocalModes, OptionalActions, SpecialCodeIndex, Termios,
+};
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+
+/// Asks the terminal where its cursor is, so that the dashboard can tell when something else has
+/// written to the terminal since the last frame.
+pub(crate) struct CursorProbe {
+    tty: File,
+    /// How many more frames to go before the next probe, unless the terminal is resized first.
+    frames_until_due: usize,
+    /// The terminal's width and height as of the last probe.
+    size: Option<(usize, usize)>,
+}
+
+impl CursorProbe {
+    /// How long to wait for the terminal to answer, in tenths of a second.
+    const TIMEOUT_DECISECONDS: u8 = 1;
+    /// Longer than any answer, which is like `ESC[24;80R`.
+    const MAX_ANSWER_LEN: usize = 32;
+    /// How often to probe, in frames, when the terminal hasn't been resized. Each probe switches
+    /// the terminal's mode and waits for it to answer, which is too much to do every frame.
+    const FRAMES_BETWEEN_PROBES: usize = 10;
+
+    pub(crate) fn open() -> Option<Self> {
+        let tty = File::options()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .ok()?;
+        Some(Self {
+            tty,
+            frames_until_due: 0,
+            size: None,
+        })
+    }
+
+    /// Whether to probe at the end of this frame: on the first one, on the first one after the
+    /// terminal is resized, and otherwise every [Self::FRAMES_BETWEEN_PROBES] frames.
+    pub(crate) fn is_due(&mut self, size: Option<(usize, usize)>) -> bool {
+        if self.frames_until_due == 0 || size != self.size {
+            self.frames_until_due = Self::FRAMES_BETWEEN_PROBES - 1;
+            self.size = size;
+            return true;
+        }
+        self.frames_until_due -= 1;
+        return false;
+    }
+
+    /// The cursor's row and column (both starting at 1). `None` if there's typed input waiting to
+    /// be read, since the answer would get mixed up with it; an error if the terminal didn't
+    /// answer. Nothing is ever written to the terminal's input, so a key typed in the moment it
+    /// takes to answer is lost.
+    pub(crate) fn position(&mut self) -> io::Result<Option<(u16, u16)>> {
+        if ioctl_fionread(&self.tty)? > 0 {
+            return Ok(None);
+        }
+        // everything we've printed has to reach the terminal before the question does
+        io::stdout().flush()?;
+        let mode = Unbuffered::enable(&self.tty)?;
+        let answer = Self::ask(&self.tty);
+        drop(mode);
+        return match parse_answer(&answer?) {
+            Some(position) => Ok(Some(position)),
+            None => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the terminal didn't say where its cursor is",
+            )),
+        };
+    }
+
+    /// Asks where the cursor is, and returns everything read until the answer ends (or the
+    /// terminal stops sending anything).
+    fn ask(mut tty: &File) -> io::Result<Vec<u8>> {
+        tty.write_all(b"\x1b[6n")?;
+        let mut answer = Vec::new();
+        let mut byte = [0u8; 1];
+        while answer.len() < Self::MAX_ANSWER_LEN {
+            if tty.read(&mut byte)? == 0 {
+                break; // timed out
+            }
+            answer.push(byte[0]);
+            if byte[0] == b'R' {
+                break;
+            }
+        }
+        return Ok(answer);
+    }
+}
+
+/// Switches the terminal to handing over each byte as it arrives, without echoing it, and back
+/// to how it was when dropped, however the probe ends.
+struct Unbuffered<'a> {
+    tty: &'a File,
+    saved: Termios,
+}
+
+impl<'a> Unbuffered<'a> {
+    fn enable(tty: &'a File) -> io::Result<Self> {
+        let saved = tcgetattr(tty)?;
+        let mut raw = saved.clone();
+        // without canonical mode, the answer can be read without a newline; without echo, it
+        // isn't shown
+        raw.local_modes
+            .remove(LocalModes::ICANON | LocalModes::ECHO);
+        raw.special_codes[SpecialCodeIndex::VMIN] = 0;
+        raw.special_codes[SpecialCodeIndex::VTIME] = CursorProbe::TIMEOUT_DECISECONDS;
+        tcsetattr(tty, OptionalActions::Now, &raw)?;
+        Ok(Self { tty, saved })
+    }
+}
+
+impl Drop for Unbuffered<'_> {
+    fn drop(&mut self) {
+        _ = tcsetattr(self.tty, OptionalActions::Now, &self.saved);
+    }
+}
+
+/// The cursor's position from the last `ESC[row;colR` in what was read, if there is one.
+fn parse_answer(read: &[u8]) -> Option<(u16, u16)> {
+    let answer = String::from_utf8_lossy(read);
+    let start = answer.rfind("\x1b[")?;
+    let (row, col) = answer[start + 2..].strip_suffix('R')?.split_once(';')?;
+    Some((row.parse().ok()?, col.parse().ok()?))
+}
@@ -0,0 +1,36 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Pipes a failure's output through `filter` (a shell pipeline, like `grep -A5 'panicked at'`),
+/// for `@details-filter`, and returns what it printed. It's an error if the filter can't be
+/// started or doesn't exit 0.
+pub(crate) fn apply(filter: &str, text: &str, cwd: Option<&Path>) -> Result<String, String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(filter)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    let mut child = command.spawn().map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let text = text.to_string();
+    // written from another thread, so a filter that prints as it reads can't fill its stdout pipe
+    // while we're still writing; and a filter like `head` may stop reading early, which is fine
+    let writer = thread::spawn(move || _ = stdin.write_all(text.as_bytes()));
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    _ = writer.join();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().find(|l| !l.trim().is_empty()) {
+            Some(reason) => format!("{}: {}", output.status, reason.trim()),
+            None => output.status.to_string(),
+        });
+    }
+    return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+}
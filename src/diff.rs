@@ -0,0 +1,88 @@
+use crate::color::Color;
+use std::fmt::Write;
+
+/// Unchanged lines to show around each change.
+const CONTEXT: usize = 2;
+/// Past this many cells in the comparison table, don't look for common lines; just show all of
+/// one and then all of the other.
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A line-by-line diff from `expected` to `actual`, like `diff -u` without the headers: expected
+/// lines that are missing start with `-` (in red), and unexpected ones with `+` (in green), with a
+/// couple of unchanged lines around each change.
+pub(crate) fn render(expected: &str, actual: &str) -> String {
+    let lines = diff_lines(
+        &expected.lines().collect::<Vec<_>>(),
+        &actual.lines().collect::<Vec<_>>(),
+    );
+    let changed: Vec<bool> = lines.iter().map(|l| !matches!(l, Line::Same(_))).collect();
+    let near_change = |i: usize| {
+        let from = i.saturating_sub(CONTEXT);
+        let to = (i + CONTEXT + 1).min(changed.len());
+        changed[from..to].iter().any(|&c| c)
+    };
+    let mut out = String::new();
+    let mut skipped = false;
+    for (i, line) in lines.iter().enumerate() {
+        match line {
+            Line::Same(_) if !near_change(i) => {
+                if !skipped {
+                    _ = writeln!(out, "{}  …{}", Color::Gray, Color::Normal);
+                    skipped = true;
+                }
+                continue;
+            }
+            Line::Same(text) => _ = writeln!(out, "  {}", text),
+            Line::Removed(text) => _ = writeln!(out, "{}- {}{}", Color::Red, text, Color::Normal),
+            Line::Added(text) => _ = writeln!(out, "{}+ {}{}", Color::Green, text, Color::Normal),
+        }
+        skipped = false;
+    }
+    return out;
+}
+
+/// The lines of both, in order, marked by whether they're in one or both: the longest common
+/// subsequence, with what's left over as removals and additions.
+fn diff_lines<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (expected.len(), actual.len());
+    if n.saturating_mul(m) > MAX_CELLS {
+        let removed = expected.iter().map(|l| Line::Removed(l));
+        return removed
+            .chain(actual.iter().map(|l| Line::Added(l)))
+            .collect();
+    }
+    // common[i][j]: the length of the longest common subsequence of expected[i..] and actual[j..]
+    let mut common = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            lines.push(Line::Same(expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(expected[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(actual[j]));
+            j += 1;
+        }
+    }
+    return lines;
+}
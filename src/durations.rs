@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+/// Formats a duration compactly: `850ms`, `12.3s`, `4m02s`, `1h05m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 1 {
+        return format!("{}ms", duration.as_millis());
+    }
+    if secs < 60 {
+        return format!("{:.1}s", duration.as_secs_f64());
+    }
+    if secs < 3600 {
+        return format!("{}m{:02}s", secs / 60, secs % 60);
+    }
+    return format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60);
+}
+
+/// Spells a duration out for reading aloud: `less than a second`, `3 seconds`,
+/// `2 minutes and 5 seconds`, `1 hour and 4 minutes`.
+pub(crate) fn spoken_duration(duration: Duration) -> String {
+    let plural = |n: u64, unit: &str| match n {
+        1 => format!("1 {}", unit),
+        n => format!("{} {}s", n, unit),
+    };
+    let secs = duration.as_secs_f64().round() as u64;
+    let (big, small) = match secs {
+        0 => return "less than a second".to_string(),
+        1..=59 => return plural(secs, "second"),
+        60..=3599 => (plural(secs / 60, "minute"), plural(secs % 60, "second")),
+        _ => (
+            plural(secs / 3600, "hour"),
+            plural(secs % 3600 / 60, "minute"),
+        ),
+    };
+    if small.starts_with("0 ") {
+        return big;
+    }
+    return format!("{} and {}", big, small);
+}
+
+/// Formats a byte count compactly: `512 B`, `47 KB`, `3.2 MB`.
+pub(crate) fn format_bytes(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    if bytes_f < KB * KB {
+        return format!("{:.0} KB", bytes_f / KB);
+    }
+    return format!("{:.1} MB", bytes_f / (KB * KB));
+}
+
+/// Parses a byte count like `500`, `64K`, `10MB` or `1G`. The units are powers of 1024, as in
+/// [format_bytes].
+pub fn parse_bytes(text: &str) -> Result<usize, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size: {:?}", text))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1024.0,
+        "M" | "MB" => 1024.0 * 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("invalid size unit in {:?}", text)),
+    };
+    let bytes = number * multiplier;
+    if bytes >= usize::MAX as f64 {
+        return Err(format!("size too large: {:?}", text));
+    }
+    return Ok(bytes as usize);
+}
+
+/// Parses a duration like `500ms`, `2s`, `1.5s`, `3m` or `1h`. A bare number is seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {:?}", text))?;
+    let multiplier = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(format!("invalid duration unit in {:?}", text)),
+    };
+    return Duration::try_from_secs_f64(number * multiplier)
+        .map_err(|_| format!("duration too long: {:?}", text));
+}
@@ -0,0 +1,20 @@
+use encoding_rs::Encoding;
+
+/// The encoding with this label (like `latin1` or `shift_jis`), for `@encoding` and `--encoding`.
+pub(crate) fn parse(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| {
+        format!(
+            "unknown encoding {:?} (try utf-8, latin1 or shift_jis)",
+            label
+        )
+    })
+}
+
+/// Captured output as text: decoded from `encoding` if the command has one, and as UTF-8
+/// otherwise. Either way, bytes that don't decode become `�`.
+pub(crate) fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> String {
+    match encoding {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
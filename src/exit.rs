@@ -0,0 +1,53 @@
+use std::process::ExitCode;
+use strum::{EnumString, EnumVariantNames};
+
+/// Why multichecks exited, which determines its exit code. The codes are part of its interface,
+/// so they shouldn't change.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Exit {
+    /// 0: every command passed (or failed with `@allow-failure`).
+    Passed,
+    /// 1: at least one command failed.
+    Failed,
+    /// 2: the command line or a config file was invalid (clap also uses 2 for usage errors).
+    UsageError,
+    /// 3: something went wrong other than a command failing, such as an I/O error.
+    InternalError,
+    /// 4: there were no commands to run.
+    NoCommands,
+    /// With [ExitCodeMode::Passthrough], the exit code of the first command to fail, in place of
+    /// [Exit::Failed]. A code that doesn't fit (or is 0) becomes 1.
+    CommandExit(i32),
+}
+
+impl Exit {
+    pub fn code(self) -> u8 {
+        match self {
+            Exit::Passed => 0,
+            Exit::Failed => 1,
+            Exit::UsageError => 2,
+            Exit::InternalError => 3,
+            Exit::NoCommands => 4,
+            Exit::CommandExit(code) => u8::try_from(code).ok().filter(|&c| c != 0).unwrap_or(1),
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit.code())
+    }
+}
+
+/// How to report failed commands in the exit code.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, EnumString, EnumVariantNames, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ExitCodeMode {
+    /// Exit with 1.
+    #[default]
+    Standard,
+    /// Exit with the first failed command's own exit code.
+    Passthrough,
+}
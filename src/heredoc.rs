@@ -0,0 +1,139 @@
+use crate::annotations;
+use crate::annotations::Annotation;
+
+/// A multi-line script, from a line ending in `<<EOF` through a line with just `EOF`.
+pub(crate) struct Heredoc {
+    pub(crate) annotations: Vec<Annotation>,
+    /// Whatever came before the `<<EOF` on the first line, if anything.
+    prefix: String,
+    /// The marker as written, like `<<-'EOF'`, to hand back to the shell along with the prefix.
+    marker: String,
+    pub(crate) terminator: String,
+    /// Whether it was `<<-`, which strips leading tabs from the body and the terminator line.
+    strip_tabs: bool,
+    /// The line number of the first line, within its input.
+    pub(crate) started_on: usize,
+    body: Vec<String>,
+}
+
+impl Heredoc {
+    /// Starts a heredoc if this line ends in `<<WORD` (or `<< WORD`, `<<'WORD'`, `<<"WORD"` or
+    /// `<<-WORD`). An error if it ends in something that's only partly like one, such as
+    /// `<<W"ORD"`, rather than guess what was meant.
+    pub(crate) fn start(line: &str, line_number: usize) -> Result<Option<Self>, String> {
+        let (annotations, rest) = annotations::split_annotations(line);
+        let Some(header) = split_header(rest)? else {
+            return Ok(None);
+        };
+        return Ok(Some(Self {
+            annotations,
+            prefix: header.prefix.to_string(),
+            marker: header.marker.to_string(),
+            terminator: header.terminator.to_string(),
+            strip_tabs: header.strip_tabs,
+            started_on: line_number,
+            body: Vec::new(),
+        }));
+    }
+
+    /// Adds a line of the body. Returns `Ok(true)` once the terminator closes the heredoc.
+    pub(crate) fn push(&mut self, line: String) -> Result<bool, String> {
+        if line.trim() == self.terminator {
+            return Ok(true);
+        }
+        if matches!(split_header(&line), Ok(Some(_))) {
+            return Err(format!(
+                "heredocs can't be nested (still inside the <<{} from line {})",
+                self.terminator, self.started_on
+            ));
+        }
+        if self.strip_tabs {
+            self.body.push(line.trim_start_matches('\t').to_string());
+        } else {
+            self.body.push(line);
+        }
+        return Ok(false);
+    }
+
+    /// The script to pass to the shell. A bare `<<EOF` runs the body itself; something like
+    /// `python3 <<EOF` is left as a heredoc for the shell to feed to that command.
+    pub(crate) fn script(&self) -> String {
+        let body = self.body.join("\n");
+        if self.prefix.is_empty() {
+            return body;
+        }
+        return format!(
+            "{} {}\n{}\n{}",
+            self.prefix, self.marker, body, self.terminator
+        );
+    }
+
+    /// What to show for the command: its first line, and `…` for the rest.
+    pub(crate) fn label(&self) -> String {
+        let first = if self.prefix.is_empty() {
+            let mut lines = self.body.iter().map(|l| l.trim()).filter(|l| !l.is_empty());
+            lines.next().unwrap_or_default().to_string()
+        } else {
+            format!("{} {}", self.prefix, self.marker)
+        };
+        return format!("{} …", first).trim_start().to_string();
+    }
+}
+
+/// The parts of a line that ends in a heredoc marker.
+struct Header<'a> {
+    /// What comes before the marker, like `python3`.
+    prefix: &'a str,
+    /// The marker itself, like `<<-'EOF'`.
+    marker: &'a str,
+    /// The word that ends the heredoc, without any quotes, like `EOF`.
+    terminator: &'a str,
+    strip_tabs: bool,
+}
+
+/// Splits `cmd <<WORD` into its parts, if the line ends in a heredoc marker. The word may be
+/// preceded by `-` and spaces, and be wrapped in single or double quotes; a word that's only partly
+/// quoted is an error rather than a guess at what the terminator should be.
+fn split_header(line: &str) -> Result<Option<Header<'_>>, String> {
+    let line = line.trim();
+    let Some(at) = line.rfind("<<") else {
+        return Ok(None);
+    };
+    let (prefix, marker) = line.split_at(at);
+    // `<<<` is a here-string, not a heredoc
+    if prefix.ends_with('<') {
+        return Ok(None);
+    }
+    let rest = &marker[2..];
+    let (strip_tabs, rest) = match rest.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let word = rest.trim_start();
+    let is_word = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let quoted = |quote: char| {
+        let inner = word.strip_prefix(quote)?.strip_suffix(quote)?;
+        is_word(inner).then_some(inner)
+    };
+    let balanced = |quote: char| word.matches(quote).count() % 2 == 0;
+    let terminator = if is_word(word) {
+        word
+    } else if let Some(inner) = quoted('\'').or_else(|| quoted('"')) {
+        inner
+    } else if balanced('\'') && balanced('"') && is_word(&word.replace(['\'', '"'], "")) {
+        // like `<<E"OF"`: the shell would take EOF, but it's likely a mistake
+        return Err(format!(
+            "can't tell where the heredoc in `{}` ends: quote the whole word after the `<<`, as in \
+             <<'EOF'",
+            line
+        ));
+    } else {
+        return Ok(None);
+    };
+    return Ok(Some(Header {
+        prefix: prefix.trim_end(),
+        marker,
+        terminator,
+        strip_tabs,
+    }));
+}
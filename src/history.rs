@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const STATE_DIR: &str = ".multichecks";
+const HISTORY_FILE: &str = "history";
+const KEPT_PER_COMMAND: usize = 10;
+
+/// Recent durations of each command, by label, persisted across runs.
+pub struct History {
+    path: PathBuf,
+    durations: HashMap<String, Vec<Duration>>,
+}
+
+impl History {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(HISTORY_FILE);
+        let mut durations: HashMap<String, Vec<Duration>> = HashMap::new();
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines() {
+                let Some((millis, label)) = line.split_once('\t') else {
+                    continue;
+                };
+                if let Ok(millis) = millis.parse() {
+                    let entry = durations.entry(label.to_string()).or_default();
+                    entry.push(Duration::from_millis(millis));
+                }
+            }
+        }
+        History { path, durations }
+    }
+
+    pub fn median(&self, label: &str) -> Option<Duration> {
+        let mut durations = self.durations.get(label)?.clone();
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        return Some(durations[durations.len() / 2]);
+    }
+
+    /// The median across every command's own median; the estimate for commands with no history.
+    pub fn overall_median(&self) -> Option<Duration> {
+        let mut medians: Vec<Duration> = self
+            .durations
+            .keys()
+            .filter_map(|label| self.median(label))
+            .collect();
+        if medians.is_empty() {
+            return None;
+        }
+        medians.sort();
+        return Some(medians[medians.len() / 2]);
+    }
+
+    pub fn record(&mut self, label: &str, duration: Duration) {
+        let entry = self.durations.entry(label.to_string()).or_default();
+        entry.push(duration);
+        if entry.len() > KEPT_PER_COMMAND {
+            entry.drain(..entry.len() - KEPT_PER_COMMAND);
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut labels: Vec<&String> = self.durations.keys().collect();
+        labels.sort();
+        let mut contents = String::new();
+        for label in labels {
+            for duration in &self.durations[label] {
+                contents.push_str(&format!("{}\t{}\n", duration.as_millis(), label));
+            }
+        }
+        fs::write(&self.path, contents)
+    }
+}
@@ -0,0 +1,70 @@
+/// The frames a command's status cycles through in the summary, one per tick: a set for while it
+/// waits to start, and a spinner for while it runs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Icons {
+    unstarted: Vec<String>,
+    running: Vec<String>,
+}
+
+impl Icons {
+    const UNSTARTED_DOTS: [&'static str; 4] = ["·  ", " · ", "  ·", " · "];
+    const RUNNING_DOTS: [&'static str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    const SERVICE: [&'static str; 4] = ["·", "•", "●", "•"];
+    const TEST: [&'static str; 4] = ["◐", "◓", "◑", "◒"];
+    const ASCII_UNSTARTED: [&'static str; 4] = [".  ", " . ", "  .", " . "];
+    const ASCII_RUNNING: [&'static str; 4] = ["|", "/", "-", "\\"];
+
+    /// Frames that only use ASCII, for `--ascii`.
+    pub(crate) fn ascii() -> Self {
+        Self {
+            unstarted: frames(&Self::ASCII_UNSTARTED),
+            running: frames(&Self::ASCII_RUNNING),
+        }
+    }
+
+    /// Sets the running spinner from `@icon`: `service`, `build` (the default), `test`, or a
+    /// comma-separated list of frames that all have the same width.
+    pub(crate) fn set_running(&mut self, spec: &str) -> Result<(), String> {
+        self.running = match spec {
+            "service" => frames(&Self::SERVICE),
+            "build" => frames(&Self::RUNNING_DOTS),
+            "test" => frames(&Self::TEST),
+            custom => {
+                let custom: Vec<String> = custom.split(',').map(str::to_string).collect();
+                let width = custom[0].chars().count();
+                if let Some(bad) = custom.iter().find(|f| f.chars().any(char::is_control)) {
+                    return Err(format!("@icon frame {:?} has a control character", bad));
+                }
+                if width == 0 || custom.iter().any(|f| f.chars().count() != width) {
+                    return Err(format!(
+                        "@icon frames must all be the same width, and not empty: {:?}",
+                        spec
+                    ));
+                }
+                custom
+            }
+        };
+        return Ok(());
+    }
+
+    pub(crate) fn unstarted(&self, tick: usize) -> &str {
+        &self.unstarted[tick % self.unstarted.len()]
+    }
+
+    pub(crate) fn running(&self, tick: usize) -> &str {
+        &self.running[tick % self.running.len()]
+    }
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Self {
+            unstarted: frames(&Self::UNSTARTED_DOTS),
+            running: frames(&Self::RUNNING_DOTS),
+        }
+    }
+}
+
+fn frames(glyphs: &[&str]) -> Vec<String> {
+    glyphs.iter().map(|g| g.to_string()).collect()
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// How many times to try deleting a directory, since on some systems files that are still open
+/// (say, by a background process the command left behind) can't be deleted right away.
+const REMOVE_ATTEMPTS: usize = 5;
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A command's own temporary `HOME` and/or `TMPDIR`, from `@isolate`, so that parallel commands
+/// don't fight over the same dot-directories.
+#[derive(Clone, Debug)]
+pub(crate) struct Isolation {
+    dir: PathBuf,
+    home: bool,
+    tmp: bool,
+}
+
+impl Isolation {
+    /// Parses `@isolate`'s value, like `home,tmp`, and picks a directory that this process
+    /// hasn't used for anything else.
+    pub(crate) fn parse(spec: &str) -> Result<Self, String> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("multichecks-{}-{}", std::process::id(), id));
+        let mut isolation = Isolation {
+            dir,
+            home: false,
+            tmp: false,
+        };
+        for part in spec.split(',').map(str::trim) {
+            match part {
+                "home" => isolation.home = true,
+                "tmp" => isolation.tmp = true,
+                _ => return Err(format!("@isolate takes home and/or tmp, not {:?}", part)),
+            }
+        }
+        Ok(isolation)
+    }
+
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The variables to point at the command's own directories.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let mut env = Vec::new();
+        if self.home {
+            env.push(("HOME".to_string(), self.subdir("home")));
+        }
+        if self.tmp {
+            env.push(("TMPDIR".to_string(), self.subdir("tmp")));
+        }
+        return env;
+    }
+
+    fn subdir(&self, name: &str) -> String {
+        self.dir.join(name).display().to_string()
+    }
+
+    /// Creates the directories, if they don't exist yet (a retry reuses them).
+    pub(crate) fn create(&self) -> io::Result<()> {
+        for (_, dir) in self.env() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Deletes the directories, if they were ever created.
+    pub(crate) fn remove(&self) -> io::Result<()> {
+        let mut attempt = 1;
+        loop {
+            match fs::remove_dir_all(&self.dir) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(_) if attempt < REMOVE_ATTEMPTS => {
+                    attempt += 1;
+                    thread::sleep(REMOVE_RETRY_DELAY);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
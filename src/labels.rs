@@ -0,0 +1,120 @@
+use crate::command::CommandDesc;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Where a command was defined: which input, and which line of it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct SourceLocation {
+    /// Like `stdin`, or a config file's path.
+    pub(crate) input: String,
+    pub(crate) line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, line {}", self.input, self.line)
+    }
+}
+
+/// How far off a label can be (in edits) and still be suggested for one that doesn't exist.
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// What's wrong with the commands' labels: each one that more than one command has, if it was
+/// given explicitly or `@needs` or `@abort-if` refers to it (so it's ambiguous which is meant),
+/// and each one that they refer to but no command has. The same command line twice, unnamed and
+/// unreferenced, is fine.
+pub(crate) fn check(commands: &[CommandDesc]) -> Vec<String> {
+    let mut by_label: HashMap<String, Vec<&CommandDesc>> = HashMap::new();
+    for command in commands {
+        by_label.entry(command.label()).or_default().push(command);
+    }
+    let referenced: HashSet<&String> = (commands.iter())
+        .flat_map(|c| c.depends_on.iter().chain(c.abort_if.iter()))
+        .collect();
+    let mut errors = Vec::new();
+    let mut reported_duplicates = Vec::new();
+    for command in commands {
+        let label = command.label();
+        let defined = &by_label[&label];
+        if defined.len() < 2 || reported_duplicates.contains(&label) {
+            continue;
+        }
+        let named = defined.iter().any(|c| c.label.is_some());
+        if !named && !referenced.contains(&label) {
+            continue;
+        }
+        let places: Vec<String> = defined.iter().map(|c| place(c)).collect();
+        errors.push(format!(
+            "{} commands have the label '{}' ({}), but each one needs its own",
+            defined.len(),
+            command.one_line_label(),
+            places.join(" and ")
+        ));
+        reported_duplicates.push(label);
+    }
+    for command in commands {
+        let references = (command.depends_on.iter().map(|l| ("needs", l)))
+            .chain(command.abort_if.iter().map(|l| ("abort-if", l)));
+        for (annotation, label) in references {
+            if by_label.contains_key(label) {
+                continue;
+            }
+            errors.push(format!(
+                "{} has @{}={}, but no command has that label{}",
+                describe(command),
+                annotation,
+                label,
+                suggestion(label, by_label.keys())
+            ));
+        }
+    }
+    return errors;
+}
+
+/// The command's label, and where it was defined if that's known.
+fn describe(command: &CommandDesc) -> String {
+    match &command.source {
+        Some(source) => format!("'{}' ({})", command.one_line_label(), source),
+        None => format!("'{}'", command.one_line_label()),
+    }
+}
+
+/// Where the command was defined, if that's known.
+fn place(command: &CommandDesc) -> String {
+    match &command.source {
+        Some(source) => source.to_string(),
+        None => "added directly".to_string(),
+    }
+}
+
+/// `(did you mean 'x'?)` with the closest labels, if any are close.
+fn suggestion<'a>(label: &str, labels: impl Iterator<Item = &'a String>) -> String {
+    let mut close: Vec<(usize, &String)> = labels
+        .map(|candidate| (edit_distance(label, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE.min(label.chars().count() / 2))
+        .collect();
+    close.sort();
+    let close: Vec<String> = (close.iter().take(3))
+        .map(|(_, candidate)| format!("'{}'", candidate))
+        .collect();
+    match close.len() {
+        0 => String::new(),
+        _ => format!(" (did you mean {}?)", close.join(" or ")),
+    }
+}
+
+/// The Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    return previous[b.len()];
+}
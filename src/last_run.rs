@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const LAST_RUN_FILE: &str = "last-run";
+
+/// Whether each command passed the last time it ran, by label, persisted across runs.
+pub struct LastRun {
+    path: PathBuf,
+    passed: HashMap<String, bool>,
+}
+
+/// How a command's result differs from the last run's, for `--diff-previous`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Change {
+    /// It passed last time, and fails now.
+    NewFail,
+    /// It failed last time, and passes now.
+    Fixed,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Change::NewFail => "NEW FAIL",
+            Change::Fixed => "FIXED",
+        })
+    }
+}
+
+impl LastRun {
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(LAST_RUN_FILE);
+        let mut passed = HashMap::new();
+        if let Ok(contents) = fs::read_to_string(&path) {
+            for line in contents.lines() {
+                match line.split_once('\t') {
+                    Some(("pass", label)) => passed.insert(label.to_string(), true),
+                    Some(("fail", label)) => passed.insert(label.to_string(), false),
+                    _ => continue,
+                };
+            }
+        }
+        LastRun { path, passed }
+    }
+
+    /// How a command's result differs from last time, if it ran then and its result changed.
+    pub fn change(&self, label: &str, passed: bool) -> Option<Change> {
+        match (self.passed.get(label)?, passed) {
+            (true, false) => Some(Change::NewFail),
+            (false, true) => Some(Change::Fixed),
+            _ => None,
+        }
+    }
+
+    pub fn record(&mut self, label: &str, passed: bool) {
+        self.passed.insert(label.to_string(), passed);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut labels: Vec<&String> = self.passed.keys().collect();
+        labels.sort();
+        let mut contents = String::new();
+        for label in labels {
+            let result = if self.passed[label] { "pass" } else { "fail" };
+            contents.push_str(&format!("{}\t{}\n", result, label));
+        }
+        fs::write(&self.path, contents)
+    }
+}
@@ -0,0 +1,85 @@
+#![allow(clippy::needless_return)]
+
+mod analysis;
+mod annotations;
+mod bench;
+mod braces;
+mod budget;
+mod capture;
+mod clock;
+mod color;
+mod command;
+mod commands;
+mod config;
+mod confirm;
+mod container;
+#[cfg(unix)]
+pub mod control;
+#[cfg(unix)]
+mod cursor;
+mod details_filter;
+mod diff;
+mod durations;
+mod encoding;
+mod exit;
+mod heredoc;
+mod history;
+mod icons;
+mod isolation;
+mod labels;
+mod last_run;
+mod lock;
+mod mock;
+mod output;
+mod parallelism;
+mod process;
+mod progress;
+mod readiness;
+mod reporter;
+#[cfg(unix)]
+mod rerun_menu;
+mod retry;
+mod rules;
+mod safety;
+mod spec;
+mod stats;
+mod structured;
+mod terminal;
+mod theme;
+mod waiter;
+mod words;
+mod wrap;
+
+pub use analysis::Analysis;
+pub use bench::{BenchReport, BenchResult};
+pub use clock::{Clock, SystemClock};
+pub use color::Color;
+pub use command::{Attempt, CommandDesc, CommandOutput, CommandStatus, ShellMode, WaitReason};
+pub use commands::{Commands, CompletionCallback, DisplayOrder, RunResult, StartOrder};
+pub use config::ConfigOptions;
+pub use confirm::ask_on_tty;
+pub use durations::{parse_bytes, parse_duration};
+pub use exit::{Exit, ExitCodeMode};
+pub use history::{History, STATE_DIR};
+pub use last_run::{Change, LastRun};
+pub use lock::{in_progress_message, StateLock};
+pub use mock::MockCommandDescBuilder;
+pub use output::OutputFormat;
+pub use parallelism::{ConcurrencyMode, ParallelismPolicy};
+pub use process::{Launcher, ProcessHandle};
+pub use progress::ProgressPattern;
+pub use reporter::{
+    AccessibleReporter, DefaultProgressReporter, DotsReporter, ProgressReporter, ProgressStyle,
+    TickerReporter,
+};
+#[cfg(unix)]
+pub use rerun_menu::rerun_menu;
+pub use retry::{Backoff, RetryBackoff};
+pub use safety::SafetyCheck;
+pub use spec::{diff as diff_specs, CommandSpec, SpecFields};
+pub use stats::RunStats;
+pub use terminal::{column_after, Terminal, TerminalSize};
+pub use theme::{ColorScheme, Theme};
+pub use waiter::Waiter;
+pub use words::{quote_word, split_words};
+pub use wrap::wrap as wrap_line;
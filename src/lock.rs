@@ -0,0 +1,68 @@
+use std::fs;
+use std::fs::{File, TryLockError};
+use std::io;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+const LOCK_FILE: &str = "lock";
+
+/// An advisory lock on the state directory, so that concurrent runs in the same directory don't
+/// clobber each other's history. It's released when this is dropped, or when the process exits,
+/// however it exits; so a crashed run never leaves a lock behind, only its PID in the file.
+pub struct StateLock {
+    _file: File,
+}
+
+impl StateLock {
+    /// Locks the state directory, creating it if needed. If another run holds the lock, either
+    /// waits for it (calling `on_wait` first, with that run's PID if it's known) or, if `wait` is
+    /// false, fails with an error that says who holds it.
+    pub fn acquire(
+        state_dir: &Path,
+        wait: bool,
+        on_wait: impl FnOnce(Option<u32>),
+    ) -> io::Result<Self> {
+        fs::create_dir_all(state_dir)?;
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(state_dir.join(LOCK_FILE))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = Self::holder(&mut file);
+                if !wait {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WouldBlock,
+                        in_progress_message(holder),
+                    ));
+                }
+                on_wait(holder);
+                file.lock()?;
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        // whatever PID is in there is from a run that has finished (or crashed)
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        Ok(StateLock { _file: file })
+    }
+
+    /// The PID that the run holding the lock recorded, if it got that far.
+    fn holder(file: &mut File) -> Option<u32> {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).ok()?;
+        return contents.trim().parse().ok();
+    }
+}
+
+/// Like `another multichecks run is in progress (pid 1234)`.
+pub fn in_progress_message(holder: Option<u32>) -> String {
+    match holder {
+        Some(pid) => format!("another multichecks run is in progress (pid {})", pid),
+        None => "another multichecks run is in progress".to_string(),
+    }
+}
@@ -1,357 +1,786 @@
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::fmt::Write;
-use std::fmt::{Error, Formatter};
-use std::io::Read;
-use std::process::{Command, ExitCode, Stdio};
-use std::str::FromStr;
+#![allow(clippy::needless_return)]
+
+use clap::builder::PossibleValuesParser;
+use clap::builder::TypedValueParser;
+use clap::Parser;
+#[cfg(unix)]
+use multichecks::control;
+use multichecks::{
+    ask_on_tty, in_progress_message, parse_bytes, parse_duration, ColorScheme, Commands,
+    ConcurrencyMode, DisplayOrder, Exit, ExitCodeMode, History, LastRun, OutputFormat,
+    ParallelismPolicy, ProgressStyle, RetryBackoff, SafetyCheck, ShellMode, StartOrder, StateLock,
+    Terminal, STATE_DIR,
+};
+use std::ffi::OsString;
+use std::io;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::thread::sleep;
 use std::time::Duration;
-use std::{fmt, io};
+use strum::VariantNames;
 
 const SLEEP_DELAY: u64 = 100;
-lazy_static! {
-    static ref COLORS_REGEX: Regex =
-        Regex::new("\x1b\\[(\\d+)m").expect("Couldn't compile pattern for ASCII color sequences");
-}
+/// Extra options, for when the command line itself is out of reach (say, in a wrapper script).
+const OPTS_VAR: &str = "MULTICHECKS_OPTS";
 
-fn main() -> ExitCode {
-    let mut commands = Commands::new();
-    for line in io::stdin().lines() {
-        commands.add_command(line.unwrap());
-    }
+#[derive(Parser)]
+#[command(
+    about = "Run multiple commands in parallel, and report on the ones that fail",
+    args_override_self = true
+)]
+struct Args {
+    /// How to render progress and the final report
+    #[arg(
+        long,
+        default_value_t = OutputFormat::Pretty,
+        value_parser = PossibleValuesParser::new(OutputFormat::VARIANTS)
+            .map(|s| s.parse::<OutputFormat>().unwrap()),
+    )]
+    format: OutputFormat,
 
-    let mut terminal = Terminal::new();
-    loop {
-        commands.summarize_all(&mut terminal);
-        sleep(Duration::from_millis(SLEEP_DELAY));
-        if commands.all_done() {
-            break;
-        }
-    }
-    commands.print_details(&mut terminal);
-    return if commands.all_succeeded() {
-        ExitCode::SUCCESS
-    } else {
-        ExitCode::FAILURE
-    };
-}
+    /// Omit durations from the plain report
+    #[arg(long)]
+    no_durations: bool,
 
-struct Terminal {
-    next_write: usize,
-    written_lines_lengths: Vec<usize>,
-}
+    /// How many commands may run at once, counting each command's `@weight` (default 1). A range
+    /// like `2..8` runs up to 8, backing off towards 2 when the system load is high.
+    #[arg(long, short = 'j', visible_aliases = ["capacity", "parallel"], value_name = "N|MIN..MAX")]
+    jobs: Option<ParallelismPolicy>,
 
-impl Terminal {
-    fn new() -> Self {
-        Terminal {
-            next_write: 0,
-            written_lines_lengths: Vec::new(),
-        }
-    }
+    /// Run commands in batches by their `@stage` (0 by default), lowest first, each batch starting
+    /// once the one before it has finished
+    #[arg(long, conflicts_with = "jobs")]
+    staged: bool,
 
-    fn reset(&mut self) {
-        let already_written = self.written_lines_lengths.len();
-        if already_written == 0 {
-            return;
-        }
-        for _ in 0..already_written {
-            print!("\x1b[2K"); // erase the line
-            print!("\x1b[F");
-        }
-        self.next_write = 0;
+    /// Collapse identical consecutive lines of a failed command's output into one
+    #[arg(long)]
+    fold_repeats: bool,
+
+    /// Which commands to start first when they can't all start at once: `input` order, or
+    /// `shortest-first` according to the durations of previous runs
+    #[arg(
+        long,
+        default_value_t = StartOrder::Input,
+        value_parser = PossibleValuesParser::new(StartOrder::VARIANTS)
+            .map(|s| s.parse::<StartOrder>().unwrap()),
+    )]
+    order: StartOrder,
+
+    /// The order of the live dashboard: `insertion`, `alphabetical`, `by-group` (see `@group`),
+    /// `failures-first` or `by-status`. Commands still run, and their details are printed, in
+    /// input order
+    #[arg(
+        long,
+        default_value_t = DisplayOrder::Insertion,
+        value_parser = PossibleValuesParser::new(DisplayOrder::VARIANTS)
+            .map(|s| s.parse::<DisplayOrder>().unwrap()),
+    )]
+    display_order: DisplayOrder,
+
+    /// Shorthand for --display-order by-status: sections of running, failed, passed and queued
+    /// commands
+    #[arg(long, conflicts_with = "display_order")]
+    group_by_status: bool,
+
+    /// Stop at the first failure: kill running commands and skip the rest
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Print the order commands would start in (and the estimates behind it), without running them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Run from DIR, like `make -C`: commands, relative paths (including --config and @cwd) and
+    /// the .multichecks state directory are all relative to it. `git-root` means the nearest
+    /// directory up from here with a .git in it
+    #[arg(long, short = 'C', value_name = "DIR")]
+    chdir: Option<PathBuf>,
+
+    /// Run `@confirm` commands without asking
+    #[arg(long, short = 'y')]
+    yes: bool,
+
+    /// Before running anything, list the commands and ask whether to run them, and exit with 2
+    /// unless the answer is yes
+    #[arg(long)]
+    confirm: bool,
+
+    /// Don't ask before running commands that look destructive (like `rm -rf /` or `mkfs`), even
+    /// without a terminal to ask on
+    #[arg(long)]
+    no_safety_check: bool,
+
+    /// Treat command lines matching this regex as destructive, instead of the built-in patterns
+    /// (repeatable)
+    #[arg(long, value_name = "REGEX", conflicts_with = "no_safety_check")]
+    safety_pattern: Vec<String>,
+
+    /// Retry each failed command up to N times (commands can override this with `@retries=N`)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retries: usize,
+
+    /// How long to wait before each retry: a fixed delay like `2s`, or `exp:1s..30s` to double
+    /// the delay each time up to a cap. Add `:jitter` to randomize each delay by up to ±50%.
+    #[arg(long, value_name = "SPEC", default_value = "0s")]
+    retry_backoff: RetryBackoff,
+
+    /// For failed commands that were retried, show the output of every attempt rather than just
+    /// the last
+    #[arg(long)]
+    show_all_attempts: bool,
+
+    /// Run each line with `sh -c`, so that pipes, redirects, globs and so on work
+    #[arg(long, conflicts_with = "login_shell")]
+    shell: bool,
+
+    /// Don't warn about commands that use shell syntax (like `>` or `|`) without --shell
+    #[arg(long)]
+    no_shell_warning: bool,
+
+    /// Run each line with `$SHELL -lc`, so that it sees the same environment (PATH, etc.) as your
+    /// login shell
+    #[arg(long)]
+    login_shell: bool,
+
+    /// Run each line in a fresh container of this image, with `docker run --rm IMAGE sh -c LINE`.
+    /// The working directory is mounted at the same path inside
+    #[arg(long, value_name = "IMAGE")]
+    docker: Option<String>,
+
+    /// Show progress in the terminal's title (only when stdout is a terminal)
+    #[arg(long)]
+    set_title: bool,
+
+    /// After the run, print concurrency statistics to stderr, to help tune --jobs
+    #[arg(long)]
+    stats: bool,
+
+    /// After the run, compare the wall time to the critical path through `@needs`, and suggest
+    /// what might make the run faster
+    #[arg(long)]
+    analyze: bool,
+
+    /// Send a command to an already-running instance instead of starting a run (e.g. `--attach
+    /// add 'cargo doc'`)
+    #[arg(long, num_args = 2, value_names = ["ACTION", "COMMAND"])]
+    attach: Option<Vec<String>>,
+
+    /// The control socket that `--attach` talks to [default: derived from the working directory]
+    #[arg(long, value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    /// Read commands from a TOML config file, in addition to any piped to stdin
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// After a failing run, offer a menu for rerunning failed commands (only when stdout is a
+    /// terminal)
+    #[arg(long)]
+    interactive_rerun: bool,
+
+    /// After the run, write a Markdown summary (suitable for a PR or wiki) to this file
+    #[arg(long, value_name = "PATH")]
+    report_file: Option<PathBuf>,
+
+    /// After the run, write a shell script that re-runs just the commands that failed, each in
+    /// its directory and with its environment, to this file
+    #[arg(long, value_name = "PATH")]
+    emit_failures_script: Option<PathBuf>,
+
+    /// Write the dependency graph to this file in Graphviz's DOT format, with each command colored
+    /// by its status (after the run, or with --dry-run, before it)
+    #[arg(long, value_name = "PATH")]
+    dump_deps: Option<PathBuf>,
+
+    /// Don't wrap long output lines in the details; leave that to the terminal
+    #[arg(long)]
+    no_wrap: bool,
+
+    /// How to show progress in the pretty format: the live `dashboard`, a single `ticker` line that
+    /// rotates through the running commands, or `dots` appended one per tick without any redrawing
+    #[arg(
+        long,
+        default_value_t = ProgressStyle::Dashboard,
+        value_parser = PossibleValuesParser::new(ProgressStyle::VARIANTS)
+            .map(|s| s.parse::<ProgressStyle>().unwrap()),
+    )]
+    progress_style: ProgressStyle,
+
+    /// Draw with a palette's colors instead of the terminal's own red, green, yellow and gray
+    #[arg(long, default_value = "default", value_parser = PossibleValuesParser::new(ColorScheme::NAMES))]
+    theme: String,
+
+    /// Draw the spinners with ASCII characters only, for terminals and fonts without the braille
+    /// and circle glyphs (this overrides @icon)
+    #[arg(long)]
+    ascii: bool,
+
+    /// Shorthand for `--progress-style ticker`
+    #[arg(long, conflicts_with = "progress_style")]
+    ticker: bool,
+
+    /// Say what's happening in plain English, a line at a time, without colors or symbols, for
+    /// screen readers
+    #[arg(long, visible_alias = "accessible", conflicts_with_all = ["progress_style", "ticker"])]
+    a11y: bool,
+
+    /// Kill commands that run for more than MULTIPLIER times their typical duration (the median of
+    /// previous runs), but at least 10s
+    #[arg(long, value_name = "MULTIPLIER")]
+    max_runtime_per_line: Option<f64>,
+
+    /// Mark commands that passed last run and fail now as NEW FAIL, and those that failed last
+    /// run and pass now as FIXED
+    #[arg(long)]
+    diff_previous: bool,
+
+    /// If another run in this directory is in progress, fail rather than waiting for it to finish
+    #[arg(long)]
+    no_wait_lock: bool,
+
+    /// Don't lock or update the .multichecks state directory (its history is still read, for
+    /// --order and the like), so that this can run alongside another run
+    #[arg(long)]
+    no_state: bool,
+
+    /// Fail commands that exit successfully without writing anything (like `@require-output` on
+    /// every command)
+    #[arg(long)]
+    require_output: bool,
+
+    /// Fail commands that exit successfully with nothing on stdout (like `@fail-empty-output` on
+    /// every command)
+    #[arg(long)]
+    fail_on_empty_output: bool,
+
+    /// Run COMMAND first, and add each line it prints as a command (in addition to stdin's)
+    #[arg(long, value_name = "COMMAND")]
+    commands_from: Option<String>,
+
+    /// In --dry-run and the JSON report, list every setting of every command, rather than the
+    /// shared settings once and then only what differs
+    #[arg(long)]
+    show_full_spec: bool,
+
+    /// Fail any command whose output matches PATTERN (a regex, where `^` and `$` match at line
+    /// boundaries), even if it exits 0
+    #[arg(long, visible_alias = "fail-on-stdout-regex", value_name = "PATTERN")]
+    fail_on_regex: Option<String>,
+
+    /// In the details, interleave each command's stdout and stderr in the order they were written,
+    /// instead of all of stdout and then all of stderr
+    #[arg(long)]
+    merge_streams: bool,
+
+    /// In the details, strip the colors from commands' output, while keeping multichecks' own
+    #[arg(long)]
+    strip_child_colors: bool,
+
+    /// In the details, show each failure's output as piped through FILTER, a shell pipeline (like
+    /// `grep -A5 'panicked at'`), unless the command has a @details-filter of its own
+    #[arg(long, value_name = "FILTER")]
+    details_filter: Option<String>,
+
+    /// Decode commands' output from ENCODING (like `latin1` or `shift_jis`) instead of UTF-8,
+    /// unless the command has an @encoding of its own
+    #[arg(long, value_name = "ENCODING")]
+    encoding: Option<String>,
+
+    /// Instead of a normal run, run each command N times, one at a time, and print the min,
+    /// median, max and mean of its durations
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    bench: Option<u32>,
+
+    /// When stdout isn't a terminal (as in CI), print a one-line summary of the commands that are
+    /// still running this often (e.g. `60s`), so that long runs don't go quiet
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    summary_interval: Option<Duration>,
+
+    /// Show how long a command has been running once that's more than SECS (like `10`, or `90s`),
+    /// in yellow, or in red at twice that
+    #[arg(long, value_name = "SECS", value_parser = parse_duration)]
+    warn_slow: Option<Duration>,
+
+    /// Keep at most about this much output (like `200M`) from all commands together, dropping
+    /// passed commands' output first to stay under it
+    #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+    total_output_budget: Option<usize>,
+
+    /// Each time another command finishes, write how many are done out of how many there are (like
+    /// `3/10`, one per line) to this already-open file descriptor, for a wrapping tool
+    #[arg(long, value_name = "N")]
+    progress_fd: Option<u32>,
+
+    /// Show each running command's process ID (and each failed one's, above its output)
+    #[arg(long)]
+    show_pids: bool,
+
+    /// In the summary, leave PREFIX off the start of commands that have it, like a path they all
+    /// share; with more than one, the longest that matches (repeatable)
+    #[arg(long, value_name = "PREFIX")]
+    strip_prefix: Vec<String>,
+
+    /// Keep failed commands' `@isolate` directories after the run, to look at what's in them
+    #[arg(long)]
+    keep_isolated_dirs_on_failure: bool,
+
+    /// Show the live dashboard in two columns side by side, the first half of the commands (in
+    /// --display-order) on the left, when the terminal is wide enough
+    #[arg(long)]
+    columns: bool,
+
+    /// Headers for the two columns, like `Frontend,Backend` (implies --columns)
+    #[arg(long, value_name = "LEFT,RIGHT", value_parser = parse_column_headers)]
+    columns_header: Option<(String, String)>,
+
+    /// Add a command for each of the --items (or --items-from), with `{}` replaced by the item
+    #[arg(long, value_name = "TEMPLATE")]
+    template: Option<String>,
+
+    /// The items for --template
+    #[arg(long, value_name = "ITEM", num_args = 1.., requires = "template")]
+    items: Vec<String>,
+
+    /// Read the items for --template from a file, one per line (`-` for stdin)
+    #[arg(long, value_name = "PATH", requires = "template")]
+    items_from: Option<PathBuf>,
+
+    /// Label each --template command with LABEL instead of its command line, with `{}` (or
+    /// `{item}`) replaced by the item and `{n}` by its position
+    #[arg(long, value_name = "LABEL", requires = "template")]
+    label: Option<String>,
+
+    /// How to report failed commands in the exit code: `standard` exits with 1, and `passthrough`
+    /// with the exit code of the first command to fail
+    #[arg(
+        long,
+        default_value_t = ExitCodeMode::Standard,
+        value_parser = PossibleValuesParser::new(ExitCodeMode::VARIANTS)
+            .map(|s| s.parse::<ExitCodeMode>().unwrap()),
+    )]
+    exit_code: ExitCodeMode,
+}
+
+/// The directory for `--chdir`: `git-root` is the nearest directory with a `.git` (a directory,
+/// or a file for worktrees and submodules), from the current one up; anything else is a path.
+fn resolve_chdir(spec: &Path) -> Result<PathBuf, String> {
+    if spec != Path::new("git-root") {
+        return spec
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", spec.display(), e));
     }
+    let here = std::env::current_dir().map_err(|e| e.to_string())?;
+    return (here.ancestors())
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+        .ok_or_else(|| format!("no git root found in {} or above it", here.display()));
 }
 
-impl Write for Terminal {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        for line in s.split_inclusive("\n") {
-            while self.written_lines_lengths.len() < (self.next_write + 1) {
-                self.written_lines_lengths.push(0);
-            }
-            print!("{}", line);
-            let prev_len = self
-                .written_lines_lengths
-                .get_mut(self.next_write)
-                .ok_or(Error)?;
-            if line.ends_with("\n") {
-                *prev_len += line.len() - 1;
-                self.next_write += 1;
-            } else {
-                *prev_len += line.len();
-            }
+fn parse_column_headers(text: &str) -> Result<(String, String), String> {
+    match text.split_once(',') {
+        Some((left, right)) if !right.contains(',') => {
+            Ok((left.trim().to_string(), right.trim().to_string()))
         }
-        return Ok(());
+        _ => Err("expected two headers, separated by a comma".to_string()),
     }
 }
 
-#[derive(Eq, PartialEq)]
-enum CommandStatus {
-    Unstarted,
-    Running,
-    Finished(i32),
-    Error(String),
+fn main() -> ExitCode {
+    // every exit goes through here, so that the codes stay as documented in `Exit`
+    return ExitCode::from(run(Args::parse_from(args_with_env_opts())));
 }
 
-#[derive(Copy, Clone, Debug)]
-enum Color {
-    Normal,
-    Gray,
-    Green,
-    Yellow,
-    Red,
-    Other(i32),
+/// Our arguments, with the words of `$MULTICHECKS_OPTS` (up to any `--`) inserted ahead of the
+/// command line's own, so that the command line overrides them.
+fn args_with_env_opts() -> Vec<OsString> {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    if let Some(opts) = std::env::var_os(OPTS_VAR) {
+        let opts = opts.to_string_lossy().into_owned();
+        let words = opts.split_whitespace().take_while(|&word| word != "--");
+        let at = args.len().min(1);
+        args.splice(at..at, words.map(OsString::from));
+    }
+    return args;
 }
 
-impl Color {
-    fn find_all(text: &str) -> Vec<Color> {
-        let mut results = Vec::new();
-        for captures in COLORS_REGEX.captures_iter(text) {
-            let color = match &captures[1] {
-                "0" => Color::Normal,
-                "90" => Color::Gray,
-                "32" => Color::Green,
-                "31" => Color::Red,
-                "33" => Color::Yellow,
-                code => match i32::from_str(code) {
-                    Ok(c) => Color::Other(c),
-                    Err(_) => Color::Normal,
-                },
-            };
-            results.push(color);
+fn run(args: Args) -> Exit {
+    let chdir = match args.chdir.as_deref().map(resolve_chdir) {
+        None => None,
+        Some(Ok(dir)) => Some(dir),
+        Some(Err(e)) => {
+            eprintln!("multichecks: --chdir: {}", e);
+            return Exit::UsageError;
+        }
+    };
+    if let Some(dir) = &chdir {
+        if let Err(e) = std::env::set_current_dir(dir) {
+            eprintln!("multichecks: --chdir: {}: {}", dir.display(), e);
+            return Exit::UsageError;
         }
-        return results;
     }
-}
-
-impl fmt::Display for Color {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let code = match self {
-            Color::Normal => 0,
-            Color::Gray => 90,
-            Color::Green => 32,
-            Color::Red => 31,
-            Color::Yellow => 33,
-            Color::Other(n) => *n,
+    if let Some(theme) = ColorScheme::named(&args.theme) {
+        theme.install();
+    }
+    #[cfg(unix)]
+    let socket_path = args
+        .control_socket
+        .clone()
+        .unwrap_or_else(control::default_socket_path);
+    if let Some(attach) = &args.attach {
+        #[cfg(unix)]
+        let result = control::attach(&socket_path, &attach[0], &attach[1]);
+        #[cfg(not(unix))]
+        let result: Result<(), String> =
+            Err(format!("{} is not supported on this platform", attach[0]));
+        return match result {
+            Ok(()) => Exit::Passed,
+            Err(e) => {
+                eprintln!("multichecks: {}", e);
+                Exit::InternalError
+            }
         };
-        write!(f, "\x1b[{}m", code)
     }
-}
 
-impl CommandStatus {
-    fn is_terminal_state(&self) -> bool {
-        match self {
-            CommandStatus::Unstarted | CommandStatus::Running => false,
-            CommandStatus::Finished(_) | CommandStatus::Error(_) => true,
+    // a dry run only reads the state, so it doesn't need to wait for anyone
+    let _lock = if args.no_state || args.dry_run {
+        None
+    } else {
+        let on_wait = |holder| eprintln!("multichecks: {}…", in_progress_message(holder));
+        match StateLock::acquire(Path::new(STATE_DIR), !args.no_wait_lock, on_wait) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("multichecks: {}", e);
+                return Exit::InternalError;
+            }
         }
+    };
+    if let Err(e) = Commands::default_timeout_from_env() {
+        eprintln!("multichecks: {}", e);
+        return Exit::UsageError;
     }
-
-    fn is_error(&self) -> bool {
-        match self {
-            CommandStatus::Unstarted | CommandStatus::Running | CommandStatus::Finished(0) => false,
-            _ => true,
-        }
+    let mut commands = Commands::new()
+        .with_output_format(args.format)
+        .with_durations(!args.no_durations)
+        .with_fold_repeats(args.fold_repeats)
+        .with_wrap(!args.no_wrap)
+        .with_require_output(args.require_output)
+        .with_fail_on_empty_output(args.fail_on_empty_output)
+        .with_show_full_spec(args.show_full_spec)
+        .with_summary_interval(args.summary_interval)
+        .with_warn_slow(args.warn_slow)
+        .with_show_pids(args.show_pids)
+        .with_shell_syntax_warnings(!args.no_shell_warning)
+        .with_strip_prefixes(args.strip_prefix.clone())
+        .with_ascii(args.ascii)
+        .with_accessible(args.a11y)
+        .with_analyze(args.analyze)
+        .with_total_output_budget(args.total_output_budget)
+        .with_columns(args.columns)
+        .with_column_headers(args.columns_header)
+        .with_merge_streams(args.merge_streams)
+        .with_strip_child_colors(args.strip_child_colors)
+        .with_details_filter(args.details_filter.clone())
+        .with_start_order(args.order)
+        .with_fail_fast(args.fail_fast)
+        .with_retries(args.retries, args.retry_backoff)
+        .with_show_all_attempts(args.show_all_attempts)
+        .with_shell(if args.login_shell {
+            ShellMode::LoginShell
+        } else if args.shell {
+            ShellMode::Shell
+        } else {
+            ShellMode::Direct
+        })
+        .with_docker(args.docker.clone())
+        .with_terminal_title(args.set_title && io::stdout().is_terminal())
+        .with_history(History::load(Path::new(STATE_DIR)))
+        .with_last_run(LastRun::load(Path::new(STATE_DIR)))
+        .with_diff_previous(args.diff_previous);
+    if let Some(jobs) = args.jobs {
+        commands = commands.with_parallelism(jobs);
     }
-
-    fn is_success(&self) -> bool {
-        match self {
-            CommandStatus::Finished(0) => true,
-            _ => false,
-        }
+    if args.staged {
+        commands = commands.with_concurrency_mode(ConcurrencyMode::Staged);
     }
-}
-
-struct CommandDesc {
-    command_strs: Vec<String>,
-    command_spawn: Option<std::process::Child>,
-    status: CommandStatus,
-}
-
-impl CommandDesc {
-    const UNSTARTED_DOTS: [&'static str; 4] = ["·  ", " · ", "  ·", " · "];
-    const RUNNING_DOTS: [&'static str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
-
-    fn new(command: Vec<String>) -> Self {
-        Self {
-            command_strs: command,
-            command_spawn: None,
-            status: CommandStatus::Unstarted,
+    if let Some(fd) = args.progress_fd {
+        // a new handle on the descriptor, rather than taking it over
+        let path = format!("/dev/fd/{}", fd);
+        match std::fs::OpenOptions::new().append(true).open(&path) {
+            Ok(file) => commands = commands.with_progress_output(Some(Box::new(file))),
+            Err(e) => {
+                eprintln!("multichecks: --progress-fd {}: {}", fd, e);
+                return Exit::UsageError;
+            }
         }
     }
-
-    fn check(&mut self) {
-        if self.status.is_terminal_state() {
-            return;
-        }
-        let Some(child) = &mut self.command_spawn else {
-            return;
-        };
-        match child.try_wait() {
-            Ok(Some(status)) => {
-                self.status = match status.code() {
-                    None => CommandStatus::Error("Error reading status code".to_string()),
-                    Some(code) => CommandStatus::Finished(code),
-                }
-            }
-            Ok(None) => {} // nothing
+    if let Some(label) = &args.encoding {
+        commands = match commands.with_encoding(label) {
+            Ok(commands) => commands,
             Err(e) => {
-                self.status = CommandStatus::Error(e.to_string());
+                eprintln!("multichecks: --encoding: {}", e);
+                return Exit::UsageError;
             }
-        }
+        };
     }
-
-    fn print_summary(&self, tick: usize, out: &mut Terminal) {
-        let (status, color) = match &self.status {
-            CommandStatus::Unstarted => (
-                Self::UNSTARTED_DOTS[tick % Self::UNSTARTED_DOTS.len()],
-                Color::Gray,
-            ),
-            CommandStatus::Running => (
-                Self::RUNNING_DOTS[tick % Self::RUNNING_DOTS.len()],
-                Color::Normal,
-            ),
-            CommandStatus::Finished(0) => ("OK", Color::Green),
-            CommandStatus::Finished(_) => ("FAILED", Color::Red),
-            CommandStatus::Error(_) => ("FAILED", Color::Red),
+    if let Some(pattern) = &args.fail_on_regex {
+        commands = match commands.with_fail_on_regex(pattern) {
+            Ok(commands) => commands,
+            Err(e) => {
+                eprintln!("multichecks: --fail-on-regex: {}", e);
+                return Exit::UsageError;
+            }
         };
-        _ = write!(
-            out,
-            "{}: {}{}\x1b[0m",
-            self.command_strs.join(" "),
-            color,
-            status
-        );
     }
-
-    fn print_details(&mut self, out: &mut Terminal) {
-        if !self.status.is_error() {
-            return;
-        }
-        match &mut self.command_spawn {
-            None => {
-                _ = writeln!(
-                    out,
-                    "{}!{} Failed to start process",
-                    Color::Red,
-                    Color::Normal
-                )
+    if !args.no_safety_check {
+        let check = if args.safety_pattern.is_empty() {
+            Ok(SafetyCheck::built_in())
+        } else {
+            SafetyCheck::with_patterns(&args.safety_pattern)
+        };
+        commands = match check {
+            Ok(check) => commands.with_safety_check(Some(check)),
+            Err(e) => {
+                eprintln!("multichecks: --safety-pattern: {}", e);
+                return Exit::UsageError;
             }
-            Some(child) => {
-                CommandDesc::print_output(child.stdout.take(), out);
-                CommandDesc::print_output(child.stderr.take(), out);
+        };
+    }
+    if let Some(multiplier) = args.max_runtime_per_line {
+        commands = commands.with_adaptive_timeout(multiplier);
+    }
+    commands.set_display_order(if args.group_by_status {
+        DisplayOrder::ByStatus
+    } else {
+        args.display_order
+    });
+    let progress_style = if args.ticker {
+        ProgressStyle::Ticker
+    } else {
+        args.progress_style
+    };
+    if progress_style != ProgressStyle::Dashboard && args.format == OutputFormat::Pretty {
+        commands = commands.with_reporter(progress_style.reporter());
+    }
+    let mut interactive_rerun = args.interactive_rerun;
+    if let Some(path) = &args.config {
+        commands.set_input_name(&path.display().to_string());
+        let added = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| commands.add_config(&text));
+        match added {
+            Ok(options) => interactive_rerun |= options.interactive_rerun,
+            Err(e) => {
+                eprintln!("multichecks: {}: {}", path.display(), e);
+                return Exit::UsageError;
             }
         }
     }
-
-    fn print_output<R: Read>(source: Option<R>, out: &mut Terminal) {
-        if let Some(mut contents) = source {
-            let mut str: String = String::new();
-            match contents.read_to_string(&mut str) {
-                Ok(_) => {}
+    if let Some(generator) = &args.commands_from {
+        if let Err(e) = commands.add_commands_from_process_stdout(generator) {
+            eprintln!("multichecks: --commands-from: {}", e);
+            return Exit::UsageError;
+        }
+    }
+    let items_from_stdin = args.items_from.as_deref() == Some(Path::new("-"));
+    if let Some(template) = &args.template {
+        let mut items = args.items.clone();
+        match &args.items_from {
+            Some(_) if items_from_stdin => items.extend(io::stdin().lines().map_while(Result::ok)),
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(text) => items.extend(text.lines().map(str::to_string)),
                 Err(e) => {
-                    _ = write!(
-                        &mut str,
-                        "{}Error reading stdout{}: {}",
-                        Color::Red,
-                        Color::Normal,
-                        e.to_string()
-                    )
+                    eprintln!("multichecks: {}: {}", path.display(), e);
+                    return Exit::UsageError;
                 }
-            }
-            let last_color = Color::Normal;
-            if !str.is_empty() {
-                for line in str.split("\n") {
-                    let colors = Color::find_all(line);
-                    let quote_color = match colors.len() {
-                        0 => Color::Normal,
-                        1 => colors[0],
-                        _ => Color::Yellow,
-                    };
-                    _ = writeln!(out, "{}│{} {}", quote_color, last_color, line);
+            },
+            None => {}
+        }
+        items.retain(|item| !item.trim().is_empty());
+        if let Err(e) = commands.add_template(template, args.label.as_deref(), &items) {
+            eprintln!("multichecks: --template: {}", e);
+            return Exit::UsageError;
+        }
+    }
+    let other_sources =
+        args.config.is_some() || args.commands_from.is_some() || args.template.is_some();
+    if !items_from_stdin && (!other_sources || !io::stdin().is_terminal()) {
+        commands.set_input_name("stdin");
+        for (i, line) in io::stdin().lines().enumerate() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    eprintln!("multichecks: couldn't read stdin: {}", e);
+                    return Exit::InternalError;
                 }
+            };
+            if let Err(e) = commands.add_command(line) {
+                eprintln!("multichecks: line {}: {}", i + 1, e);
+                return Exit::UsageError;
             }
         }
+        if let Err(e) = commands.finish_input() {
+            eprintln!("multichecks: {}", e);
+            return Exit::UsageError;
+        }
     }
 
-    fn start(&mut self) {
-        let Some((command_name, command_args)) = self.command_strs.split_first() else {
-            return
-        };
-        let mut command = Command::new(command_name);
-        command
-            .args(command_args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-        self.command_spawn = match command.spawn() {
-            Ok(child) => {
-                self.status = CommandStatus::Running;
-                Some(child)
+    if commands.commands().is_empty() {
+        eprintln!("multichecks: no commands to run");
+        return Exit::NoCommands;
+    }
+    let label_errors = commands.label_errors();
+    if !label_errors.is_empty() {
+        for error in label_errors {
+            eprintln!("multichecks: {}", error);
+        }
+        return Exit::UsageError;
+    }
+    for warning in commands.warnings() {
+        eprintln!("multichecks: warning: {}", warning);
+    }
+
+    if args.dry_run {
+        if let Some(dir) = &chdir {
+            println!("in {}:", dir.display());
+        }
+        let mut plan = String::new();
+        commands.print_plan(&mut plan);
+        print!("{}", plan);
+        dump_deps(&commands, args.dump_deps.as_deref());
+        return Exit::Passed;
+    }
+
+    if args.confirm {
+        let mut plan = String::new();
+        commands.print_plan(&mut plan);
+        let question = format!(
+            "{}\nRun these {} commands?",
+            plan,
+            commands.commands().len()
+        );
+        match ask_on_tty(&question) {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!("multichecks: not confirmed, so not running anything");
+                return Exit::UsageError;
             }
             Err(e) => {
-                self.status = CommandStatus::Error(e.to_string());
-                None
+                eprintln!("multichecks: --confirm needs a terminal to ask on: {}", e);
+                return Exit::UsageError;
             }
         }
     }
-}
 
-struct Commands {
-    commands: Vec<CommandDesc>,
-    tick: usize,
-}
+    commands.confirm(|command| {
+        let question = match command.destructive_match() {
+            Some(pattern) => format!(
+                "'{}' looks destructive (like {}). Run it anyway?",
+                command.label(),
+                pattern
+            ),
+            // --yes only covers @confirm; destructive commands need --no-safety-check
+            None if args.yes => return None,
+            None => format!("Run '{}'?", command.label()),
+        };
+        return match ask_on_tty(&question) {
+            Ok(true) => None,
+            Ok(false) => Some("not confirmed".to_string()),
+            Err(_) if command.destructive_match().is_some() => Some(
+                "looks destructive, but there's no terminal to confirm it on (see --no-safety-check)"
+                    .to_string(),
+            ),
+            Err(_) => Some("needs confirmation, but there's no terminal (see --yes)".to_string()),
+        };
+    });
 
-impl Commands {
-    fn new() -> Self {
-        Self {
-            commands: Vec::new(),
-            tick: 0,
+    if let Some(runs) = args.bench {
+        let report = commands.bench(runs as usize);
+        print!("{}", report);
+        if let Err(e) = commands.remove_isolated_dirs(false) {
+            eprintln!("multichecks: couldn't remove isolated directory {}", e);
         }
+        if report.all_passed() {
+            return Exit::Passed;
+        }
+        println!();
+        commands.print_details(&mut Terminal::for_stdout());
+        return Exit::Failed;
     }
 
-    fn add_command(&mut self, text: String) {
-        let splits = text
-            .split_whitespace()
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect();
-        self.commands.push(CommandDesc::new(splits));
-    }
+    #[cfg(unix)]
+    let control_server = control::ControlServer::bind(&socket_path).ok();
 
-    fn all_done(&self) -> bool {
-        self.commands.iter().all(|c| c.status.is_terminal_state())
+    let mut terminal = Terminal::for_stdout();
+    loop {
+        #[cfg(unix)]
+        if let Some(server) = &control_server {
+            server.poll(|command| {
+                commands
+                    .add_command(command)
+                    .and_then(|_| commands.finish_input())
+            });
+        }
+        commands.summarize_all();
+        sleep(Duration::from_millis(SLEEP_DELAY));
+        if commands.all_done() {
+            break;
+        }
     }
-
-    fn all_succeeded(&self) -> bool {
-        self.commands.iter().all(|c| c.status.is_success())
+    commands.print_details(&mut terminal);
+    if args.stats {
+        eprint!("{}", commands.stats());
     }
-
-    fn summarize_all(&mut self, out: &mut Terminal) {
-        out.reset();
-        let last_commands_idx = self.commands.len();
-        let action: fn(&mut CommandDesc);
-        if self.tick > 0 {
-            action = CommandDesc::check;
-        } else {
-            action = CommandDesc::start;
+    #[cfg(unix)]
+    if interactive_rerun && !commands.all_succeeded() && io::stdout().is_terminal() {
+        if let Err(e) = multichecks::rerun_menu(&mut commands) {
+            eprintln!("multichecks: rerun menu: {}", e);
         }
-        for command in self.commands.iter_mut() {
-            action(command);
+    }
+    if let Some(path) = &args.report_file {
+        let mut report = String::new();
+        _ = commands.write_markdown_report(&mut report);
+        if let Err(e) = std::fs::write(path, report) {
+            eprintln!("multichecks: couldn't write {}: {}", path.display(), e);
         }
-        for (i, command) in self.commands.iter().enumerate() {
-            command.print_summary(self.tick, out);
-            if i != last_commands_idx {
-                _ = writeln!(out);
-            }
+    }
+    if let Some(path) = &args.emit_failures_script {
+        if let Err(e) = write_script(path, &commands.failures_script()) {
+            eprintln!("multichecks: couldn't write {}: {}", path.display(), e);
         }
-        self.tick = self.tick.wrapping_add(1);
     }
+    dump_deps(&commands, args.dump_deps.as_deref());
+    if let Err(e) = commands.remove_isolated_dirs(args.keep_isolated_dirs_on_failure) {
+        eprintln!("multichecks: couldn't remove isolated directory {}", e);
+    }
+    if !args.no_state {
+        if let Err(e) = commands.save_history() {
+            eprintln!("multichecks: couldn't save history: {}", e);
+        }
+        if let Err(e) = commands.save_last_run() {
+            eprintln!("multichecks: couldn't save results: {}", e);
+        }
+    }
+    return commands.exit(args.exit_code);
+}
+
+/// Writes a script, executable by its owner.
+fn write_script(path: &Path, script: &str) -> io::Result<()> {
+    std::fs::write(path, script)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = std::fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o700);
+        std::fs::set_permissions(path, permissions)?;
+    }
+    return Ok(());
+}
 
-    fn print_details(&mut self, out: &mut Terminal) {
-        out.reset();
-        for command in &mut self.commands {
-            command.print_summary(0, out);
-            _ = writeln!(out);
-            command.print_details(out);
+fn dump_deps(commands: &Commands, path: Option<&Path>) {
+    if let Some(path) = path {
+        if let Err(e) = std::fs::write(path, commands.to_dot()) {
+            eprintln!("multichecks: couldn't write {}: {}", path.display(), e);
         }
     }
 }
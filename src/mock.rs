@@ -0,0 +1,124 @@
+use crate::command::{CommandDesc, CommandOutput, CommandStatus};
+use crate::process::{Launcher, ProcessHandle};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Builds a [CommandDesc] that pretends to run, without spawning a process. Useful for testing
+/// code that drives [crate::Commands].
+pub struct MockCommandDescBuilder {
+    label: String,
+    statuses: Vec<CommandStatus>,
+    output: CommandOutput,
+    start_latency: Duration,
+    wait_for: Vec<String>,
+}
+
+impl MockCommandDescBuilder {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            statuses: Vec::new(),
+            output: CommandOutput::default(),
+            start_latency: Duration::ZERO,
+            wait_for: Vec::new(),
+        }
+    }
+
+    /// What each successive check of a started attempt sees. `Finished` and `Error` end the
+    /// attempt, and anything else means it's still running. The last status repeats once the
+    /// others are used up, and with no statuses at all, the command passes on its first check.
+    pub fn with_statuses(mut self, statuses: impl IntoIterator<Item = CommandStatus>) -> Self {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    pub fn with_stdout(mut self, stdout: &str) -> Self {
+        self.output.stdout = stdout.to_string();
+        self
+    }
+
+    pub fn with_stderr(mut self, stderr: &str) -> Self {
+        self.output.stderr = stderr.to_string();
+        self
+    }
+
+    /// How long each attempt runs before its statuses start being used.
+    pub fn with_start_latency(mut self, latency: Duration) -> Self {
+        self.start_latency = latency;
+        self
+    }
+
+    /// Holds the command back until something accepts TCP connections at each address, as with
+    /// `@wait-for`.
+    pub fn with_wait_for(mut self, addresses: &[&str]) -> Self {
+        self.wait_for = addresses.iter().map(|a| a.to_string()).collect();
+        self
+    }
+
+    pub fn build(self) -> CommandDesc {
+        let command = self
+            .label
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        let mut desc = CommandDesc::new(command).with_launcher(Arc::new(MockLauncher {
+            statuses: self.statuses,
+            output: self.output,
+            start_latency: self.start_latency,
+        }));
+        desc.wait_for = self.wait_for;
+        return desc;
+    }
+}
+
+struct MockLauncher {
+    statuses: Vec<CommandStatus>,
+    output: CommandOutput,
+    start_latency: Duration,
+}
+
+impl Launcher for MockLauncher {
+    fn launch(&self, _command: &CommandDesc) -> io::Result<Box<dyn ProcessHandle>> {
+        Ok(Box::new(MockProcess {
+            statuses: self.statuses.iter().cloned().collect(),
+            output: self.output.clone(),
+            ready_at: Instant::now() + self.start_latency,
+        }))
+    }
+}
+
+struct MockProcess {
+    statuses: VecDeque<CommandStatus>,
+    output: CommandOutput,
+    ready_at: Instant,
+}
+
+impl ProcessHandle for MockProcess {
+    fn try_wait(&mut self) -> io::Result<Option<Option<i32>>> {
+        if Instant::now() < self.ready_at {
+            return Ok(None);
+        }
+        let status = if self.statuses.len() > 1 {
+            self.statuses.pop_front()
+        } else {
+            self.statuses.front().cloned()
+        };
+        match status.unwrap_or(CommandStatus::Finished(0)) {
+            CommandStatus::Finished(code) => Ok(Some(Some(code))),
+            CommandStatus::Error(e) => Err(io::Error::other(e)),
+            CommandStatus::Unstarted | CommandStatus::Running | CommandStatus::Skipped(_) => {
+                Ok(None)
+            }
+        }
+    }
+
+    fn kill(&mut self) {
+        self.statuses = VecDeque::from([CommandStatus::Finished(-1)]);
+    }
+
+    fn finish_output(&mut self) -> CommandOutput {
+        self.output.clone()
+    }
+}
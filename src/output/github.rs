@@ -0,0 +1,73 @@
+use crate::command::{CommandDesc, CommandStatus};
+use std::fmt;
+use std::fmt::Formatter;
+
+pub(crate) struct GithubActionsReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+}
+
+impl GithubActionsReport<'_> {
+    fn escape_data(text: &str) -> String {
+        text.replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    }
+
+    fn escape_property(text: &str) -> String {
+        Self::escape_data(text)
+            .replace(':', "%3A")
+            .replace(',', "%2C")
+    }
+
+    fn write_output(f: &mut Formatter<'_>, text: &str) -> fmt::Result {
+        if text.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{}", text)?;
+        if !text.ends_with('\n') {
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for GithubActionsReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for command in self.commands {
+            let label = command.label();
+            let result = match &command.status {
+                CommandStatus::Finished(0) => "OK",
+                _ if command.is_allowed_failure() => "FAILED (allowed)",
+                CommandStatus::Skipped(_) => "SKIPPED",
+                _ => "FAILED",
+            };
+            writeln!(f, "::group::{} ({})", Self::escape_data(&label), result)?;
+            Self::write_output(f, command.stdout())?;
+            Self::write_output(f, command.stderr())?;
+            writeln!(f, "::endgroup::")?;
+            let message = match &command.status {
+                CommandStatus::Finished(0) | CommandStatus::Skipped(_) => continue,
+                CommandStatus::Finished(code) => {
+                    format!("{} failed with exit code {}", label, code)
+                }
+                CommandStatus::Error(e) => format!("{} failed: {}", label, e),
+                CommandStatus::Unstarted | CommandStatus::Running => {
+                    format!("{} did not finish", label)
+                }
+            };
+            let level = if command.is_allowed_failure() {
+                "warning"
+            } else {
+                "error"
+            };
+            writeln!(
+                f,
+                "::{} title={}::{}",
+                level,
+                Self::escape_property(&label),
+                Self::escape_data(&message)
+            )?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::clock::Clock;
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) struct GitlabCiReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+    /// For the sections' timestamps, when a command didn't start or finish.
+    pub(crate) clock: &'a dyn Clock,
+}
+
+impl GitlabCiReport<'_> {
+    fn write_output(f: &mut Formatter<'_>, text: &str) -> fmt::Result {
+        if text.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{}", text)?;
+        if !text.ends_with('\n') {
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for GitlabCiReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let unix_secs = |time: Option<SystemTime>| {
+            time.unwrap_or_else(|| self.clock.now())
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        for (i, command) in self.commands.iter().enumerate() {
+            let section = format!("multichecks_{}", i + 1);
+            let (result, color) = match &command.status {
+                CommandStatus::Finished(0) => ("OK", Color::Green),
+                _ if command.is_allowed_failure() => ("FAILED (allowed)", Color::Yellow),
+                CommandStatus::Skipped(_) => ("SKIPPED", Color::Gray),
+                _ => ("FAILED", Color::Red),
+            };
+            let collapsed = if command.status.is_error() {
+                ""
+            } else {
+                "[collapsed=true]"
+            };
+            writeln!(
+                f,
+                "\x1b[0Ksection_start:{}:{}{}\r\x1b[0K{}: {}{}{}",
+                unix_secs(command.started_at()),
+                section,
+                collapsed,
+                command.label(),
+                color,
+                result,
+                Color::Normal
+            )?;
+            Self::write_output(f, command.stdout())?;
+            Self::write_output(f, command.stderr())?;
+            writeln!(
+                f,
+                "\x1b[0Ksection_end:{}:{}\r\x1b[0K",
+                unix_secs(command.finished_at()),
+                section
+            )?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,116 @@
+use crate::clock::rfc3339;
+use crate::command::{CommandDesc, CommandStatus};
+use crate::output::status_name;
+use crate::spec;
+use crate::spec::{CommandSpec, SpecFields};
+use serde_json::{json, Value};
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::{Duration, SystemTime};
+
+fn status_fields(status: &CommandStatus) -> (Option<i32>, Option<&str>, Option<&str>) {
+    match status {
+        CommandStatus::Finished(code) => (Some(*code), None, None),
+        CommandStatus::Error(e) => (None, Some(e.as_str()), None),
+        CommandStatus::Skipped(reason) => (None, None, Some(reason.as_str())),
+        CommandStatus::Unstarted | CommandStatus::Running => (None, None, None),
+    }
+}
+
+fn attempt_json(
+    status: &CommandStatus,
+    started_at: Option<SystemTime>,
+    finished_at: Option<SystemTime>,
+    duration: Option<Duration>,
+) -> Value {
+    let (exit_code, error, _) = status_fields(status);
+    json!({
+        "status": status_name(status),
+        "exit_code": exit_code,
+        "error": error,
+        "started_at": started_at.map(rfc3339),
+        "finished_at": finished_at.map(rfc3339),
+        "duration_secs": duration.map(|d| d.as_secs_f64()),
+    })
+}
+
+pub(crate) fn command_json(command: &CommandDesc) -> Value {
+    let (exit_code, error, skip_reason) = status_fields(&command.status);
+    let mut attempts: Vec<Value> = command
+        .attempts()
+        .iter()
+        .map(|a| {
+            let mut attempt = attempt_json(&a.status, a.started_at, a.finished_at, a.duration());
+            // the last attempt's output is the command's own stdout and stderr
+            let output = a.output.clone().unwrap_or_default();
+            attempt["stdout"] = json!(output.stdout);
+            attempt["stderr"] = json!(output.stderr);
+            attempt
+        })
+        .collect();
+    attempts.push(attempt_json(
+        &command.status,
+        command.started_at(),
+        command.finished_at(),
+        command.duration(),
+    ));
+    json!({
+        "command": command.label(),
+        "status": status_name(&command.status),
+        "exit_code": exit_code,
+        "error": error,
+        "skip_reason": skip_reason,
+        "allowed_failure": command.is_allowed_failure(),
+        "rule_note": command.rule_note(),
+        "script": command.script(),
+        "started_at": command.started_at().map(rfc3339),
+        "finished_at": command.finished_at().map(rfc3339),
+        "duration_secs": command.duration().map(|d| d.as_secs_f64()),
+        "attempts": attempts,
+        "stdout": command.stdout(),
+        "stderr": command.stderr(),
+    })
+}
+
+pub(crate) struct JsonReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+    pub(crate) full_spec: bool,
+    /// With `--analyze`, the run's [crate::Analysis].
+    pub(crate) analysis: Option<Value>,
+}
+
+impl fmt::Display for JsonReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let specs: Vec<CommandSpec> = self.commands.iter().map(|c| c.spec()).collect();
+        let (common, deltas) = if self.full_spec {
+            (
+                SpecFields::new(),
+                specs.iter().map(|s| s.fields(true)).collect(),
+            )
+        } else {
+            spec::diff(&specs)
+        };
+        let commands: Vec<Value> = self
+            .commands
+            .iter()
+            .zip(deltas)
+            .map(|(command, delta)| {
+                let mut json = command_json(command);
+                json["spec"] = json!(delta);
+                json
+            })
+            .collect();
+        let mut report = json!({
+            "success": self.commands.iter().all(|c| !c.fails_run()),
+            "commands": commands,
+        });
+        if !self.full_spec {
+            report["common_spec"] = json!(common);
+        }
+        if let Some(analysis) = &self.analysis {
+            report["analysis"] = analysis.clone();
+        }
+        let text = serde_json::to_string_pretty(&report).map_err(|_| fmt::Error)?;
+        writeln!(f, "{}", text)
+    }
+}
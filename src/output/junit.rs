@@ -0,0 +1,101 @@
+use crate::clock::rfc3339;
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::SystemTime;
+
+pub(crate) struct JUnitReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in Color::strip_all(text).chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {} // not representable in XML 1.0
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
+
+impl fmt::Display for JUnitReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let count = |pred: fn(&CommandStatus) -> bool| {
+            self.commands.iter().filter(|c| pred(&c.status)).count()
+        };
+        let failures = count(|s| matches!(s, CommandStatus::Finished(code) if *code != 0));
+        let errors = count(|s| matches!(s, CommandStatus::Error(_)));
+        let time: f64 = self
+            .commands
+            .iter()
+            .filter_map(|c| c.duration())
+            .map(|d| d.as_secs_f64())
+            .sum();
+        let timestamp = |started_at: Option<SystemTime>| match started_at {
+            // JUnit's timestamps are xs:dateTime without a timezone (implicitly UTC)
+            Some(time) => format!(" timestamp=\"{}\"", &rfc3339(time)[..19]),
+            None => String::new(),
+        };
+        let suite_start = self.commands.iter().filter_map(|c| c.started_at()).min();
+        let attrs = format!(
+            "name=\"multichecks\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\"",
+            self.commands.len(),
+            failures,
+            errors,
+            time
+        );
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(f, "<testsuites {}>", attrs)?;
+        writeln!(f, "  <testsuite {}{}>", attrs, timestamp(suite_start))?;
+        for command in self.commands {
+            let time = command.duration().map(|d| d.as_secs_f64()).unwrap_or(0.0);
+            writeln!(
+                f,
+                "    <testcase name=\"{}\" classname=\"multichecks\" time=\"{:.3}\"{}>",
+                escape_xml(&command.label()),
+                time,
+                timestamp(command.started_at())
+            )?;
+            match &command.status {
+                CommandStatus::Finished(0) => {}
+                CommandStatus::Finished(code) => {
+                    writeln!(f, "      <failure message=\"exit code {}\"/>", code)?
+                }
+                CommandStatus::Error(e) => {
+                    writeln!(f, "      <error message=\"{}\"/>", escape_xml(e))?
+                }
+                CommandStatus::Skipped(reason) => {
+                    writeln!(f, "      <skipped message=\"{}\"/>", escape_xml(reason))?
+                }
+                CommandStatus::Unstarted | CommandStatus::Running => {
+                    writeln!(f, "      <skipped/>")?
+                }
+            }
+            if !command.stdout().is_empty() {
+                writeln!(
+                    f,
+                    "      <system-out>{}</system-out>",
+                    escape_xml(command.stdout())
+                )?;
+            }
+            if !command.stderr().is_empty() {
+                writeln!(
+                    f,
+                    "      <system-err>{}</system-err>",
+                    escape_xml(command.stderr())
+                )?;
+            }
+            writeln!(f, "    </testcase>")?;
+        }
+        writeln!(f, "  </testsuite>")?;
+        writeln!(f, "</testsuites>")
+    }
+}
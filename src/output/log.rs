@@ -0,0 +1,156 @@
+use crate::clock::{rfc3339_seconds, Clock, SystemClock};
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use crate::reporter::ProgressReporter;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Writes a timestamped line each time a command starts or finishes, and the output of each
+/// failure right after it. Nothing is ever redrawn, so the log can be `tee`d into a file.
+pub(crate) struct LogReporter {
+    /// Each command's status and start time, as of the last lines written for it.
+    reported: Vec<(CommandStatus, Option<SystemTime>)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl LogReporter {
+    pub(crate) fn new() -> Self {
+        Self {
+            reported: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    fn write_event(
+        out: &mut impl Write,
+        command: &CommandDesc,
+        clock: &dyn Clock,
+    ) -> io::Result<()> {
+        let timestamp = |time| timestamp(time, clock);
+        let label = command.label();
+        let dur = format_secs(command.duration());
+        match &command.status {
+            // started is its own event, since a command can start and finish within one tick
+            CommandStatus::Unstarted | CommandStatus::Running => return Ok(()),
+            CommandStatus::Finished(code) => {
+                let event = if *code == 0 { "EXIT" } else { "FAIL" };
+                writeln!(
+                    out,
+                    "{} {} {} code={} dur={}{}",
+                    timestamp(command.finished_at()),
+                    event,
+                    label,
+                    code,
+                    dur,
+                    allowed_note(command)
+                )?;
+            }
+            CommandStatus::Error(error) => {
+                writeln!(
+                    out,
+                    "{} FAIL {} error={:?} dur={}{}",
+                    timestamp(command.finished_at()),
+                    label,
+                    error,
+                    dur,
+                    allowed_note(command)
+                )?;
+            }
+            CommandStatus::Skipped(reason) => {
+                writeln!(
+                    out,
+                    "{} SKIP {} reason={:?}",
+                    timestamp(command.finished_at()),
+                    label,
+                    reason
+                )?;
+            }
+        }
+        if command.status.is_error() {
+            for text in [command.stdout(), command.stderr()] {
+                let text = Color::strip_all(text);
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                if text.is_empty() {
+                    continue;
+                }
+                for line in text.split('\n') {
+                    writeln!(out, "    {}: {}", label, line.trim_end_matches('\r'))?;
+                }
+            }
+        }
+        return out.flush();
+    }
+}
+
+impl ProgressReporter for LogReporter {
+    fn report(&mut self, commands: &[CommandDesc], _tick: usize) {
+        self.reported
+            .resize(commands.len(), (CommandStatus::Unstarted, None));
+        let mut out = io::stdout().lock();
+        for ((status, started_at), command) in self.reported.iter_mut().zip(commands) {
+            if command.started_at().is_some() && *started_at != command.started_at() {
+                *started_at = command.started_at();
+                let start = timestamp(*started_at, &*self.clock);
+                _ = writeln!(out, "{} START {}", start, command.label());
+            }
+            if *status == command.status {
+                continue;
+            }
+            *status = command.status.clone();
+            _ = Self::write_event(&mut out, command, &*self.clock);
+        }
+        _ = out.flush();
+    }
+
+    fn note(&mut self, message: &str) {
+        let mut out = io::stdout().lock();
+        for line in Color::strip_all(message).lines() {
+            _ = writeln!(out, "{} NOTE {}", timestamp(None, &*self.clock), line);
+        }
+        _ = out.flush();
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+/// The last line of the log: how many commands passed, failed and were skipped.
+pub(crate) fn write_summary(
+    commands: &[CommandDesc],
+    clock: &dyn Clock,
+    out: &mut impl fmt::Write,
+) -> fmt::Result {
+    let count = |f: fn(&CommandDesc) -> bool| commands.iter().filter(|&c| f(c)).count();
+    writeln!(
+        out,
+        "{} DONE passed={} failed={} skipped={}",
+        timestamp(commands.iter().filter_map(|c| c.finished_at()).max(), clock),
+        count(|c| c.status == CommandStatus::Finished(0)),
+        count(|c| c.status.is_error()),
+        count(|c| matches!(c.status, CommandStatus::Skipped(_))),
+    )
+}
+
+/// The time, or now by `clock` if there isn't one, to the second.
+fn timestamp(time: Option<SystemTime>, clock: &dyn Clock) -> String {
+    rfc3339_seconds(time.unwrap_or_else(|| clock.now()))
+}
+
+fn format_secs(duration: Option<Duration>) -> String {
+    match duration {
+        Some(d) => format!("{:.1}s", d.as_secs_f64()),
+        None => "-".to_string(),
+    }
+}
+
+fn allowed_note(command: &CommandDesc) -> &'static str {
+    if command.is_allowed_failure() {
+        " allowed-failure"
+    } else {
+        ""
+    }
+}
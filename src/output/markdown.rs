@@ -0,0 +1,82 @@
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use crate::durations::format_duration;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// A summary table followed by each failure's output, for pasting into a PR or wiki.
+pub(crate) struct MarkdownReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+}
+
+impl MarkdownReport<'_> {
+    fn emoji(command: &CommandDesc) -> &'static str {
+        match command.status {
+            CommandStatus::Finished(0) => "✅",
+            _ if command.is_allowed_failure() => "⚠️",
+            CommandStatus::Finished(_) | CommandStatus::Error(_) => "❌",
+            CommandStatus::Skipped(_) => "⏭️",
+            CommandStatus::Unstarted | CommandStatus::Running => "⏳",
+        }
+    }
+
+    fn html_escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    fn write_output(f: &mut Formatter<'_>, command: &CommandDesc) -> fmt::Result {
+        let mut output = Color::strip_escapes(command.stdout());
+        output.push_str(&Color::strip_escapes(command.stderr()));
+        let output = output.trim_end_matches('\n');
+        if output.is_empty() {
+            return writeln!(f, "_(no output)_");
+        }
+        // the fence has to be longer than any run of backticks in the output
+        let mut fence = "```".to_string();
+        while output.contains(&fence) {
+            fence.push('`');
+        }
+        writeln!(f, "{}", fence)?;
+        writeln!(f, "{}", output)?;
+        writeln!(f, "{}", fence)
+    }
+}
+
+impl fmt::Display for MarkdownReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "| Command | Status | Exit code | Duration |")?;
+        writeln!(f, "|---|:---:|---:|---:|")?;
+        for command in self.commands {
+            let exit_code = match &command.status {
+                CommandStatus::Finished(code) => code.to_string(),
+                _ => "—".to_string(),
+            };
+            let duration = command.duration().map_or("—".to_string(), format_duration);
+            writeln!(
+                f,
+                "| `{}` | {} | {} | {} |",
+                command.label().replace('|', "\\|"),
+                Self::emoji(command),
+                exit_code,
+                duration
+            )?;
+        }
+        for command in self.commands.iter().filter(|c| c.status.is_error()) {
+            writeln!(f)?;
+            writeln!(f, "<details>")?;
+            writeln!(
+                f,
+                "<summary><code>{}</code>: {}</summary>",
+                Self::html_escape(&command.label()),
+                Self::html_escape(&command.status.to_string())
+            )?;
+            writeln!(f)?;
+            Self::write_output(f, command)?;
+            writeln!(f)?;
+            writeln!(f, "</details>")?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,42 @@
+use crate::command::CommandStatus;
+use strum::{EnumString, EnumVariantNames};
+
+pub(crate) mod github;
+pub(crate) mod gitlab;
+pub(crate) mod json;
+pub(crate) mod junit;
+pub(crate) mod log;
+pub(crate) mod markdown;
+pub(crate) mod ndjson;
+pub(crate) mod plain;
+pub(crate) mod tap;
+
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, EnumString, EnumVariantNames, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Pretty,
+    Plain,
+    Json,
+    Tap,
+    Ndjson,
+    Log,
+    #[strum(serialize = "junit")]
+    JUnit,
+    GithubActions,
+    GitlabCi,
+    Table,
+}
+
+pub(crate) fn status_name(status: &CommandStatus) -> &'static str {
+    match status {
+        CommandStatus::Unstarted => "unstarted",
+        CommandStatus::Running => "running",
+        CommandStatus::Finished(0) => "passed",
+        CommandStatus::Finished(_) => "failed",
+        CommandStatus::Error(_) => "error",
+        CommandStatus::Skipped(_) => "skipped",
+    }
+}
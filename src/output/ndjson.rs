@@ -0,0 +1,106 @@
+use crate::clock::{rfc3339, Clock, SystemClock};
+use crate::command::{CommandDesc, CommandStatus, WaitReason};
+use crate::output::json::command_json;
+use crate::reporter::ProgressReporter;
+use serde_json::json;
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Emits an event each time a command starts or finishes, or starts waiting for something else.
+pub(crate) struct NdjsonReporter {
+    reported: Vec<(CommandStatus, Option<WaitReason>)>,
+    /// Each command's start time, as of its last `started` event.
+    started: Vec<Option<SystemTime>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl NdjsonReporter {
+    pub(crate) fn new() -> Self {
+        Self {
+            reported: Vec::new(),
+            started: Vec::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl ProgressReporter for NdjsonReporter {
+    fn report(&mut self, commands: &[CommandDesc], _tick: usize) {
+        self.reported
+            .resize(commands.len(), (CommandStatus::Unstarted, None));
+        self.started.resize(commands.len(), None);
+        for ((previous, started_at), command) in
+            (self.reported.iter_mut().zip(self.started.iter_mut())).zip(commands)
+        {
+            // a command can start and finish within one tick, so this isn't tied to Running
+            if command.started_at().is_some() && *started_at != command.started_at() {
+                *started_at = command.started_at();
+                let event = json!({
+                    "event": "started",
+                    "command": command.label(),
+                    "started_at": started_at.map(rfc3339),
+                });
+                println!("{}", event);
+            }
+            let current = (command.status.clone(), command.wait_reason().cloned());
+            if *previous == current {
+                continue;
+            }
+            *previous = current;
+            let event = match &command.status {
+                CommandStatus::Unstarted => {
+                    let Some(reason) = command.wait_reason() else {
+                        continue;
+                    };
+                    let waiting_on = match reason {
+                        WaitReason::Dependencies(labels) => labels.clone(),
+                        WaitReason::Readiness(address) => vec![address.clone()],
+                        WaitReason::Mutex(name) => vec![name.clone()],
+                        WaitReason::Jobs | WaitReason::Delay(_) | WaitReason::Stage(_) => {
+                            Vec::new()
+                        }
+                    };
+                    json!({
+                        "event": "waiting",
+                        "command": command.label(),
+                        "reason": reason.kind(),
+                        "waiting_on": waiting_on,
+                        "message": reason.describe(&*self.clock),
+                    })
+                }
+                CommandStatus::Running => continue,
+                CommandStatus::Finished(_)
+                | CommandStatus::Error(_)
+                | CommandStatus::Skipped(_) => {
+                    let mut event = command_json(command);
+                    event["event"] = json!("finished");
+                    // output is only complete once the run is done; it's in the summary event
+                    if let Some(obj) = event.as_object_mut() {
+                        obj.remove("stdout");
+                        obj.remove("stderr");
+                    }
+                    event
+                }
+            };
+            println!("{}", event);
+        }
+    }
+
+    fn note(&mut self, message: &str) {
+        println!("{}", json!({"event": "note", "message": message}));
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+pub(crate) fn write_summary(commands: &[CommandDesc], out: &mut impl fmt::Write) -> fmt::Result {
+    let event = json!({
+        "event": "summary",
+        "success": commands.iter().all(|c| !c.fails_run()),
+        "commands": commands.iter().map(command_json).collect::<Vec<_>>(),
+    });
+    writeln!(out, "{}", event)
+}
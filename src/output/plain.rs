@@ -0,0 +1,90 @@
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+pub(crate) struct PlainReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+    pub(crate) durations: bool,
+}
+
+impl PlainReport<'_> {
+    const STATUS_WIDTH: usize = 7;
+    const DURATION_WIDTH: usize = 8;
+
+    fn format_secs(duration: Option<Duration>, prefix: &str) -> String {
+        match duration {
+            Some(d) => format!("{}{:.1}s", prefix, d.as_secs_f64()),
+            None => "-".to_string(),
+        }
+    }
+
+    fn status_text(status: &CommandStatus) -> &'static str {
+        match status {
+            CommandStatus::Unstarted | CommandStatus::Running => "?",
+            CommandStatus::Finished(0) => "OK",
+            CommandStatus::Finished(_) | CommandStatus::Error(_) => "FAILED",
+            CommandStatus::Skipped(_) => "SKIPPED",
+        }
+    }
+
+    fn write_output(f: &mut Formatter<'_>, stream: &str, text: &str) -> fmt::Result {
+        let text = Color::strip_all(text);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        if text.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "  {}:", stream)?;
+        for line in text.split('\n') {
+            writeln!(f, "  | {}", line.trim_end_matches('\r'))?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for PlainReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let run_started = self.commands.iter().filter_map(|c| c.started_at()).min();
+        for command in self.commands {
+            write!(
+                f,
+                "{:<width$}",
+                Self::status_text(&command.status),
+                width = Self::STATUS_WIDTH
+            )?;
+            if self.durations {
+                let offset = match (run_started, command.started_at()) {
+                    (Some(run_started), Some(started)) => started.duration_since(run_started).ok(),
+                    _ => None,
+                };
+                write!(
+                    f,
+                    " {:>width$} {:>width$}",
+                    Self::format_secs(offset, "+"),
+                    Self::format_secs(command.duration(), ""),
+                    width = Self::DURATION_WIDTH
+                )?;
+            }
+            writeln!(f, "  {}", command.label())?;
+        }
+        for command in self.commands {
+            if !command.status.is_error() {
+                continue;
+            }
+            writeln!(f)?;
+            writeln!(f, "== {}", command.label())?;
+            if command.is_allowed_failure() {
+                writeln!(f, "  failure allowed")?;
+            }
+            match &command.status {
+                CommandStatus::Finished(code) => writeln!(f, "  exit code: {}", code)?,
+                CommandStatus::Error(e) => writeln!(f, "  error: {}", e)?,
+                CommandStatus::Unstarted | CommandStatus::Running | CommandStatus::Skipped(_) => {}
+            }
+            Self::write_output(f, "stdout", command.stdout())?;
+            Self::write_output(f, "stderr", command.stderr())?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,60 @@
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use std::fmt;
+use std::fmt::Formatter;
+
+pub(crate) struct TapReport<'a> {
+    pub(crate) commands: &'a [CommandDesc],
+}
+
+impl TapReport<'_> {
+    fn write_block(f: &mut Formatter<'_>, key: &str, text: &str) -> fmt::Result {
+        let text = Color::strip_all(text);
+        let text = text.trim_end_matches('\n');
+        if text.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "  {}: |", key)?;
+        for line in text.split('\n') {
+            writeln!(f, "    {}", line.trim_end_matches('\r'))?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for TapReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "TAP version 13")?;
+        writeln!(f, "1..{}", self.commands.len())?;
+        for (i, command) in self.commands.iter().enumerate() {
+            let ok = if command.status.is_success() {
+                "ok"
+            } else {
+                "not ok"
+            };
+            if let CommandStatus::Skipped(reason) = &command.status {
+                writeln!(f, "ok {} - {} # SKIP {}", i + 1, command.label(), reason)?;
+                continue;
+            }
+            write!(f, "{} {} - {}", ok, i + 1, command.label())?;
+            if command.is_allowed_failure() {
+                // TAP's TODO directive is for failures that shouldn't fail the run
+                write!(f, " # TODO failure allowed")?;
+            }
+            writeln!(f)?;
+            if !command.status.is_error() {
+                continue;
+            }
+            writeln!(f, "  ---")?;
+            match &command.status {
+                CommandStatus::Finished(code) => writeln!(f, "  exit_code: {}", code)?,
+                CommandStatus::Error(e) => writeln!(f, "  error: {:?}", e)?,
+                CommandStatus::Unstarted | CommandStatus::Running | CommandStatus::Skipped(_) => {}
+            }
+            Self::write_block(f, "stdout", command.stdout())?;
+            Self::write_block(f, "stderr", command.stderr())?;
+            writeln!(f, "  ...")?;
+        }
+        return Ok(());
+    }
+}
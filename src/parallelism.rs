@@ -0,0 +1,152 @@
+use crate::command::WaitReason;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const LOAD_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much `@weight` may be running at once.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParallelismPolicy {
+    Fixed(usize),
+    /// Up to `max`, backing off towards `min` when the system load average is high.
+    Range {
+        min: usize,
+        max: usize,
+    },
+}
+
+impl ParallelismPolicy {
+    pub fn slots(&self, load: Option<f64>) -> usize {
+        match *self {
+            ParallelismPolicy::Fixed(n) => n,
+            ParallelismPolicy::Range { min, max } => {
+                let threshold = max.saturating_sub(1) as f64;
+                match load {
+                    Some(load) if load > threshold => {
+                        let excess = (load - threshold).ceil() as usize;
+                        max.saturating_sub(excess).max(min)
+                    }
+                    _ => max,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ParallelismPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("expected a number or a range like 1..8, got {:?}", s))
+        };
+        let Some((min, max)) = s.split_once("..") else {
+            return Ok(ParallelismPolicy::Fixed(parse(s)?));
+        };
+        let (min, max) = (parse(min)?, parse(max)?);
+        if min == 0 || min > max {
+            return Err(format!("invalid range {:?}: need 1 <= min <= max", s));
+        }
+        Ok(ParallelismPolicy::Range { min, max })
+    }
+}
+
+impl fmt::Display for ParallelismPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParallelismPolicy::Fixed(n) => write!(f, "{}", n),
+            ParallelismPolicy::Range { min, max } => write!(f, "{}..{}", min, max),
+        }
+    }
+}
+
+/// How the scheduler decides whether a command that's otherwise ready may start, given what's
+/// already running.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ConcurrencyMode {
+    /// Everything starts as soon as it's ready.
+    #[default]
+    Parallel,
+    /// As much `@weight` as the policy allows runs at once.
+    Capped(ParallelismPolicy),
+    /// One command at a time, whatever its weight.
+    Sequential,
+    /// By `@stage`, lowest first: a command starts only once every command in earlier stages has
+    /// finished (or been skipped). Commands in the same stage run in parallel.
+    Staged,
+}
+
+/// What's going on in a run, as far as [ConcurrencyMode] cares.
+pub(crate) struct Occupancy {
+    pub(crate) running: usize,
+    pub(crate) running_weight: usize,
+    /// The lowest `@stage` of any command that hasn't finished.
+    pub(crate) current_stage: Option<usize>,
+}
+
+impl ConcurrencyMode {
+    /// Why a command of this `weight` and `stage` has to wait, if it does.
+    pub(crate) fn hold(
+        &self,
+        weight: usize,
+        stage: usize,
+        occupancy: &Occupancy,
+        load: &mut LoadMonitor,
+    ) -> Option<WaitReason> {
+        match *self {
+            ConcurrencyMode::Parallel => None,
+            ConcurrencyMode::Capped(policy) => {
+                let capacity = policy.slots(load.load());
+                // a command heavier than the whole capacity can still run, as long as it's alone
+                let fits =
+                    occupancy.running_weight == 0 || occupancy.running_weight + weight <= capacity;
+                (!fits).then_some(WaitReason::Jobs)
+            }
+            ConcurrencyMode::Sequential => (occupancy.running > 0).then_some(WaitReason::Jobs),
+            ConcurrencyMode::Staged => match occupancy.current_stage {
+                Some(current) if current < stage => Some(WaitReason::Stage(current)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Samples the 1-minute load average, at most once per [LOAD_SAMPLE_INTERVAL].
+#[derive(Default)]
+pub(crate) struct LoadMonitor {
+    last_sample: Option<(Instant, Option<f64>)>,
+}
+
+impl LoadMonitor {
+    pub(crate) fn load(&mut self) -> Option<f64> {
+        if let Some((at, load)) = self.last_sample {
+            if at.elapsed() < LOAD_SAMPLE_INTERVAL {
+                return load;
+            }
+        }
+        let load = Self::read_load();
+        self.last_sample = Some((Instant::now(), load));
+        return load;
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read_load() -> Option<f64> {
+        let contents = std::fs::read_to_string("/proc/loadavg").ok()?;
+        contents.split_whitespace().next()?.parse().ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn read_load() -> Option<f64> {
+        // macOS and the BSDs print something like "{ 1.23 1.45 1.67 }"
+        let output = std::process::Command::new("sysctl")
+            .args(["-n", "vm.loadavg"])
+            .output()
+            .ok()?;
+        let text = String::from_utf8(output.stdout).ok()?;
+        text.split_whitespace().find_map(|token| token.parse().ok())
+    }
+}
@@ -0,0 +1,138 @@
+use crate::capture::Capture;
+use crate::command::{CommandDesc, CommandOutput};
+use crate::container;
+use encoding_rs::Encoding;
+use std::io;
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long to wait for a process's output streams to close after it exits. Background processes
+/// that inherited them can keep them open indefinitely, so we give up on them after this.
+const CLOSE_GRACE: Duration = Duration::from_secs(1);
+
+/// A started command, which [CommandDesc] polls until it exits.
+pub trait ProcessHandle: Send {
+    /// `Ok(None)` while it's still running. Once it has exited, its exit code, or `None` if it
+    /// didn't have one (e.g. it was killed by a signal).
+    fn try_wait(&mut self) -> io::Result<Option<Option<i32>>>;
+
+    /// Stops the process, and waits for it to exit.
+    fn kill(&mut self);
+
+    /// Everything the process wrote. Called once, after it has exited or been killed.
+    fn finish_output(&mut self) -> CommandOutput;
+
+    /// How far along the process's output says it is, from 0 to 1, if the command has a
+    /// `@progress` pattern and the output has matched it.
+    fn progress(&self) -> Option<f64> {
+        None
+    }
+
+    /// The OS's process ID, if it's a real process.
+    fn pid(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Starts a command's process. Each attempt of a retried command is launched separately.
+pub trait Launcher: Send + Sync {
+    fn launch(&self, command: &CommandDesc) -> io::Result<Box<dyn ProcessHandle>>;
+}
+
+/// Runs commands as child processes, capturing their stdout and stderr.
+pub(crate) struct ProcessLauncher;
+
+impl Launcher for ProcessLauncher {
+    fn launch(&self, command: &CommandDesc) -> io::Result<Box<dyn ProcessHandle>> {
+        let container = command.container.as_ref().map(|_| container::unique_name());
+        let Some(mut process) = command.build_command(container.as_deref()) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+        };
+        process.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = process.spawn()?;
+        let captures = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(stderr)) => {
+                let pattern = command.progress_pattern.clone();
+                Some((
+                    Capture::spawn(stdout, pattern.clone(), command.output_budget.clone()),
+                    Capture::spawn(stderr, pattern, command.output_budget.clone()),
+                ))
+            }
+            _ => None,
+        };
+        return Ok(Box::new(ChildProcess {
+            child,
+            captures,
+            exited: None,
+            container,
+            encoding: command.encoding,
+        }));
+    }
+}
+
+struct ChildProcess {
+    child: Child,
+    captures: Option<(Capture, Capture)>,
+    /// Its exit code, and when it was found to have exited, once it has.
+    exited: Option<(Option<i32>, Instant)>,
+    /// The name of the container it's running in, with `--docker`.
+    container: Option<String>,
+    /// What its output is encoded in, from `@encoding`.
+    encoding: Option<&'static Encoding>,
+}
+
+impl ProcessHandle for ChildProcess {
+    /// It counts as still running until its output streams have closed too, so that none of its
+    /// output is missed, or until [CLOSE_GRACE] after it exits. That's polled rather than waited
+    /// for, so that the run carries on meanwhile.
+    fn try_wait(&mut self) -> io::Result<Option<Option<i32>>> {
+        let (code, exited_at) = match self.exited {
+            Some(exited) => exited,
+            None => match self.child.try_wait()? {
+                Some(status) => *self.exited.insert((status.code(), Instant::now())),
+                None => return Ok(None),
+            },
+        };
+        let closed =
+            (self.captures.as_ref()).is_none_or(|(out, err)| out.is_closed() && err.is_closed());
+        if closed || exited_at.elapsed() >= CLOSE_GRACE {
+            return Ok(Some(code));
+        }
+        return Ok(None);
+    }
+
+    fn kill(&mut self) {
+        if let Some(name) = &self.container {
+            container::kill(name);
+        }
+        _ = self.child.kill();
+        _ = self.child.wait();
+    }
+
+    fn pid(&self) -> Option<u32> {
+        Some(self.child.id())
+    }
+
+    fn progress(&self) -> Option<f64> {
+        let (stdout, stderr) = self.captures.as_ref()?;
+        // whichever stream reported progress last
+        let latest = stdout.progress().into_iter().chain(stderr.progress());
+        latest
+            .max_by_key(|(at, _)| *at)
+            .map(|(_, progress)| progress)
+    }
+
+    fn finish_output(&mut self) -> CommandOutput {
+        let Some((stdout, stderr)) = self.captures.take() else {
+            return CommandOutput::default();
+        };
+        let (stdout, stdout_times, stdout_charged) = stdout.finish(self.encoding);
+        let (stderr, stderr_times, stderr_charged) = stderr.finish(self.encoding);
+        CommandOutput {
+            stdout,
+            stderr,
+            line_times: Some((stdout_times, stderr_times)),
+            charged: stdout_charged + stderr_charged,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::command::{CommandDesc, CommandStatus};
+use regex::Regex;
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// How to find a command's progress in its output, from `@progress`: either a preset's name, or
+/// a regex whose first group is a percentage, or whose first two groups are like `23` and `118`
+/// in `23/118`.
+#[derive(Clone, Debug)]
+pub struct ProgressPattern {
+    regex: Regex,
+}
+
+impl ProgressPattern {
+    const PRESETS: [(&'static str, &'static str); 3] = [
+        // `Building [=====>    ] 45/118: foo(bin)`, which cargo only shows on a terminal
+        ("cargo", r"Building \[[=> ]*\] (\d+)/(\d+)"),
+        // `[ 45%] Building CXX object ...`
+        ("cmake", r"^\[\s*(\d+)%\]"),
+        // `tests/test_foo.py ....    [ 45%]`
+        ("pytest", r"\[\s*(\d+)%\]\s*$"),
+    ];
+
+    /// How far along a line says the command is, from 0 to 1.
+    pub(crate) fn progress(&self, line: &str) -> Option<f64> {
+        let captures = self.regex.captures(line)?;
+        let number = |i: usize| captures.get(i)?.as_str().parse::<f64>().ok();
+        let fraction = match (number(1)?, number(2)) {
+            (done, Some(total)) if total > 0.0 => done / total,
+            (_, Some(_)) => return None,
+            (percent, None) => percent / 100.0,
+        };
+        return Some(fraction.clamp(0.0, 1.0));
+    }
+}
+
+impl FromStr for ProgressPattern {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let pattern = Self::PRESETS
+            .iter()
+            .find(|(name, _)| *name == spec)
+            .map_or(spec, |(_, pattern)| pattern);
+        let regex = Regex::new(pattern).map_err(|e| format!("invalid @progress: {}", e))?;
+        if regex.captures_len() < 2 {
+            return Err(format!(
+                "@progress {:?} needs a group for the percentage (or two, for done/total)",
+                spec
+            ));
+        }
+        Ok(Self { regex })
+    }
+}
+
+impl fmt::Display for ProgressPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.regex)
+    }
+}
+
+/// A bar like `▓▓▓▓░░░░░░ 45%`.
+pub(crate) fn progress_bar(fraction: f64) -> String {
+    const CELLS: usize = 10;
+    let filled = ((fraction * CELLS as f64).round() as usize).min(CELLS);
+    return format!(
+        "{}{} {:.0}%",
+        "▓".repeat(filled),
+        "░".repeat(CELLS - filled),
+        fraction * 100.0
+    );
+}
+
+/// The progress of every command with a `@progress` pattern, weighted by `@weight`: those that
+/// finished count as done, and those that haven't started (or reported anything) as not
+/// started. `None` if no command has a pattern.
+pub(crate) fn overall_progress(commands: &[CommandDesc]) -> Option<f64> {
+    let mut done = 0.0;
+    let mut total = 0.0;
+    for command in commands.iter().filter(|c| c.progress_pattern.is_some()) {
+        let weight = command.weight as f64;
+        total += weight;
+        done += weight
+            * match &command.status {
+                CommandStatus::Unstarted => 0.0,
+                CommandStatus::Running => command.progress().unwrap_or(0.0),
+                _ => 1.0,
+            };
+    }
+    return (total > 0.0).then(|| done / total);
+}
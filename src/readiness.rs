@@ -0,0 +1,71 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How long to give each connection attempt.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long to wait between attempts.
+const RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Waits on a background thread until something accepts TCP connections at each of a command's
+/// `@wait-for` addresses, in turn. It stops trying when dropped.
+pub(crate) struct ReadinessProbe {
+    /// The address it's still waiting for, or `None` once they're all up.
+    pending: Arc<Mutex<Option<String>>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ReadinessProbe {
+    pub(crate) fn spawn(addresses: Vec<String>) -> Self {
+        let pending = Arc::new(Mutex::new(addresses.first().cloned()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (thread_pending, thread_stop) = (Arc::clone(&pending), Arc::clone(&stop));
+        thread::spawn(move || {
+            for address in &addresses {
+                if let Ok(mut pending) = thread_pending.lock() {
+                    *pending = Some(address.clone());
+                }
+                while !accepts_connections(address) {
+                    if thread_stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+            }
+            if let Ok(mut pending) = thread_pending.lock() {
+                *pending = None;
+            }
+        });
+        ReadinessProbe { pending, stop }
+    }
+
+    /// The address it's still waiting for, or `None` once they're all up.
+    pub(crate) fn pending(&self) -> Option<String> {
+        match self.pending.lock() {
+            Ok(pending) => pending.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl Drop for ReadinessProbe {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether something at `address` (like `db:5432`) accepts a TCP connection.
+fn accepts_connections(address: &str) -> bool {
+    let Ok(resolved) = address.to_socket_addrs() else {
+        // the name may not resolve until whatever provides it is up, too
+        return false;
+    };
+    for socket_address in resolved {
+        if TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).is_ok() {
+            return true;
+        }
+    }
+    return false;
+}
@@ -0,0 +1,621 @@
+use crate::clock::{Clock, SystemClock};
+use crate::color::Color;
+use crate::command::{CommandDesc, CommandStatus};
+use crate::commands::{DisplayOrder, StatusSection};
+use crate::durations::{format_duration, spoken_duration};
+use crate::progress::overall_progress;
+use crate::terminal::Terminal;
+use std::fmt::Write;
+use std::io;
+use std::io::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use strum::{EnumString, EnumVariantNames};
+
+/// Which built-in [ProgressReporter] to show while commands run.
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, EnumString, EnumVariantNames, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ProgressStyle {
+    /// [DefaultProgressReporter]
+    #[default]
+    Dashboard,
+    /// [TickerReporter]
+    Ticker,
+    /// [DotsReporter]
+    Dots,
+}
+
+impl ProgressStyle {
+    pub fn reporter(self) -> Box<dyn ProgressReporter> {
+        match self {
+            ProgressStyle::Dashboard => Box::new(DefaultProgressReporter::new()),
+            ProgressStyle::Ticker => Box::new(TickerReporter::new()),
+            ProgressStyle::Dots => Box::new(DotsReporter::new()),
+        }
+    }
+}
+
+/// Renders progress while commands run. `report` is called once per tick, after commands have
+/// been started and checked. Reporters are `Send`, so that `Commands::run_async` can run them on
+/// its own thread.
+pub trait ProgressReporter: Send {
+    fn report(&mut self, commands: &[CommandDesc], tick: usize);
+
+    /// Called once after the last tick, before the final details are printed.
+    fn finish(&mut self, _commands: &[CommandDesc]) {}
+
+    /// For reporters that show every command at once, the order to show them in.
+    fn set_display_order(&mut self, _order: DisplayOrder) {}
+
+    /// For reporters that show how long things have taken, where to get the time from.
+    fn set_clock(&mut self, _clock: Arc<dyn Clock>) {}
+
+    /// Shows a message from outside the reporter, without disturbing what it's drawn.
+    fn note(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
+}
+
+/// The live dashboard: one line per command, redrawn in place each tick.
+pub struct DefaultProgressReporter {
+    terminal: Terminal,
+    set_title: bool,
+    reported: Vec<CommandStatus>,
+    display_order: DisplayOrder,
+    summary_interval: Option<Duration>,
+    last_summary: Option<SystemTime>,
+    columns: bool,
+    column_headers: Option<(String, String)>,
+    clock: Arc<dyn Clock>,
+}
+
+impl DefaultProgressReporter {
+    /// The most running commands to name in a periodic summary.
+    const MAX_SUMMARY_RUNNING: usize = 3;
+    /// The space between the two columns of the two-column layout.
+    const COLUMN_GAP: usize = 4;
+
+    pub fn new() -> Self {
+        Self {
+            terminal: Terminal::for_stdout(),
+            set_title: false,
+            reported: Vec::new(),
+            display_order: DisplayOrder::default(),
+            summary_interval: None,
+            last_summary: None,
+            columns: false,
+            column_headers: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Draws to the given terminal, rather than one for stdout.
+    pub fn with_terminal(mut self, terminal: Terminal) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    /// Also show progress in the terminal's title.
+    pub fn with_terminal_title(mut self, set_title: bool) -> Self {
+        self.set_title = set_title;
+        self
+    }
+
+    pub fn with_display_order(mut self, order: DisplayOrder) -> Self {
+        self.display_order = order;
+        self
+    }
+
+    /// Shows the commands in two columns side by side (the first half on the left), when they fit
+    /// in the terminal's width.
+    pub fn with_columns(mut self, columns: bool) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Headers to show in bold above the left and right columns. Implies
+    /// [DefaultProgressReporter::with_columns].
+    pub fn with_column_headers(mut self, headers: Option<(String, String)>) -> Self {
+        self.columns |= headers.is_some();
+        self.column_headers = headers;
+        self
+    }
+
+    /// Draws the two-column layout. Returns false, without drawing anything, if it doesn't fit.
+    fn report_columns(&mut self, commands: &[CommandDesc], tick: usize) -> bool {
+        let summaries: Vec<String> = self
+            .display_order
+            .arrange(commands)
+            .iter()
+            .map(|&index| {
+                let mut summary = String::new();
+                commands[index].print_summary(tick, &*self.clock, &mut summary);
+                summary
+            })
+            .collect();
+        let (left, right) = summaries.split_at(summaries.len().div_ceil(2));
+        let (left_header, right_header) = match &self.column_headers {
+            Some((left, right)) => (left.as_str(), right.as_str()),
+            None => ("", ""),
+        };
+        let visible_width = |text: &str| Color::strip_escapes(text).chars().count();
+        let column_width = |header: &str, column: &[String]| {
+            let widths = column.iter().map(|s| visible_width(s));
+            widths.chain([visible_width(header)]).max().unwrap_or(0)
+        };
+        let left_width = column_width(left_header, left) + Self::COLUMN_GAP;
+        let right_width = column_width(right_header, right);
+        if self
+            .terminal
+            .width()
+            .is_some_and(|width| left_width + right_width >= width)
+        {
+            return false;
+        }
+        let out = &mut self.terminal;
+        out.reset();
+        let pad = |text: &str| " ".repeat(left_width.saturating_sub(visible_width(text)));
+        if self.column_headers.is_some() {
+            let bold = Color::Other(1);
+            _ = write!(
+                out,
+                "{}{}{}{}",
+                bold,
+                left_header,
+                Color::Normal,
+                pad(left_header)
+            );
+            _ = writeln!(out, "{}{}{}", bold, right_header, Color::Normal);
+        }
+        for (i, summary) in left.iter().enumerate() {
+            _ = write!(out, "{}", summary);
+            if let Some(right) = right.get(i) {
+                _ = write!(out, "{}{}", pad(summary), right);
+            }
+            _ = writeln!(out);
+        }
+        return true;
+    }
+
+    /// On an append-only terminal, also print a one-line summary this often while commands are
+    /// running, so that a long quiet stretch still produces some output.
+    pub fn with_summary_interval(mut self, interval: Option<Duration>) -> Self {
+        self.summary_interval = interval;
+        self
+    }
+
+    fn report_summary(&mut self, commands: &[CommandDesc]) {
+        let (Some(interval), Some(run_started)) = (
+            self.summary_interval,
+            commands.iter().filter_map(|c| c.started_at()).min(),
+        ) else {
+            return;
+        };
+        let now = self.clock.now();
+        let last = self.last_summary.unwrap_or(run_started);
+        if now.duration_since(last).unwrap_or_default() < interval {
+            return;
+        }
+        self.last_summary = Some(now);
+        let mut running: Vec<&CommandDesc> = commands
+            .iter()
+            .filter(|c| c.status == CommandStatus::Running)
+            .collect();
+        if running.is_empty() {
+            return;
+        }
+        running.sort_by_key(|c| c.started_at());
+        let elapsed = |started: Option<SystemTime>| {
+            format_duration(
+                started
+                    .and_then(|s| now.duration_since(s).ok())
+                    .unwrap_or_default(),
+            )
+        };
+        let done = commands
+            .iter()
+            .filter(|c| c.status.is_terminal_state())
+            .count();
+        let failed = commands.iter().filter(|c| c.status.is_error()).count();
+        let mut line = format!(
+            "[{}] {}/{} done, {} failed, running: ",
+            elapsed(Some(run_started)),
+            done,
+            commands.len(),
+            failed
+        );
+        let named: Vec<String> = running
+            .iter()
+            .take(Self::MAX_SUMMARY_RUNNING)
+            .map(|c| format!("{} ({})", c.summary_label(), elapsed(c.started_at())))
+            .collect();
+        line.push_str(&named.join(", "));
+        if running.len() > named.len() {
+            line.push_str(&format!(" and {} more", running.len() - named.len()));
+        }
+        _ = writeln!(self.terminal, "{}", line);
+    }
+
+    /// Since an append-only terminal can't redraw the dashboard, print a line for each change.
+    fn report_transitions(&mut self, commands: &[CommandDesc]) {
+        self.reported
+            .resize(commands.len(), CommandStatus::Unstarted);
+        for (previous, command) in self.reported.iter_mut().zip(commands) {
+            if *previous == command.status {
+                continue;
+            }
+            let out = &mut self.terminal;
+            match (&previous, &command.status) {
+                (_, CommandStatus::Running) => {
+                    _ = writeln!(out, "{}: started", command.summary_label())
+                }
+                (CommandStatus::Running, CommandStatus::Unstarted) => {
+                    _ = writeln!(out, "{}: failed, will retry", command.summary_label())
+                }
+                (_, CommandStatus::Unstarted) => {}
+                _ => {
+                    command.print_summary(0, &*self.clock, out);
+                    _ = writeln!(out);
+                }
+            }
+            *previous = command.status.clone();
+        }
+    }
+
+    fn progress_title(commands: &[CommandDesc]) -> String {
+        let done = commands
+            .iter()
+            .filter(|c| c.status.is_terminal_state())
+            .count();
+        let failed = commands.iter().filter(|c| c.status.is_error()).count();
+        let mut title = format!("checks {}/{}", done, commands.len());
+        if let Some(progress) = overall_progress(commands) {
+            title.push_str(&format!(" · {:.0}%", progress * 100.0));
+        }
+        if failed > 0 {
+            title.push_str(&format!(" ({} failed)", failed));
+        }
+        return title;
+    }
+}
+
+impl Default for DefaultProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter for DefaultProgressReporter {
+    fn report(&mut self, commands: &[CommandDesc], tick: usize) {
+        if self.terminal.is_append_only() {
+            self.report_transitions(commands);
+            self.report_summary(commands);
+            return;
+        }
+        if !(self.columns && self.report_columns(commands, tick)) {
+            let out = &mut self.terminal;
+            out.reset();
+            let last_commands_idx = commands.len();
+            let mut section = None;
+            for (i, &index) in self.display_order.arrange(commands).iter().enumerate() {
+                if self.display_order == DisplayOrder::ByStatus {
+                    let current = StatusSection::of(&commands[index]);
+                    if section != Some(current) {
+                        section = Some(current);
+                        let count = commands
+                            .iter()
+                            .filter(|c| StatusSection::of(c) == current)
+                            .count();
+                        let (header, color) = current.header();
+                        let bold = Color::Other(1);
+                        _ = writeln!(out, "{}{}{} ({})\x1b[0m", bold, color, header, count);
+                    }
+                }
+                commands[index].print_summary(tick, &*self.clock, out);
+                if i != last_commands_idx {
+                    _ = writeln!(out);
+                }
+            }
+        }
+        let out = &mut self.terminal;
+        if self.set_title {
+            out.set_title(Some(&Self::progress_title(commands)));
+        }
+        out.end_frame();
+    }
+
+    fn set_display_order(&mut self, order: DisplayOrder) {
+        self.display_order = order;
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn note(&mut self, message: &str) {
+        self.terminal.note(message);
+    }
+
+    fn finish(&mut self, _commands: &[CommandDesc]) {
+        if self.set_title {
+            self.terminal.set_title(None);
+        }
+        self.terminal.reset();
+    }
+}
+
+/// For formats that only produce a final report.
+pub(crate) struct SilentReporter;
+
+impl ProgressReporter for SilentReporter {
+    fn report(&mut self, _commands: &[CommandDesc], _tick: usize) {}
+}
+
+/// A single status line, for when even a line per command is too much. It shows one running
+/// command at a time, rotating through them, and ends with a one-line summary.
+pub struct TickerReporter {
+    terminal: Terminal,
+    started_at: Option<SystemTime>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TickerReporter {
+    /// How many ticks to show each running command for.
+    const TICKS_PER_COMMAND: usize = 20;
+
+    pub fn new() -> Self {
+        Self {
+            terminal: Terminal::for_stdout(),
+            started_at: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Draws to the given terminal, rather than one for stdout.
+    pub fn with_terminal(mut self, terminal: Terminal) -> Self {
+        self.terminal = terminal;
+        self
+    }
+
+    fn line(&self, commands: &[CommandDesc], current: Option<String>) -> String {
+        let done = commands
+            .iter()
+            .filter(|c| c.status.is_terminal_state())
+            .count();
+        let failed = commands.iter().filter(|c| c.status.is_error()).count();
+        let mut line = format!("[{}/{}]", done, commands.len());
+        if let Some(progress) = overall_progress(commands) {
+            line.push_str(&format!(" {:.0}%", progress * 100.0));
+        }
+        if let Some(current) = current {
+            line.push(' ');
+            line.push_str(&current);
+        }
+        if failed > 0 {
+            line.push_str(&format!(" · {} failed", failed));
+        }
+        let elapsed = self
+            .started_at
+            .and_then(|s| self.clock.now().duration_since(s).ok());
+        if let Some(elapsed) = elapsed {
+            line.push_str(&format!(" · {}", format_duration(elapsed)));
+        }
+        return line;
+    }
+
+    fn truncate(&self, line: String) -> String {
+        // leave the last column free, so the cursor doesn't wrap onto the next line
+        match self.terminal.width() {
+            Some(width) if line.chars().count() >= width => {
+                let mut truncated: String = line.chars().take(width.saturating_sub(2)).collect();
+                truncated.push('…');
+                truncated
+            }
+            _ => line,
+        }
+    }
+}
+
+impl Default for TickerReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter for TickerReporter {
+    fn report(&mut self, commands: &[CommandDesc], tick: usize) {
+        if self.started_at.is_none() {
+            self.started_at = commands.iter().filter_map(|c| c.started_at()).min();
+        }
+        if self.terminal.is_append_only() {
+            return;
+        }
+        let running: Vec<&CommandDesc> = commands
+            .iter()
+            .filter(|c| c.status == CommandStatus::Running)
+            .collect();
+        let current = match running.len() {
+            0 => None,
+            n => {
+                let command = running[(tick / Self::TICKS_PER_COMMAND) % n];
+                Some(format!(
+                    "{} {}",
+                    command.icons.running(tick),
+                    command.summary_label()
+                ))
+            }
+        };
+        let line = self.truncate(self.line(commands, current));
+        print!("\r\x1b[2K{}", line);
+        _ = io::stdout().flush();
+    }
+
+    fn note(&mut self, message: &str) {
+        // the next tick redraws the status line below it
+        if !self.terminal.is_append_only() {
+            print!("\r\x1b[2K");
+        }
+        println!("{}", message);
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    fn finish(&mut self, commands: &[CommandDesc]) {
+        if !self.terminal.is_append_only() {
+            print!("\r\x1b[2K");
+        }
+        let line = self.truncate(self.line(commands, Some("done".to_string())));
+        println!("{}", line);
+    }
+}
+
+/// Appends a `.` per tick and never redraws anything, for screen readers, slow serial terminals
+/// and logs. Ends with a one-line count of how things went.
+pub struct DotsReporter {
+    dots: usize,
+}
+
+impl DotsReporter {
+    pub fn new() -> Self {
+        Self { dots: 0 }
+    }
+}
+
+impl Default for DotsReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter for DotsReporter {
+    fn report(&mut self, _commands: &[CommandDesc], _tick: usize) {
+        print!(".");
+        _ = io::stdout().flush();
+        self.dots += 1;
+    }
+
+    fn finish(&mut self, commands: &[CommandDesc]) {
+        if self.dots > 0 {
+            println!();
+        }
+        let count =
+            |f: fn(&CommandStatus) -> bool| commands.iter().filter(|c| f(&c.status)).count();
+        println!(
+            "{} passed, {} failed, {} skipped",
+            count(CommandStatus::is_success),
+            count(CommandStatus::is_error),
+            count(|s| matches!(s, CommandStatus::Skipped(_)))
+        );
+    }
+}
+
+/// Says what happened in plain English, one appended line per change (`build passed in 3
+/// seconds`), without colors, escape sequences, symbols or bells, for screen readers.
+pub struct AccessibleReporter {
+    /// Each command's status and number of retried attempts, as of the last announcement.
+    reported: Vec<(CommandStatus, usize)>,
+}
+
+impl AccessibleReporter {
+    pub fn new() -> Self {
+        Self {
+            reported: Vec::new(),
+        }
+    }
+
+    /// What a command's current status is, like `test failed with exit code 1 after 2 seconds`.
+    pub(crate) fn describe(command: &CommandDesc) -> Option<String> {
+        let label = Color::strip_escapes(&command.label());
+        let mut line = match &command.status {
+            CommandStatus::Unstarted => return None,
+            CommandStatus::Running => format!("{} started", label),
+            status => Self::outcome(&label, status, command.duration(), command),
+        };
+        if command.is_allowed_failure() {
+            line.push_str(", which is allowed");
+        }
+        return Some(line);
+    }
+
+    /// How the `index`th attempt at a command that's being retried ended.
+    fn describe_retry(command: &CommandDesc, index: usize) -> String {
+        let label = Color::strip_escapes(&command.label());
+        let attempt = &command.attempts()[index];
+        return format!(
+            "{}, and will be retried, attempt {} of {}",
+            Self::outcome(&label, &attempt.status, attempt.duration(), command),
+            index + 2,
+            command.retries + 1
+        );
+    }
+
+    /// How a run ended, with the status words carrying the meaning.
+    fn outcome(
+        label: &str,
+        status: &CommandStatus,
+        took: Option<Duration>,
+        command: &CommandDesc,
+    ) -> String {
+        let after = took
+            .map(|took| format!(" after {}", spoken_duration(took)))
+            .unwrap_or_default();
+        match status {
+            CommandStatus::Finished(0) => match took {
+                Some(took) => format!("{} passed in {}", label, spoken_duration(took)),
+                None => format!("{} passed", label),
+            },
+            CommandStatus::Finished(code) => {
+                format!("{} failed with exit code {}{}", label, code, after)
+            }
+            CommandStatus::Error(error) if error.starts_with("timed out after") => {
+                match command.timeout {
+                    Some(timeout) => {
+                        format!("{} timed out after {}", label, spoken_duration(timeout))
+                    }
+                    None => format!("{} timed out{}", label, after),
+                }
+            }
+            CommandStatus::Error(error) => {
+                format!("{} failed{}: {}", label, after, Color::strip_escapes(error))
+            }
+            CommandStatus::Skipped(reason) => format!("{} was skipped: {}", label, reason),
+            CommandStatus::Unstarted | CommandStatus::Running => label.to_string(),
+        }
+    }
+}
+
+impl Default for AccessibleReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressReporter for AccessibleReporter {
+    fn report(&mut self, commands: &[CommandDesc], _tick: usize) {
+        self.reported
+            .resize(commands.len(), (CommandStatus::Unstarted, 0));
+        for ((status, retried), command) in self.reported.iter_mut().zip(commands) {
+            let attempts = command.attempts().len();
+            if *status == command.status && *retried == attempts {
+                continue;
+            }
+            for index in *retried..attempts {
+                println!("{}", Self::describe_retry(command, index));
+            }
+            if let Some(line) = Self::describe(command) {
+                println!("{}", line);
+            }
+            *status = command.status.clone();
+            *retried = attempts;
+        }
+        _ = io::stdout().flush();
+    }
+
+    fn note(&mut self, message: &str) {
+        println!("{}", Color::strip_escapes(message));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::color::Color;
+use crate::commands::Commands;
+use crate::terminal::Terminal;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+enum Key {
+    Up,
+    Down,
+    Enter,
+    All,
+    Quit,
+    Other,
+}
+
+/// Puts the controlling terminal into a mode where we get each key as it's pressed, and restores
+/// it when dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Ok(Self {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+}
+
+fn read_key(tty: &mut File) -> io::Result<Key> {
+    let mut byte = [0u8; 1];
+    tty.read_exact(&mut byte)?;
+    let key = match byte[0] {
+        b'\r' | b'\n' => Key::Enter,
+        b'k' => Key::Up,
+        b'j' => Key::Down,
+        b'a' => Key::All,
+        b'q' | 3 | 4 => Key::Quit, // ctrl-c and ctrl-d, too
+        0x1b => {
+            let mut sequence = [0u8; 2];
+            tty.read_exact(&mut sequence)?;
+            match sequence {
+                [b'[', b'A'] => Key::Up,
+                [b'[', b'B'] => Key::Down,
+                _ => Key::Other,
+            }
+        }
+        _ => Key::Other,
+    };
+    return Ok(key);
+}
+
+/// After a run, lets the user pick failed commands to run again, with their output shown live.
+/// Returns once there are no failures left or the user quits.
+pub fn rerun_menu(commands: &mut Commands) -> io::Result<()> {
+    let mut tty = File::open("/dev/tty")?;
+    let mut selected = 0;
+    loop {
+        let failed: Vec<usize> = (0..commands.commands().len())
+            .filter(|&i| commands.commands()[i].status().is_error())
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        selected = selected.min(failed.len() - 1);
+
+        let mut out = Terminal::new();
+        _ = writeln!(
+            out,
+            "{}Rerun: ↑/↓ to select, enter to rerun, a for all failures, q to quit{}",
+            Color::Gray,
+            Color::Normal
+        );
+        for (n, &i) in failed.iter().enumerate() {
+            let marker = if n == selected { ">" } else { " " };
+            _ = write!(out, "{} ", marker);
+            commands.commands()[i].print_summary(0, commands.clock(), &mut out);
+            _ = writeln!(out);
+        }
+
+        let key = {
+            let _raw = RawMode::enable()?;
+            read_key(&mut tty)?
+        };
+        out.reset();
+        print!("\x1b[J"); // reset leaves the first line in place
+        let to_rerun = match key {
+            Key::Up => {
+                selected = selected.saturating_sub(1);
+                continue;
+            }
+            Key::Down => {
+                selected += 1;
+                continue;
+            }
+            Key::Enter => vec![failed[selected]],
+            Key::All => failed,
+            Key::Quit => return Ok(()),
+            Key::Other => continue,
+        };
+        for i in to_rerun {
+            println!(
+                "{}$ {}{}",
+                Color::Gray,
+                commands.commands()[i].label(),
+                Color::Normal
+            );
+            commands.rerun(i);
+            let mut summary = Terminal::new();
+            commands.commands()[i].print_summary(0, commands.clock(), &mut summary);
+            _ = write!(summary, "\n\n");
+        }
+    }
+}
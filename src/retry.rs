@@ -0,0 +1,70 @@
+use crate::durations::parse_duration;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How long to wait between a failed attempt and its retry.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Backoff {
+    #[default]
+    Immediate,
+    Fixed(Duration),
+    /// Doubles after each attempt, starting at `initial`, up to `max`.
+    Exponential {
+        initial: Duration,
+        max: Duration,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RetryBackoff {
+    pub backoff: Backoff,
+    /// Randomly scale each delay by up to ±50%, so retries from parallel commands spread out.
+    pub jitter: bool,
+}
+
+impl RetryBackoff {
+    /// The delay before retry number `retry` (starting at 1).
+    pub fn delay(&self, retry: usize) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Immediate => Duration::ZERO,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 2u32.saturating_pow(retry.saturating_sub(1) as u32);
+                initial.saturating_mul(factor).min(max)
+            }
+        };
+        if !self.jitter {
+            return delay;
+        }
+        let random = RandomState::new().build_hasher().finish();
+        let scale = 0.5 + (random as f64 / u64::MAX as f64);
+        return delay.mul_f64(scale);
+    }
+}
+
+impl FromStr for RetryBackoff {
+    type Err = String;
+
+    /// Parses `2s`, `exp:1s..30s`, optionally followed by `:jitter`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (spec, jitter) = match s.strip_suffix(":jitter") {
+            Some(spec) => (spec, true),
+            None => (s, false),
+        };
+        let backoff = match spec.strip_prefix("exp:") {
+            Some(range) => {
+                let (initial, max) = range
+                    .split_once("..")
+                    .ok_or_else(|| format!("expected exp:INITIAL..MAX, got {:?}", s))?;
+                Backoff::Exponential {
+                    initial: parse_duration(initial)?,
+                    max: parse_duration(max)?,
+                }
+            }
+            None => Backoff::Fixed(parse_duration(spec)?),
+        };
+        Ok(RetryBackoff { backoff, jitter })
+    }
+}
@@ -0,0 +1,81 @@
+use crate::command::{CommandOutput, CommandStatus};
+use crate::diff;
+use regex::{Regex, RegexBuilder};
+
+/// Post-conditions on a finished command's output, which override what its exit code says.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OutputRules {
+    pub(crate) success_when: Option<Regex>,
+    pub(crate) failure_when: Option<Regex>,
+    /// Fail if the command exits 0 without writing anything.
+    pub(crate) require_output: bool,
+    /// Fail if the command exits 0 without writing anything to stdout (stderr doesn't count).
+    pub(crate) fail_empty_output: bool,
+    /// What stdout must be, from `@expect-output` or `@expect-output-file`.
+    pub(crate) expected_stdout: Option<String>,
+}
+
+impl OutputRules {
+    /// Compiles a rule's pattern. `^` and `$` match at line boundaries.
+    pub(crate) fn compile(pattern: &str) -> Result<Regex, String> {
+        RegexBuilder::new(pattern)
+            .multi_line(true)
+            .build()
+            .map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))
+    }
+
+    /// The status the rules say the command should have instead, along with which rule fired. If
+    /// both rules match, failure wins.
+    pub(crate) fn apply(
+        &self,
+        status: &CommandStatus,
+        output: Option<&CommandOutput>,
+    ) -> Option<(CommandStatus, String)> {
+        let CommandStatus::Finished(code) = status else {
+            return None;
+        };
+        let empty = CommandOutput::default();
+        let output = output.unwrap_or(&empty);
+        if self.require_output && *code == 0 && output.is_empty() {
+            let note = "command succeeded but produced no output (required by @require-output)"
+                .to_string();
+            return Some((CommandStatus::Error(note.clone()), note));
+        }
+        if self.fail_empty_output && *code == 0 && output.stdout.is_empty() {
+            let note = "exit 0, but stdout was empty (fail-empty-output)".to_string();
+            return Some((CommandStatus::Error("empty output".to_string()), note));
+        }
+        if *code == 0 && self.expected_output_diff(output).is_some() {
+            let note = "exit 0, but stdout wasn't the expected output:".to_string();
+            return Some((CommandStatus::Error("unexpected output".to_string()), note));
+        }
+        if let Some(re) = Self::matching(&self.failure_when, output) {
+            if *code == 0 {
+                let note = format!("exit 0, but output matched failure-when /{}/", re);
+                return Some((CommandStatus::Error(note.clone()), note));
+            }
+        } else if let Some(re) = Self::matching(&self.success_when, output) {
+            if *code != 0 {
+                let note = format!("exit {}, but output matched success-when /{}/", code, re);
+                return Some((CommandStatus::Finished(0), note));
+            }
+        }
+        return None;
+    }
+
+    /// How stdout differs from the expected output, if it does. Trailing newlines don't count,
+    /// so that `@expect-output=hello` matches `echo hello`.
+    pub(crate) fn expected_output_diff(&self, output: &CommandOutput) -> Option<String> {
+        let expected = self.expected_stdout.as_deref()?.trim_end_matches('\n');
+        let actual = output.stdout.trim_end_matches('\n');
+        if expected == actual {
+            return None;
+        }
+        return Some(diff::render(expected, actual));
+    }
+
+    fn matching<'a>(rule: &'a Option<Regex>, output: &CommandOutput) -> Option<&'a Regex> {
+        rule.as_ref()
+            .filter(|re| re.is_match(&output.stdout) || re.is_match(&output.stderr))
+    }
+}
@@ -0,0 +1,52 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// Command lines that are almost certainly a mistake, and what to call each in a warning.
+    static ref BUILT_IN: Vec<(Regex, String)> = [
+        (
+            r"\brm\s+(?:-\S*\s+)*-\S*[rR]\S*\s+(?:-\S*\s+)*(?:/\*?|~/?|\$HOME/?)(?:[\s;&|]|$)",
+            "rm -rf /",
+        ),
+        (r"\bmkfs(?:\.\w+)?\b", "mkfs"),
+        (r"\bdd\b.*\bof=/dev/", "dd of=/dev/"),
+        (r">\s*/dev/(?:sd|hd|nvme|disk|mmcblk)", "> /dev/<disk>"),
+        (r":\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}", "fork bomb"),
+    ]
+    .into_iter()
+    .map(|(pattern, name)| (Regex::new(pattern).expect("built-in pattern"), name.to_string()))
+    .collect();
+}
+
+/// Flags commands that look destructive, so that they aren't run without the user confirming.
+#[derive(Clone, Debug)]
+pub struct SafetyCheck {
+    patterns: Vec<(Regex, String)>,
+}
+
+impl SafetyCheck {
+    /// Checks for `rm -rf /`, `mkfs`, `dd of=/dev/…` and the like.
+    pub fn built_in() -> Self {
+        Self {
+            patterns: BUILT_IN.clone(),
+        }
+    }
+
+    /// Checks for these regexes instead of the built-in ones.
+    pub fn with_patterns(patterns: &[String]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p).map(|r| (r, p.clone())))
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { patterns })
+    }
+
+    /// What the line looks like, if it looks destructive.
+    pub(crate) fn check(&self, line: &str) -> Option<&str> {
+        self.patterns
+            .iter()
+            .find(|(regex, _)| regex.is_match(line))
+            .map(|(_, name)| name.as_str())
+    }
+}
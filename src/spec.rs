@@ -0,0 +1,67 @@
+use crate::command::ShellMode;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// How a command will be run, once its line, annotations and defaults are resolved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandSpec {
+    pub args: Vec<String>,
+    pub shell: ShellMode,
+    /// The image it runs in, with `--docker`.
+    pub container: Option<String>,
+    pub cwd: Option<PathBuf>,
+    pub env: BTreeMap<String, String>,
+}
+
+/// A spec's settings by name (`cwd`, `shell`, `docker`, and each environment variable), for comparing
+/// commands field by field.
+pub type SpecFields = BTreeMap<String, String>;
+
+impl CommandSpec {
+    /// The spec's settings. Unless `full`, settings that are at their defaults are left out.
+    pub fn fields(&self, full: bool) -> SpecFields {
+        let mut fields = SpecFields::new();
+        if full || self.shell != ShellMode::Direct {
+            fields.insert(
+                "shell".to_string(),
+                format!("{:?}", self.shell).to_lowercase(),
+            );
+        }
+        if let Some(image) = &self.container {
+            fields.insert("docker".to_string(), image.clone());
+        }
+        match &self.cwd {
+            Some(cwd) => _ = fields.insert("cwd".to_string(), cwd.display().to_string()),
+            None if full => _ = fields.insert("cwd".to_string(), ".".to_string()),
+            None => {}
+        }
+        for (key, value) in &self.env {
+            fields.insert(format!("${}", key), value.clone());
+        }
+        return fields;
+    }
+}
+
+/// Splits commands' fields into the ones they all share, and each command's remaining ones.
+pub fn diff(specs: &[CommandSpec]) -> (SpecFields, Vec<SpecFields>) {
+    let all_fields: Vec<SpecFields> = specs.iter().map(|s| s.fields(false)).collect();
+    let mut common = all_fields.first().cloned().unwrap_or_default();
+    common.retain(|key, value| all_fields.iter().all(|f| f.get(key) == Some(value)));
+    let deltas = all_fields
+        .into_iter()
+        .map(|mut fields| {
+            fields.retain(|key, _| !common.contains_key(key));
+            fields
+        })
+        .collect();
+    return (common, deltas);
+}
+
+/// Formats fields as `key=value` pairs, e.g. `cwd=web $NODE_ENV=test`.
+pub(crate) fn format_fields(fields: &SpecFields) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,108 @@
+use crate::command::CommandDesc;
+use crate::durations::{format_bytes, format_duration};
+use std::fmt;
+use std::fmt::Formatter;
+use std::time::{Duration, SystemTime};
+
+/// How well a run used its parallelism, computed from each attempt's start and finish times.
+#[derive(Clone, Debug, Default)]
+pub struct RunStats {
+    /// The most commands that were running at the same time.
+    pub peak_concurrency: usize,
+    /// The sum of every attempt's duration.
+    pub command_time: Duration,
+    /// From the first start to the last finish.
+    pub wall_time: Duration,
+    /// The bytes of stdout and stderr captured from every attempt.
+    pub output_bytes: usize,
+}
+
+impl RunStats {
+    pub(crate) fn from_commands(commands: &[CommandDesc]) -> Self {
+        let mut intervals: Vec<(SystemTime, SystemTime)> = Vec::new();
+        for command in commands {
+            let earlier = command
+                .attempts()
+                .iter()
+                .map(|a| (a.started_at, a.finished_at));
+            for interval in earlier.chain([(command.started_at(), command.finished_at())]) {
+                if let (Some(started), Some(finished)) = interval {
+                    intervals.push((started, finished));
+                }
+            }
+        }
+        let output_bytes = commands
+            .iter()
+            .flat_map(|c| {
+                c.attempts()
+                    .iter()
+                    .map(|a| a.output.as_ref())
+                    .chain([c.output()])
+            })
+            .flatten()
+            .map(|output| output.byte_count())
+            .sum();
+        let (Some(first), Some(last)) = (
+            intervals.iter().map(|i| i.0).min(),
+            intervals.iter().map(|i| i.1).max(),
+        ) else {
+            return Self {
+                output_bytes,
+                ..Self::default()
+            };
+        };
+
+        // sorting by time, with finishes before starts, so that back-to-back attempts don't overlap
+        let mut events: Vec<(SystemTime, i32)> = Vec::with_capacity(intervals.len() * 2);
+        for (started, finished) in &intervals {
+            events.push((*started, 1));
+            events.push((*finished, -1));
+        }
+        events.sort();
+        let mut running = 0;
+        let mut peak = 0;
+        for (_, delta) in events {
+            running += delta;
+            peak = peak.max(running);
+        }
+        return Self {
+            peak_concurrency: peak as usize,
+            command_time: intervals
+                .iter()
+                .map(|(started, finished)| finished.duration_since(*started).unwrap_or_default())
+                .sum(),
+            wall_time: last.duration_since(first).unwrap_or_default(),
+            output_bytes,
+        };
+    }
+
+    /// On average, how many commands were running at once.
+    pub fn average_concurrency(&self) -> f64 {
+        if self.wall_time.is_zero() {
+            return 0.0;
+        }
+        self.command_time.as_secs_f64() / self.wall_time.as_secs_f64()
+    }
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "peak concurrency:    {}", self.peak_concurrency)?;
+        writeln!(f, "average concurrency: {:.2}", self.average_concurrency())?;
+        writeln!(
+            f,
+            "command time:        {}",
+            format_duration(self.command_time)
+        )?;
+        writeln!(
+            f,
+            "wall time:           {}",
+            format_duration(self.wall_time)
+        )?;
+        writeln!(
+            f,
+            "output:              {}",
+            format_bytes(self.output_bytes)
+        )
+    }
+}
@@ -0,0 +1,107 @@
+use crate::color::Color;
+use serde_json::Value;
+use std::fmt::Write;
+use strum::EnumString;
+
+/// What a command's output is, from `@output-format`, for how to show it in the details.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum ChildFormat {
+    /// JSON lines if the first non-empty line is a JSON object or array, and text otherwise.
+    #[default]
+    Auto,
+    Json,
+    Text,
+}
+
+/// The color of object keys in pretty-printed JSON.
+const KEY_COLOR: Color = Color::Other(36);
+
+/// Cargo's `--message-format=json` messages that say nothing worth reading in a failure's output.
+const CARGO_NOISE: &[&str] = &[
+    "compiler-artifact",
+    "build-script-executed",
+    "build-finished",
+];
+
+/// How to show JSON-lines output in the details: cargo's compiler messages as just the
+/// diagnostics they render, and any other JSON pretty-printed, with lines that aren't JSON left
+/// as they are. `None` if the output should be shown as it is.
+pub(crate) fn render(text: &str, format: ChildFormat) -> Option<String> {
+    match format {
+        ChildFormat::Text => return None,
+        ChildFormat::Json => {}
+        ChildFormat::Auto => {
+            let first = text.lines().find(|line| !line.trim().is_empty())?;
+            parse(first)?;
+        }
+    }
+    let mut rendered = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        match parse(line) {
+            Some(value) => render_message(&value, &mut rendered),
+            None => rendered.push_str(line),
+        }
+    }
+    return Some(rendered);
+}
+
+/// The line as a JSON object or array, if it is one.
+fn parse(line: &str) -> Option<Value> {
+    match serde_json::from_str(line.trim()) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => Some(value),
+        _ => None,
+    }
+}
+
+fn render_message(value: &Value, out: &mut String) {
+    if let Some(reason) = value.get("reason").and_then(Value::as_str) {
+        if CARGO_NOISE.contains(&reason) {
+            return;
+        }
+        let rendered = value.pointer("/message/rendered").and_then(Value::as_str);
+        if let ("compiler-message", Some(rendered)) = (reason, rendered) {
+            out.push_str(rendered);
+            if !rendered.ends_with('\n') {
+                out.push('\n');
+            }
+            return;
+        }
+    }
+    pretty_print(value, 0, out);
+    out.push('\n');
+}
+
+/// Like `serde_json::to_string_pretty`, but with the keys in color.
+fn pretty_print(value: &Value, indent: usize, out: &mut String) {
+    let pad = |depth: usize| "  ".repeat(depth);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (i, (key, value)) in map.iter().enumerate() {
+                let key = Value::String(key.clone());
+                _ = write!(
+                    out,
+                    "{}{}{}{}: ",
+                    pad(indent + 1),
+                    KEY_COLOR,
+                    key,
+                    Color::Normal
+                );
+                pretty_print(value, indent + 1, out);
+                out.push_str(if i + 1 < map.len() { ",\n" } else { "\n" });
+            }
+            _ = write!(out, "{}}}", pad(indent));
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&pad(indent + 1));
+                pretty_print(item, indent + 1, out);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            _ = write!(out, "{}]", pad(indent));
+        }
+        _ => _ = write!(out, "{}", value),
+    }
+}
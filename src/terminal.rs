@@ -0,0 +1,406 @@
+use crate::color::Color;
+#[cfg(unix)]
+use crate::cursor::CursorProbe;
+use std::fmt;
+use std::fmt::{Error, Write};
+use std::io::IsTerminal;
+
+/// The width to assume when stdout isn't a terminal.
+const DEFAULT_WIDTH: usize = 80;
+/// Terminals put a tab stop every 8 columns.
+const TAB_WIDTH: usize = 8;
+
+/// How far into an escape sequence we are. They can be split across writes (`write!` writes a
+/// [Color] in several pieces), so this carries over from one to the next.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+enum Escape {
+    #[default]
+    None,
+    Started,
+    /// `ESC [`, up to a final byte like `m`
+    Csi,
+    /// `ESC ]`, up to a BEL
+    Osc,
+}
+
+/// The column the cursor ends up at after printing `text` from column `start` (both counting
+/// from 0), as the dashboard counts it to tell how its lines wrap: escape sequences take no room,
+/// tabs go to the next tab stop, and `\r` goes back to the start.
+pub fn column_after(text: &str, start: usize) -> usize {
+    return advance_column(text, start, &mut Escape::None);
+}
+
+/// Like [column_after], but with an escape sequence that a previous write started carried over in
+/// `escape`, and left there if this one doesn't finish it either.
+fn advance_column(text: &str, start: usize, escape: &mut Escape) -> usize {
+    let mut column = start;
+    for c in text.chars() {
+        *escape = match (*escape, c) {
+            (Escape::None, '\x1b') => Escape::Started,
+            (Escape::None, '\t') => {
+                column = (column / TAB_WIDTH + 1) * TAB_WIDTH;
+                Escape::None
+            }
+            (Escape::None, '\r') => {
+                column = 0;
+                Escape::None
+            }
+            (Escape::None, c) => {
+                if !c.is_control() {
+                    column += 1;
+                }
+                Escape::None
+            }
+            (Escape::Started, '[') => Escape::Csi,
+            (Escape::Started, ']') => Escape::Osc,
+            (Escape::Started, _) => Escape::None,
+            (Escape::Csi, '@'..='~') => Escape::None,
+            (Escape::Osc, '\x07') => Escape::None,
+            (state, _) => state,
+        };
+    }
+    return column;
+}
+
+/// Where a [Terminal] gets its dimensions from.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TerminalSize {
+    /// Ask the terminal that stdout is attached to, if it is (and if it reports a nonzero size).
+    #[default]
+    Query,
+    /// Act as if there's no terminal, as when stdout is piped.
+    Unknown,
+    /// Act as if the terminal has these dimensions, whatever stdout is attached to.
+    Fixed { width: usize, height: usize },
+}
+
+impl TerminalSize {
+    /// The width and height, if there are any.
+    fn dimensions(self) -> Option<(usize, usize)> {
+        let (width, height) = match self {
+            TerminalSize::Query => terminal_size::terminal_size()
+                .map(|(width, height)| (width.0 as usize, height.0 as usize))?,
+            TerminalSize::Unknown => return None,
+            TerminalSize::Fixed { width, height } => (width, height),
+        };
+        // some pseudo-terminals report 0x0, which is as good as not knowing
+        return (width > 0 && height > 0).then_some((width, height));
+    }
+}
+
+pub struct Terminal {
+    next_write: usize,
+    written_lines_lengths: Vec<usize>,
+    title_saved: bool,
+    append_only: bool,
+    size: TerminalSize,
+    pending_line: String,
+    escape: Escape,
+    #[cfg(unix)]
+    cursor_probe: Option<CursorProbe>,
+    /// Where the cursor was at the end of the last frame, if known.
+    frame_end: Option<(u16, u16)>,
+    /// The width the last frame was drawn at, which determines how its lines wrapped.
+    frame_width: Option<usize>,
+    /// Whether the terminal has saved (with DECSC) where the last frame starts, for
+    /// [Terminal::reset] to go back to.
+    anchored: bool,
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Terminal {
+            next_write: 0,
+            written_lines_lengths: Vec::new(),
+            title_saved: false,
+            append_only: false,
+            size: TerminalSize::default(),
+            pending_line: String::new(),
+            escape: Escape::default(),
+            #[cfg(unix)]
+            cursor_probe: None,
+            frame_end: None,
+            frame_width: None,
+            anchored: false,
+        }
+    }
+
+    /// A terminal for stdout that's append-only if stdout can't handle cursor movement: either
+    /// it isn't a terminal, or `TERM=dumb`.
+    pub fn for_stdout() -> Self {
+        let dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+        #[allow(unused_mut)]
+        let mut terminal = Self::new().with_append_only(dumb || !std::io::stdout().is_terminal());
+        #[cfg(unix)]
+        if !terminal.append_only {
+            terminal.cursor_probe = CursorProbe::open();
+        }
+        return terminal;
+    }
+
+    /// In append-only mode, nothing is ever erased or redrawn, and escape sequences (including
+    /// colors) are stripped from everything written.
+    pub fn with_append_only(mut self, append_only: bool) -> Self {
+        self.append_only = append_only;
+        self
+    }
+
+    pub fn is_append_only(&self) -> bool {
+        self.append_only
+    }
+
+    /// Overrides where the dimensions come from, so that width-dependent output (wrapping,
+    /// truncation, columns, headers) can be pinned down regardless of what stdout is.
+    pub fn with_size(mut self, size: TerminalSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// The width of the terminal, if there is one. Everything that depends on the width goes
+    /// through this, and should have a sensible fallback for `None`.
+    pub fn width(&self) -> Option<usize> {
+        self.size.dimensions().map(|(width, _)| width)
+    }
+
+    /// The height of the terminal, if there is one.
+    pub fn height(&self) -> Option<usize> {
+        self.size.dimensions().map(|(_, height)| height)
+    }
+
+    /// Prints a blank line, and then a heading like `── title ─────`, filled out to the width of
+    /// the terminal.
+    pub fn print_section_header(&mut self, title: &str) {
+        let width = self.width().unwrap_or(DEFAULT_WIDTH);
+        let heading = format!("── {} ", title);
+        let fill = width.saturating_sub(heading.chars().count());
+        _ = write!(self, "\n{}{}\n", heading, "─".repeat(fill));
+    }
+
+    /// Prints a row of headers and then the rows, with each column padded to its widest cell and
+    /// two spaces between columns. Escape sequences (like colors) in cells don't count towards
+    /// their width.
+    pub fn print_table(&mut self, headers: &[&str], rows: &[Vec<String>]) {
+        let visible_width = |cell: &str| Color::strip_escapes(cell).chars().count();
+        let header_row: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+        let all_rows = || std::iter::once(&header_row).chain(rows);
+        let mut widths = Vec::new();
+        for row in all_rows() {
+            widths.resize(widths.len().max(row.len()), 0);
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(visible_width(cell));
+            }
+        }
+        for row in all_rows() {
+            let mut line = String::new();
+            for (i, cell) in row.iter().enumerate() {
+                if i + 1 < row.len() {
+                    let pad = widths[i] - visible_width(cell);
+                    _ = write!(line, "{}{}  ", cell, " ".repeat(pad));
+                } else {
+                    line.push_str(cell);
+                }
+            }
+            _ = writeln!(self, "{}", line.trim_end());
+        }
+    }
+
+    /// Sets the terminal window's title, saving the previous one the first time. `None` clears
+    /// the title and restores the saved one, on terminals that support xterm's title stack.
+    pub fn set_title(&mut self, title: Option<&str>) {
+        if self.append_only {
+            return;
+        }
+        match title {
+            Some(title) => {
+                if !self.title_saved {
+                    print!("\x1b[22;0t");
+                    self.title_saved = true;
+                }
+                print!(
+                    "\x1b]0;{}\x07",
+                    title.replace(|c: char| c.is_control(), " ")
+                );
+            }
+            None => {
+                print!("\x1b]0;\x07");
+                if self.title_saved {
+                    print!("\x1b[23;0t");
+                    self.title_saved = false;
+                }
+            }
+        }
+    }
+
+    /// Marks the end of a frame that the next [Terminal::reset] will erase. While the frame is
+    /// sure to be right above the cursor, this saves where it starts, so that the reset can go
+    /// straight back there. If the terminal can report its cursor position, that reset also checks
+    /// that nothing else has written to the terminal in the meantime (after every few frames, and
+    /// after the terminal is resized).
+    pub fn end_frame(&mut self) {
+        self.anchored = false;
+        if !self.append_only && self.written_lines_lengths.len() <= self.next_write {
+            // the frame ends with a newline, so the cursor is at the start of the row below it
+            let rows = self.rows_written();
+            if rows > 0 {
+                print!("\x1b[{}F\x1b7\x1b[{}E", rows, rows);
+                self.anchored = true;
+            }
+        }
+        self.frame_end = if self.probe_is_due() {
+            self.cursor_position()
+        } else {
+            None
+        };
+        self.frame_width = self.width();
+    }
+
+    /// How many rows of the terminal the lines written since the last reset take up, counting the
+    /// ones that wrapped.
+    fn rows_written(&self) -> usize {
+        let width = self.width();
+        return (self.written_lines_lengths.iter())
+            .map(|&len| match width {
+                Some(width) if width > 0 && len > width => len.div_ceil(width),
+                _ => 1,
+            })
+            .sum();
+    }
+
+    /// Prints a message above the current frame, where the next frame won't erase it. Anything
+    /// else that writes to stdout while a frame is up throws off [Terminal::reset], so messages
+    /// during a run should go through this.
+    pub fn note(&mut self, message: &str) {
+        self.reset();
+        if self.append_only {
+            _ = writeln!(self, "{}", message);
+            return;
+        }
+        // written directly, so that it isn't counted as part of the frame
+        println!("{}", message);
+    }
+
+    /// Whether to check where the cursor is at the end of this frame. It isn't every frame, since
+    /// asking the terminal takes a while.
+    fn probe_is_due(&mut self) -> bool {
+        #[cfg(unix)]
+        if let Some(probe) = &mut self.cursor_probe {
+            return probe.is_due(self.size.dimensions());
+        }
+        return false;
+    }
+
+    fn cursor_position(&mut self) -> Option<(u16, u16)> {
+        #[cfg(unix)]
+        if let Some(probe) = &mut self.cursor_probe {
+            return match probe.position() {
+                Ok(position) => position,
+                Err(_) => {
+                    // it doesn't answer, so don't keep waiting for it every frame
+                    self.cursor_probe = None;
+                    None
+                }
+            };
+        }
+        return None;
+    }
+
+    /// Erases what's been written since the last reset: by going back to where
+    /// [Terminal::end_frame] saved that the frame starts (with DECRC) and clearing from there
+    /// down, or if it didn't, by moving the cursor back up over it. If something else moved the
+    /// cursor since then, that would erase the wrong lines, so instead this leaves them alone and
+    /// starts afresh on a new line below.
+    pub fn reset(&mut self) {
+        if self.append_only {
+            return;
+        }
+        let anchored = std::mem::take(&mut self.anchored);
+        let frame_width = self.frame_width.take();
+        if !self.written_lines_lengths.is_empty()
+            && frame_width.is_some()
+            && frame_width != self.width()
+        {
+            // the terminal was resized, so the frame's lines have rewrapped in ways we can't
+            // count (and the saved start may have moved with them); leave it be, and start
+            // afresh below it rather than erase the wrong rows
+            let ends_line = self.written_lines_lengths.len() <= self.next_write;
+            if !ends_line {
+                println!();
+            }
+            self.written_lines_lengths.clear();
+            self.next_write = 0;
+            self.frame_end = None;
+            return;
+        }
+        if let Some(expected) = self.frame_end.take() {
+            if let Some(actual) = self.cursor_position().filter(|&actual| actual != expected) {
+                if actual.1 != 1 {
+                    println!();
+                }
+                self.written_lines_lengths.clear();
+                self.next_write = 0;
+                return;
+            }
+        }
+        if self.written_lines_lengths.is_empty() {
+            return;
+        }
+        if anchored {
+            print!("\x1b8\x1b[J");
+            self.written_lines_lengths.clear();
+            self.next_write = 0;
+            return;
+        }
+        // lines wider than the terminal wrap onto more than one row
+        let rows = self.rows_written();
+        for _ in 0..rows {
+            print!("\x1b[2K"); // erase the line
+            print!("\x1b[F");
+        }
+        print!("\x1b[2K"); // and the first line, which may be longer than the next frame's
+        self.written_lines_lengths.clear();
+        self.next_write = 0;
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if !self.pending_line.is_empty() {
+            print!("{}", Color::strip_escapes(&self.pending_line));
+        }
+    }
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Terminal {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.append_only {
+            // escape sequences can be split across writes, so strip them a line at a time
+            self.pending_line.push_str(s);
+            if let Some(end) = self.pending_line.rfind('\n') {
+                let complete: String = self.pending_line.drain(..=end).collect();
+                print!("{}", Color::strip_escapes(&complete));
+            }
+            return Ok(());
+        }
+        for line in s.split_inclusive("\n") {
+            while self.written_lines_lengths.len() < (self.next_write + 1) {
+                self.written_lines_lengths.push(0);
+            }
+            print!("{}", line);
+            let prev_len = self
+                .written_lines_lengths
+                .get_mut(self.next_write)
+                .ok_or(Error)?;
+            *prev_len = advance_column(line.trim_end_matches('\n'), *prev_len, &mut self.escape);
+            if line.ends_with("\n") {
+                self.next_write += 1;
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::OnceLock;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The colors to draw with instead of the terminal's own red, green, yellow and gray, as RGB,
+/// by what they mean.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    /// Commands that haven't started or were skipped, and other secondary text.
+    pub muted: (u8, u8, u8),
+    pub passed: (u8, u8, u8),
+    pub failed: (u8, u8, u8),
+    /// Allowed failures, retries and slow commands.
+    pub warning: (u8, u8, u8),
+}
+
+impl Theme {
+    /// Uses this theme for everything drawn from now on. Only the first theme installed takes
+    /// effect.
+    pub fn install(self) {
+        _ = THEME.set(self);
+    }
+
+    pub(crate) fn current() -> Option<&'static Theme> {
+        THEME.get()
+    }
+}
+
+/// The built-in themes, after popular terminal palettes.
+pub struct ColorScheme;
+
+impl ColorScheme {
+    /// The names `--theme` takes; `default` is the terminal's own colors.
+    pub const NAMES: &'static [&'static str] = &["default", "solarized", "nord", "dracula"];
+
+    /// The theme with this name, or `None` for `default` (or a name that isn't one).
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "solarized" => Some(Self::solarized()),
+            "nord" => Some(Self::nord()),
+            "dracula" => Some(Self::dracula()),
+            _ => None,
+        }
+    }
+
+    /// Solarized: base01, green, red and yellow.
+    pub fn solarized() -> Theme {
+        Theme {
+            muted: (0x58, 0x6e, 0x75),
+            passed: (0x85, 0x99, 0x00),
+            failed: (0xdc, 0x32, 0x2f),
+            warning: (0xb5, 0x89, 0x00),
+        }
+    }
+
+    /// Nord: the comment gray, and Aurora's green, red and yellow (nord14, nord11 and nord13).
+    pub fn nord() -> Theme {
+        Theme {
+            muted: (0x61, 0x6e, 0x88),
+            passed: (0xa3, 0xbe, 0x8c),
+            failed: (0xbf, 0x61, 0x6a),
+            warning: (0xeb, 0xcb, 0x8b),
+        }
+    }
+
+    /// Dracula: comment, green, red and yellow.
+    pub fn dracula() -> Theme {
+        Theme {
+            muted: (0x62, 0x72, 0xa4),
+            passed: (0x50, 0xfa, 0x7b),
+            failed: (0xff, 0x55, 0x55),
+            warning: (0xf1, 0xfa, 0x8c),
+        }
+    }
+}
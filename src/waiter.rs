@@ -0,0 +1,85 @@
+use crate::command::CommandStatus;
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Lets other threads block until a command finishes, while one thread drives the run. Get one
+/// from [crate::Commands::waiter]; clones share the same run.
+#[derive(Clone, Default)]
+pub struct Waiter {
+    shared: Arc<(Mutex<Board>, Condvar)>,
+}
+
+#[derive(Default)]
+struct Board {
+    /// Each added command's label, and its final status once it has one.
+    statuses: HashMap<String, Option<CommandStatus>>,
+    closed: bool,
+}
+
+impl Waiter {
+    /// Blocks until the command with the given label finishes (or is skipped), and returns its
+    /// final status.
+    pub fn wait_for(&self, label: &str) -> Result<CommandStatus, String> {
+        self.wait(label, None)
+    }
+
+    /// Like [Waiter::wait_for], but gives up after `timeout`.
+    pub fn wait_for_timeout(
+        &self,
+        label: &str,
+        timeout: Duration,
+    ) -> Result<CommandStatus, String> {
+        self.wait(label, Some(Instant::now() + timeout))
+    }
+
+    fn wait(&self, label: &str, deadline: Option<Instant>) -> Result<CommandStatus, String> {
+        let (lock, condvar) = &*self.shared;
+        let mut board = lock.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            match board.statuses.get(label) {
+                None => return Err(format!("no command {:?}", label)),
+                Some(Some(status)) => return Ok(status.clone()),
+                Some(None) if board.closed => {
+                    return Err(format!("the run ended before {:?} finished", label))
+                }
+                Some(None) => {}
+            }
+            board = match deadline {
+                None => condvar.wait(board).unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(format!("timed out waiting for {:?}", label));
+                    }
+                    condvar
+                        .wait_timeout(board, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+    }
+
+    fn board(&self) -> MutexGuard<'_, Board> {
+        self.shared.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn added(&self, label: String) {
+        self.board().statuses.entry(label).or_insert(None);
+    }
+
+    pub(crate) fn completed(&self, label: &str, status: &CommandStatus) {
+        let mut board = self.board();
+        if let Some(entry @ None) = board.statuses.get_mut(label) {
+            *entry = Some(status.clone());
+            self.shared.1.notify_all();
+        }
+    }
+
+    /// Wakes up anyone still waiting, since their commands will never finish.
+    pub(crate) fn close(&self) {
+        self.board().closed = true;
+        self.shared.1.notify_all();
+    }
+}
@@ -0,0 +1,62 @@
+/// Splits text into words the way a shell would: on whitespace, except inside `'single'` or
+/// `"double"` quotes (which are removed), and with `\` escaping the next character (anywhere
+/// but in single quotes, and in double quotes only before `"`, `\` or `$`). Nothing is
+/// expanded.
+pub fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unclosed ' in {:?}", text)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("unclosed \" in {:?}", text)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unclosed \" in {:?}", text)),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err(format!("trailing \\ in {:?}", text)),
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    return Ok(words);
+}
+
+/// Quotes a word so that a shell reads it back as just that word, as with [split_words]. Words
+/// that don't need quoting are left alone.
+pub fn quote_word(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    return format!("'{}'", word.replace('\'', r"'\''"));
+}
@@ -0,0 +1,104 @@
+use crate::color::{ESCAPES_REGEX, SGR_REGEX};
+
+/// Wraps a line of captured output to fit the given widths (the first row's, then every other
+/// row's), at whitespace where possible. Escape sequences don't count toward the width, and the
+/// active color is re-emitted at the start of each continuation row.
+pub fn wrap(line: &str, first_width: usize, rest_width: usize) -> Vec<String> {
+    let mut wrapper = Wrapper {
+        rows: Vec::new(),
+        row: String::new(),
+        row_width: 0,
+        limit: first_width.max(1),
+        rest_width: rest_width.max(1),
+        active_color: String::new(),
+        last_break: None,
+    };
+    let mut rest = line;
+    while !rest.is_empty() {
+        let (text, escape) = match ESCAPES_REGEX.find(rest) {
+            Some(m) => (&rest[..m.start()], Some(m.as_str())),
+            None => (rest, None),
+        };
+        text.chars().for_each(|c| wrapper.push_char(c));
+        rest = &rest[text.len()..];
+        if let Some(escape) = escape {
+            wrapper.push_escape(escape);
+            rest = &rest[escape.len()..];
+        }
+    }
+    wrapper.rows.push(wrapper.row);
+    return wrapper.rows;
+}
+
+struct Wrapper {
+    rows: Vec<String>,
+    row: String,
+    row_width: usize,
+    limit: usize,
+    rest_width: usize,
+    active_color: String,
+    /// The byte offset and width just past the row's last whitespace, and the color there.
+    last_break: Option<(usize, usize, String)>,
+}
+
+impl Wrapper {
+    fn push_escape(&mut self, escape: &str) {
+        self.row.push_str(escape);
+        if SGR_REGEX.is_match(escape) {
+            self.active_color = match escape {
+                "\x1b[0m" => String::new(),
+                color => color.to_string(),
+            };
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        if c.is_whitespace() {
+            if self.row_width >= self.limit {
+                // the row is full right at the end of a word, so it breaks here rather than at
+                // the whitespace before that word
+                self.last_break = None;
+                self.break_row();
+                return;
+            }
+            if self.continues_at_start() {
+                return;
+            }
+        }
+        if self.row_width + 1 > self.limit {
+            self.break_row();
+        }
+        self.row.push(c);
+        self.row_width += 1;
+        if c.is_whitespace() {
+            self.last_break = Some((self.row.len(), self.row_width, self.active_color.clone()));
+        }
+    }
+
+    /// Whether nothing has been written to a continuation row yet.
+    fn continues_at_start(&self) -> bool {
+        return !self.rows.is_empty() && self.row_width == 0;
+    }
+
+    fn break_row(&mut self) {
+        let carried = match self.last_break.take() {
+            Some((offset, width, color)) if offset < self.row.len() => {
+                let carried = format!("{}{}", color, &self.row[offset..]);
+                self.row.truncate(offset);
+                self.row_width -= width;
+                carried
+            }
+            _ => {
+                self.row_width = 0;
+                self.active_color.clone()
+            }
+        };
+        let finished = std::mem::replace(&mut self.row, carried);
+        let mut finished = finished.trim_end().to_string();
+        if finished.contains('\x1b') {
+            finished.push_str("\x1b[0m");
+        }
+        self.rows.push(finished);
+        self.limit = self.rest_width;
+    }
+}
@@ -0,0 +1,90 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::Commands;
+
+/// The label of each command that the line expands into.
+fn expand(line: &str) -> Result<Vec<String>, String> {
+    let mut commands = Commands::new();
+    commands.add_command(line.to_string())?;
+    return Ok(commands.commands().iter().map(|c| c.label()).collect());
+}
+
+#[test]
+fn each_alternative_becomes_a_command() {
+    assert_eq!(
+        expand("cargo test -p {foo,bar}").unwrap(),
+        ["cargo test -p foo", "cargo test -p bar"]
+    );
+}
+
+#[test]
+fn several_patterns_expand_to_every_combination() {
+    assert_eq!(
+        expand("echo {a,b}{1,2}").unwrap(),
+        ["echo a1", "echo a2", "echo b1", "echo b2"]
+    );
+}
+
+#[test]
+fn nested_patterns_expand_within_their_alternative() {
+    assert_eq!(
+        expand("echo {a,b{1,2},c}").unwrap(),
+        ["echo a", "echo b1", "echo b2", "echo c"]
+    );
+}
+
+#[test]
+fn empty_alternatives_are_kept() {
+    assert_eq!(expand("echo x{,y}").unwrap(), ["echo x", "echo xy"]);
+}
+
+#[test]
+fn braces_in_quotes_without_commas_or_after_a_dollar_are_left_alone() {
+    for line in [
+        "echo '{a,b}'",
+        "echo \"{a,b}\"",
+        "echo {a}",
+        "echo ${HOME,,}",
+        "echo {}",
+    ] {
+        assert_eq!(expand(line).unwrap(), [line], "{}", line);
+    }
+}
+
+#[test]
+fn a_comma_inside_quotes_in_a_pattern_doesnt_split_it() {
+    assert_eq!(expand("echo {'a,b',c}").unwrap(), ["echo 'a,b'", "echo c"]);
+}
+
+#[test]
+fn unclosed_braces_are_left_alone() {
+    assert_eq!(expand("echo {a,b").unwrap(), ["echo {a,b"]);
+}
+
+#[test]
+fn annotations_are_expanded_too() {
+    let mut commands = Commands::new();
+    commands
+        .add_command("@env=MODE={debug,release} true".to_string())
+        .unwrap();
+    let modes: Vec<_> = (commands.commands().iter())
+        .map(|c| c.spec().env["MODE"].clone())
+        .collect();
+    assert_eq!(modes, ["debug", "release"]);
+}
+
+#[test]
+fn expanding_to_at_most_a_thousand_commands_is_fine() {
+    // 10 × 10 × 10
+    let digits = "{0,1,2,3,4,5,6,7,8,9}";
+    let line = format!("echo {}{}{}", digits, digits, digits);
+    assert_eq!(expand(&line).unwrap().len(), 1000);
+}
+
+#[test]
+fn expanding_to_more_than_a_thousand_commands_is_an_error() {
+    let digits = "{0,1,2,3,4,5,6,7,8,9}";
+    let line = format!("echo {}{}{}{{a,b}}", digits, digits, digits);
+    let error = expand(&line).unwrap_err();
+    assert_eq!(error, "braces expand to more than 1000 commands");
+}
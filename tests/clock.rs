@@ -0,0 +1,23 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::{Clock, WaitReason};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+struct FixedClock(SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+#[test]
+fn delays_count_down_by_the_injected_clock() {
+    let start = UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let delay = WaitReason::Delay(start + Duration::from_secs(90));
+    assert_eq!(delay.describe(&FixedClock(start)), "starts in 1m30s");
+    let later = FixedClock(start + Duration::from_secs(85));
+    assert_eq!(delay.describe(&later), "starts in 5s");
+    let past = FixedClock(start + Duration::from_secs(100));
+    assert_eq!(delay.describe(&past), "starts in 0s");
+}
@@ -0,0 +1,50 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::column_after;
+
+#[test]
+fn each_printable_character_takes_a_column() {
+    assert_eq!(column_after("hello", 0), 5);
+    assert_eq!(column_after("hello", 3), 8);
+    assert_eq!(column_after("✓ ünï", 0), 5);
+    assert_eq!(column_after("", 7), 7);
+}
+
+#[test]
+fn tabs_go_to_the_next_tab_stop() {
+    assert_eq!(column_after("\t", 0), 8);
+    assert_eq!(column_after("ab\t", 0), 8);
+    assert_eq!(column_after("\t", 7), 8);
+    assert_eq!(column_after("\t", 8), 16);
+    assert_eq!(column_after("a\tb\tc", 0), 17);
+}
+
+#[test]
+fn carriage_returns_go_back_to_the_start() {
+    assert_eq!(column_after("progress 50%\rdone", 0), 4);
+    assert_eq!(column_after("\rx", 12), 1);
+}
+
+#[test]
+fn escape_sequences_take_no_room() {
+    assert_eq!(column_after("\x1b[31mred\x1b[0m", 0), 3);
+    assert_eq!(column_after("\x1b[1;38;5;208mbold\x1b[K", 0), 4);
+    // a hyperlink: OSC 8, ended by BEL
+    assert_eq!(
+        column_after("\x1b]8;;https://example.com\x07link\x1b]8;;\x07", 0),
+        4
+    );
+    // ESC followed by something other than `[` or `]` is a two-byte sequence, like DECSC
+    assert_eq!(column_after("\x1b7ab", 0), 2);
+}
+
+#[test]
+fn other_control_characters_take_no_room() {
+    assert_eq!(column_after("a\x07b", 0), 2);
+}
+
+#[test]
+fn an_unfinished_escape_sequence_swallows_the_rest() {
+    assert_eq!(column_after("ab\x1b[31", 0), 2);
+    assert_eq!(column_after("ab\x1b]0;title", 0), 2);
+}
@@ -0,0 +1,65 @@
+#![allow(clippy::needless_return, dead_code)]
+
+use multichecks::{CommandDesc, Commands, OutputFormat};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long a run in a test may take before it counts as hung.
+const RUN_LIMIT: Duration = Duration::from_secs(10);
+
+/// Commands that report nothing, so that tests don't draw a dashboard.
+pub fn quiet(commands: Vec<CommandDesc>) -> Commands {
+    let mut quiet = Commands::new().with_output_format(OutputFormat::Json);
+    for command in commands {
+        quiet.add_desc(command);
+    }
+    return quiet;
+}
+
+/// Runs the commands to the end, on another thread, and hands them back. Panics if that takes
+/// more than a few seconds, so that a run that would hang fails the test instead.
+pub fn run_to_end(mut commands: Commands) -> Commands {
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        commands.run();
+        _ = done.send(commands);
+    });
+    return finished
+        .recv_timeout(RUN_LIMIT)
+        .expect("the run should end rather than hang");
+}
+
+/// The command with this label.
+pub fn command<'a>(commands: &'a Commands, label: &str) -> &'a CommandDesc {
+    return (commands.commands().iter())
+        .find(|c| c.label() == label)
+        .unwrap_or_else(|| panic!("no command {:?}", label));
+}
+
+/// Runs the multichecks binary (without its state directory) on `stdin`, and returns its exit
+/// code and stdout.
+pub fn run_binary(args: &[&str], stdin: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_multichecks"))
+        .arg("--no-state")
+        .args(args)
+        .env_remove("MULTICHECKS_OPTS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("multichecks should start");
+    let mut input = child.stdin.take().expect("stdin is piped");
+    input
+        .write_all(stdin.as_bytes())
+        .expect("stdin should take the commands");
+    drop(input);
+    let output = child.wait_with_output().expect("multichecks should exit");
+    let code = output
+        .status
+        .code()
+        .expect("multichecks should exit normally");
+    return (code, String::from_utf8_lossy(&output.stdout).into_owned());
+}
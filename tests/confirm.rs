@@ -0,0 +1,61 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{command, run_to_end};
+use multichecks::{CommandStatus, Commands, OutputFormat, SafetyCheck};
+use std::path::PathBuf;
+
+/// A file for a command to create, which mustn't exist yet.
+fn marker(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("multichecks-{}-{}", name, std::process::id()));
+    _ = std::fs::remove_file(&path);
+    return path;
+}
+
+/// Commands that run `touch` on the marker, with every confirmation declined.
+fn declined(line: &str, safety_check: Option<SafetyCheck>) -> Commands {
+    let mut commands = Commands::new()
+        .with_output_format(OutputFormat::Json)
+        .with_safety_check(safety_check);
+    commands
+        .add_command(line.to_string())
+        .expect("the line should parse");
+    commands.confirm(|_| Some("not confirmed".to_string()));
+    return commands;
+}
+
+#[test]
+fn bench_doesnt_run_declined_commands() {
+    let marker = marker("bench");
+    let mut commands = declined(&format!("@confirm touch {}", marker.display()), None);
+    let report = commands.bench(2);
+    assert!(!marker.exists(), "the declined command ran");
+    assert_eq!(report.results[0].skipped.as_deref(), Some("not confirmed"));
+    assert!(report.results[0].durations.is_empty());
+    assert!(report.all_passed());
+}
+
+#[test]
+fn restart_all_doesnt_run_declined_commands() {
+    let marker = marker("restart");
+    let line = format!("@confirm touch {}", marker.display());
+    let mut commands = declined(&line, None);
+    commands.restart_all();
+    let commands = run_to_end(commands);
+    assert!(!marker.exists(), "the declined command ran");
+    assert_eq!(
+        command(&commands, &format!("touch {}", marker.display())).status(),
+        &CommandStatus::Skipped("not confirmed".to_string())
+    );
+}
+
+#[test]
+fn bench_doesnt_run_commands_the_safety_check_blocked() {
+    let marker = marker("safety");
+    let check = SafetyCheck::with_patterns(&["touch".to_string()]).expect("a valid pattern");
+    let mut commands = declined(&format!("touch {}", marker.display()), Some(check));
+    let report = commands.bench(1);
+    assert!(!marker.exists(), "the blocked command ran");
+    assert_eq!(report.results[0].skipped.as_deref(), Some("not confirmed"));
+}
@@ -0,0 +1,53 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{command, quiet, run_to_end};
+use multichecks::{CommandStatus, MockCommandDescBuilder};
+
+/// The error a command failed with.
+fn error(status: &CommandStatus) -> &str {
+    match status {
+        CommandStatus::Error(e) => e,
+        other => panic!("expected an error, not {:?}", other),
+    }
+}
+
+#[test]
+fn empty_line_fails_instead_of_waiting() {
+    let empty = MockCommandDescBuilder::new("").build();
+    let other = MockCommandDescBuilder::new("other").build();
+    let commands = run_to_end(quiet(vec![empty, other]));
+    assert!(commands.all_done());
+    assert_eq!(error(command(&commands, "").status()), "empty command line");
+    assert!(command(&commands, "other").status().is_success());
+}
+
+#[test]
+fn missing_dependency_fails_instead_of_waiting() {
+    let mut orphan = MockCommandDescBuilder::new("orphan").build();
+    orphan.depends_on(&["nonexistent"]);
+    let commands = run_to_end(quiet(vec![orphan]));
+    assert!(commands.all_done());
+    let orphan = command(&commands, "orphan");
+    // caught before the deadlock detector, as a dependency that can never pass
+    match orphan.status() {
+        CommandStatus::Skipped(reason) => assert!(reason.contains("nonexistent"), "{}", reason),
+        other => panic!("expected it to be skipped, not {:?}", other),
+    }
+    assert_eq!(orphan.started_at(), None);
+}
+
+#[test]
+fn dependency_cycle_fails_instead_of_hanging() {
+    let mut chicken = MockCommandDescBuilder::new("chicken").build();
+    chicken.depends_on(&["egg"]);
+    let mut egg = MockCommandDescBuilder::new("egg").build();
+    egg.depends_on(&["chicken"]);
+    let bystander = MockCommandDescBuilder::new("bystander").build();
+    let commands = run_to_end(quiet(vec![chicken, egg, bystander]));
+    assert!(commands.all_done());
+    assert!(error(command(&commands, "chicken").status()).contains("cycle"));
+    assert!(error(command(&commands, "egg").status()).contains("cycle"));
+    assert!(command(&commands, "bystander").status().is_success());
+}
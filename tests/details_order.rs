@@ -0,0 +1,44 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+
+/// The quoted output lines of the details, without the quote bar, skipping blank ones.
+fn details_lines(stdout: &str) -> Vec<&str> {
+    return (stdout.lines())
+        .filter_map(|line| line.strip_prefix("│ "))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+}
+
+#[test]
+fn commands_are_in_input_order() {
+    // the first command finishes last
+    let input = "sleep 0.3; echo first; false\necho second; false\necho third; false\n";
+    let (code, stdout) = run_binary(&["--shell"], input);
+    assert_eq!(code, 1);
+    assert_eq!(details_lines(&stdout), ["first", "second", "third"]);
+}
+
+#[test]
+fn stdout_comes_before_stderr() {
+    let input = "echo err1 >&2; echo out1; sleep 0.1; echo err2 >&2; echo out2; false\n";
+    let (_, stdout) = run_binary(&["--shell"], input);
+    assert_eq!(details_lines(&stdout), ["out1", "out2", "err1", "err2"]);
+}
+
+#[test]
+fn merge_streams_interleaves_by_when_lines_were_written() {
+    let input = "echo a >&2; sleep 0.1; echo b; sleep 0.1; echo c >&2; sleep 0.1; echo d; false\n";
+    let (_, stdout) = run_binary(&["--shell", "--merge-streams"], input);
+    assert_eq!(details_lines(&stdout), ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn same_output_every_time() {
+    let input = "echo err >&2; echo out; false\nsleep 0.2; echo late; false\n";
+    let (_, first) = run_binary(&["--shell", "--format", "plain", "--no-durations"], input);
+    let (_, second) = run_binary(&["--shell", "--format", "plain", "--no-durations"], input);
+    assert_eq!(first, second);
+}
@@ -0,0 +1,33 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::{parse_bytes, parse_duration};
+use std::time::Duration;
+
+#[test]
+fn durations() {
+    assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+    assert_eq!(parse_duration("3m"), Ok(Duration::from_secs(180)));
+    assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+}
+
+#[test]
+fn durations_too_long_for_a_duration_are_errors() {
+    let huge = format!("{}h", "9".repeat(400));
+    assert!(parse_duration(&huge).is_err());
+    assert!(parse_duration("99999999999999999999h").is_err());
+}
+
+#[test]
+fn sizes() {
+    assert_eq!(parse_bytes("500"), Ok(500));
+    assert_eq!(parse_bytes("64K"), Ok(64 * 1024));
+    assert_eq!(parse_bytes("1.5M"), Ok(1536 * 1024));
+}
+
+#[test]
+fn sizes_too_big_for_usize_are_errors() {
+    assert!(parse_bytes("99999999999999999999G").is_err());
+    assert!(parse_bytes(&"9".repeat(400)).is_err());
+    assert!(parse_bytes("17179869184G").is_err());
+}
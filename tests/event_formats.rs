@@ -0,0 +1,27 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+
+/// A command that fails as it starts, so that it never shows up as running in any tick.
+const FAILS_TO_START: &str = "no-such-command-multichecks-test\n";
+
+#[test]
+fn log_starts_commands_that_finish_within_a_tick() {
+    let (_, stdout) = run_binary(&["--format", "log"], FAILS_TO_START);
+    let events: Vec<&str> = (stdout.lines())
+        .filter_map(|line| line.split(' ').nth(1))
+        .collect();
+    assert_eq!(events, ["START", "FAIL", "DONE"], "{}", stdout);
+}
+
+#[test]
+fn ndjson_starts_commands_that_finish_within_a_tick() {
+    let (_, stdout) = run_binary(&["--format", "ndjson"], FAILS_TO_START);
+    let events: Vec<String> = (stdout.lines())
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("each line is JSON"))
+        .filter_map(|event| event["event"].as_str().map(str::to_string))
+        .collect();
+    assert_eq!(events, ["started", "finished", "summary"], "{}", stdout);
+}
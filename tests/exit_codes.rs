@@ -0,0 +1,103 @@
+#![allow(clippy::needless_return)]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs multichecks with `args`, feeding it `stdin`, and returns its exit code.
+fn exit_code(args: &[&str], stdin: &str) -> i32 {
+    return exit_code_with_env(args, &[], stdin);
+}
+
+/// Like [exit_code], with these environment variables set as well.
+fn exit_code_with_env(args: &[&str], env: &[(&str, &str)], stdin: &str) -> i32 {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_multichecks"))
+        .arg("--no-state")
+        .args(args)
+        .env_remove("MULTICHECKS_OPTS")
+        .env_remove("MULTICHECKS_DEFAULT_TIMEOUT_SECS")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("multichecks should start");
+    let mut input = child.stdin.take().expect("stdin is piped");
+    input
+        .write_all(stdin.as_bytes())
+        .expect("stdin should take the commands");
+    drop(input);
+    let status = child.wait().expect("multichecks should exit");
+    return status.code().expect("multichecks should exit normally");
+}
+
+#[test]
+fn passed() {
+    assert_eq!(exit_code(&[], "true\ntrue\n"), 0);
+}
+
+#[test]
+fn allowed_failure_passes() {
+    assert_eq!(exit_code(&[], "@allow-failure false\n"), 0);
+}
+
+#[test]
+fn failed() {
+    assert_eq!(exit_code(&[], "true\nfalse\n"), 1);
+}
+
+#[test]
+fn usage_error_for_bad_option() {
+    assert_eq!(exit_code(&["--no-such-option"], "true\n"), 2);
+}
+
+#[test]
+fn usage_error_for_bad_default_timeout() {
+    for value in ["5m", "abc", "-1", "1.5"] {
+        let env = [("MULTICHECKS_DEFAULT_TIMEOUT_SECS", value)];
+        assert_eq!(exit_code_with_env(&[], &env, "true\n"), 2, "{}", value);
+    }
+    let env = [("MULTICHECKS_DEFAULT_TIMEOUT_SECS", " 30 ")];
+    assert_eq!(exit_code_with_env(&[], &env, "true\n"), 0);
+}
+
+#[test]
+fn usage_error_for_bad_annotation() {
+    assert_eq!(exit_code(&[], "@no-such-annotation true\n"), 2);
+}
+
+#[test]
+fn usage_error_for_allow_failure_with_expect_fail() {
+    // there's no @expect-fail, so it's rejected like any other unknown annotation
+    assert_eq!(exit_code(&[], "@allow-failure @expect-fail false\n"), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn internal_error_when_attach_finds_nothing() {
+    let socket = std::env::temp_dir().join("multichecks-exit-codes-test-nothing-here.sock");
+    let socket = socket.to_str().expect("temp dir is UTF-8");
+    let args = ["--control-socket", socket, "--attach", "add", "true"];
+    assert_eq!(exit_code(&args, ""), 3);
+}
+
+#[test]
+fn no_commands() {
+    assert_eq!(exit_code(&[], ""), 4);
+    assert_eq!(exit_code(&[], "# just a comment\n"), 4);
+}
+
+#[test]
+fn passthrough_uses_first_failure_code() {
+    let args = ["--exit-code", "passthrough", "--shell", "-j1"];
+    assert_eq!(exit_code(&args, "exit 7\nexit 9\n"), 7);
+}
+
+#[test]
+fn passthrough_still_passes() {
+    assert_eq!(exit_code(&["--exit-code", "passthrough"], "true\n"), 0);
+}
+
+#[test]
+fn standard_ignores_command_code() {
+    assert_eq!(exit_code(&["--shell"], "exit 7\n"), 1);
+}
@@ -0,0 +1,73 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+use std::fs;
+use std::path::PathBuf;
+
+/// A golden file with these lines, unique to the test.
+fn golden(name: &str, lines: &[&str]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "multichecks-expect-output-{}-{}.txt",
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, lines.join("\n") + "\n").expect("the golden file should be written");
+    return path;
+}
+
+/// The diff in the details of a run of `input`, which should fail.
+fn diff_of(input: &str) -> Vec<String> {
+    let (code, stdout) = run_binary(&["--shell"], input);
+    assert_eq!(code, 1, "{}", stdout);
+    let (_, after) = stdout
+        .split_once("wasn't the expected output:\n")
+        .unwrap_or_else(|| panic!("no diff in {}", stdout));
+    return (after.lines())
+        .take_while(|line| !line.is_empty() && !line.starts_with('│'))
+        .map(str::to_string)
+        .collect();
+}
+
+#[test]
+fn lines_are_matched_up_by_the_longest_common_subsequence() {
+    let path = golden("lcs", &["a", "b", "c"]);
+    let input = format!(
+        "@expect-output-file={} printf 'a\\nc\\nd\\n'\n",
+        path.display()
+    );
+    assert_eq!(diff_of(&input), ["  a", "- b", "  c", "+ d"]);
+    _ = fs::remove_file(path);
+}
+
+#[test]
+fn unchanged_lines_far_from_a_change_are_elided() {
+    let expected: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+    let expected: Vec<&str> = expected.iter().map(String::as_str).collect();
+    let path = golden("context", &expected);
+    let input = format!(
+        "@expect-output-file={} seq 20 | sed s/^10$/ten/\n",
+        path.display()
+    );
+    assert_eq!(
+        diff_of(&input),
+        ["  …", "  8", "  9", "- 10", "+ ten", "  11", "  12", "  …"]
+    );
+    _ = fs::remove_file(path);
+}
+
+#[test]
+fn trailing_newlines_dont_count() {
+    let input = "@expect-output=hi printf 'hi\\n\\n\\n'\n@expect-output=hi printf hi\n";
+    let (code, stdout) = run_binary(&["--shell"], input);
+    assert_eq!(code, 0, "{}", stdout);
+}
+
+#[test]
+fn trailing_spaces_do_count() {
+    assert_eq!(
+        diff_of("@expect-output=hi printf 'hi '\n"),
+        ["- hi", "+ hi "]
+    );
+}
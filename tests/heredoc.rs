@@ -0,0 +1,129 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+use multichecks::Commands;
+
+/// The script of each command that the lines add.
+fn scripts(lines: &[&str]) -> Result<Vec<String>, String> {
+    let mut commands = Commands::new();
+    for line in lines {
+        commands.add_command(line.to_string())?;
+    }
+    commands.finish_input()?;
+    return Ok(commands
+        .commands()
+        .iter()
+        .map(|c| c.script().unwrap_or("(not a script)").to_string())
+        .collect());
+}
+
+#[test]
+fn spaces_after_the_marker_are_allowed() {
+    let scripts = scripts(&["<< EOF", "echo a", "echo b", "EOF"]).unwrap();
+    assert_eq!(scripts, ["echo a\necho b"]);
+}
+
+#[test]
+fn quoted_terminators_are_unquoted_and_kept_quoted_for_the_shell() {
+    let scripts = scripts(&["cat <<'EOF'", "$HOME", "EOF", "<<\"END\"", "true", "END"]).unwrap();
+    assert_eq!(scripts, ["cat <<'EOF'\n$HOME\nEOF", "true"]);
+}
+
+#[test]
+fn dash_strips_leading_tabs_from_the_body_and_terminator() {
+    let scripts = scripts(&["<<-EOF", "\techo a", "\t\techo b", "\tEOF"]).unwrap();
+    assert_eq!(scripts, ["echo a\necho b"]);
+}
+
+#[test]
+fn partly_quoted_terminators_are_rejected() {
+    for header in ["<<E\"OF\"", "cat <<'E'OF"] {
+        let error = scripts(&[header, "true", "EOF"]).unwrap_err();
+        assert!(error.contains("can't tell where the heredoc"), "{}", error);
+    }
+}
+
+#[test]
+fn here_strings_and_quoted_arrows_are_not_heredocs() {
+    let scripts = scripts(&["cat <<< hi", "echo '<<'"]).unwrap();
+    assert_eq!(scripts, ["(not a script)", "(not a script)"]);
+}
+
+#[test]
+fn a_quoted_heredoc_runs_as_one_command() {
+    let (code, stdout) = run_binary(&["--format", "json"], "cat << 'EOF'\necho not run\nEOF\n");
+    assert_eq!(code, 0, "{}", stdout);
+    assert!(
+        stdout.contains(r#""stdout": "echo not run\n""#),
+        "{}",
+        stdout
+    );
+    assert_eq!(stdout.matches("\"command\":").count(), 1, "{}", stdout);
+}
+
+#[test]
+fn a_bare_heredoc_runs_its_body_and_is_labeled_by_its_first_line() {
+    let mut commands = Commands::new();
+    for line in [
+        "@retries=1 <<EOF",
+        "",
+        "  curl localhost",
+        "  test -f x",
+        "EOF",
+    ] {
+        commands.add_command(line.to_string()).unwrap();
+    }
+    commands.finish_input().unwrap();
+    let [command] = commands.commands() else {
+        panic!("expected one command");
+    };
+    assert_eq!(command.label(), "curl localhost …");
+    assert_eq!(command.script(), Some("\n  curl localhost\n  test -f x"));
+}
+
+#[test]
+fn a_prefixed_heredoc_is_handed_to_the_shell_for_that_command() {
+    let mut commands = Commands::new();
+    for line in ["python3 <<EOF", "print(1)", "EOF"] {
+        commands.add_command(line.to_string()).unwrap();
+    }
+    commands.finish_input().unwrap();
+    let [command] = commands.commands() else {
+        panic!("expected one command");
+    };
+    assert_eq!(command.label(), "python3 <<EOF …");
+    assert_eq!(command.script(), Some("python3 <<EOF\nprint(1)\nEOF"));
+}
+
+#[test]
+fn a_prefixed_heredoc_feeds_its_body_to_the_command() {
+    let (code, stdout) = run_binary(&["--format", "json"], "tr a-z A-Z <<EOF\nshout\nEOF\n");
+    assert_eq!(code, 0, "{}", stdout);
+    assert!(stdout.contains(r#""stdout": "SHOUT\n""#), "{}", stdout);
+}
+
+#[test]
+fn nested_heredocs_are_an_error() {
+    let error = scripts(&["<<EOF", "cat <<END", "x", "END", "EOF"]).unwrap_err();
+    assert_eq!(
+        error,
+        "heredocs can't be nested (still inside the <<EOF from line 1)"
+    );
+}
+
+#[test]
+fn an_unterminated_heredoc_is_an_error() {
+    let error = scripts(&["true", "<<EOF", "echo a"]).unwrap_err();
+    assert_eq!(
+        error,
+        "the <<EOF from line 2 is never closed by a line with just EOF"
+    );
+}
+
+#[test]
+fn a_here_string_inside_a_heredoc_is_not_nesting() {
+    let scripts = scripts(&["<<EOF", "cat <<< hi", "EOF"]).unwrap();
+    assert_eq!(scripts, ["cat <<< hi"]);
+}
@@ -0,0 +1,75 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::{CommandDesc, Commands};
+
+fn label_errors(lines: &[&str]) -> Vec<String> {
+    let mut commands = Commands::new();
+    commands.set_input_name("stdin");
+    for line in lines {
+        commands
+            .add_command(line.to_string())
+            .expect("the line should parse");
+    }
+    return commands.label_errors();
+}
+
+#[test]
+fn every_referenced_duplicate_label_is_reported_with_where_it_was_defined() {
+    let errors = label_errors(&[
+        "true",
+        "echo hi",
+        "true",
+        "echo hi",
+        "true",
+        "@needs=true @abort-if='echo hi' sleep 1",
+    ]);
+    assert_eq!(
+        errors,
+        [
+            "3 commands have the label 'true' (stdin, line 1 and stdin, line 3 and stdin, line 5), \
+             but each one needs its own",
+            "2 commands have the label 'echo hi' (stdin, line 2 and stdin, line 4), but each one \
+             needs its own",
+        ]
+    );
+}
+
+#[test]
+fn unnamed_unreferenced_duplicates_are_fine() {
+    assert!(label_errors(&["echo a", "echo a", "true"]).is_empty());
+}
+
+#[test]
+fn explicitly_named_duplicates_are_reported() {
+    let mut commands = Commands::new();
+    commands.add_desc(CommandDesc::new(vec!["true".to_string()]).with_label("check"));
+    commands.add_desc(CommandDesc::new(vec!["false".to_string()]).with_label("check"));
+    let errors = commands.label_errors();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0].starts_with("2 commands have the label 'check'"),
+        "{}",
+        errors[0]
+    );
+}
+
+#[test]
+fn missing_labels_are_reported_with_suggestions() {
+    let errors = label_errors(&["cargo build", "@needs='cargo biuld' cargo test"]);
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert!(
+        errors[0].contains("no command has that label"),
+        "{}",
+        errors[0]
+    );
+    assert!(
+        errors[0].contains("did you mean 'cargo build'?"),
+        "{}",
+        errors[0]
+    );
+}
+
+#[test]
+fn distinct_labels_are_fine() {
+    assert!(label_errors(&["true", "echo hi"]).is_empty());
+}
@@ -0,0 +1,17 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+
+#[test]
+fn dropping_output_gives_back_the_bytes_it_was_charged() {
+    // 12000 bytes of UTF-16 decode to 6000 of text; dropping it once the first command passes
+    // must free all 12000, or the second command's 12000 won't fit in 16K
+    let input = "@encoding=utf-16le printf 'a\\000%.0s' $(seq 6000)\n\
+                 sleep 0.5; head -c 12000 /dev/zero | tr '\\0' b; echo; false\n";
+    let (code, stdout) = run_binary(&["--shell", "-j1", "--total-output-budget", "16K"], input);
+    assert_eq!(code, 1);
+    assert!(stdout.contains(&"b".repeat(12000)), "{}", stdout);
+    assert!(!stdout.contains("KB of output dropped"), "{}", stdout);
+}
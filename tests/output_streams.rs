@@ -0,0 +1,29 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{command, run_to_end};
+use multichecks::{CommandStatus, Commands, OutputFormat, ShellMode};
+use std::time::Duration;
+
+#[test]
+fn a_stream_held_open_by_a_background_process_doesnt_hold_up_the_others() {
+    let mut commands = Commands::new()
+        .with_output_format(OutputFormat::Json)
+        .with_shell(ShellMode::Shell);
+    for line in ["sleep 3 & echo started", "sleep 0.3"] {
+        commands.add_command(line.to_string()).unwrap();
+    }
+    let commands = run_to_end(commands);
+    let background = command(&commands, "sleep 3 & echo started");
+    assert_eq!(background.status(), &CommandStatus::Finished(0));
+    assert_eq!(background.output().unwrap().stdout, "started\n");
+    // it's noticed as soon as it exits, rather than once the other one has given up waiting for
+    // its stream to close
+    let quick = command(&commands, "sleep 0.3");
+    assert!(
+        quick.duration().unwrap() < Duration::from_millis(800),
+        "{:?}",
+        quick.duration()
+    );
+}
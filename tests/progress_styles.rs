@@ -0,0 +1,16 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::run_binary;
+
+#[test]
+fn dots_count_passes_failures_and_skips_separately() {
+    let input = "true\nfalse\n@needs=false echo never\n";
+    let (code, stdout) = run_binary(&["--progress-style", "dots"], input);
+    assert_eq!(code, 1);
+    let count = (stdout.lines())
+        .find(|line| line.ends_with(" skipped"))
+        .unwrap_or_else(|| panic!("no count in {:?}", stdout));
+    assert_eq!(count, "1 passed, 1 failed, 1 skipped");
+}
@@ -0,0 +1,154 @@
+#![allow(clippy::needless_return)]
+
+mod common;
+
+use common::{command, quiet, run_to_end};
+use multichecks::{CommandStatus, MockCommandDescBuilder, WaitReason};
+use std::net::TcpListener;
+use std::time::Duration;
+
+fn running_then(status: CommandStatus, checks: usize) -> Vec<CommandStatus> {
+    let mut statuses = vec![CommandStatus::Running; checks];
+    statuses.push(status);
+    return statuses;
+}
+
+#[test]
+fn follows_the_mocked_statuses() {
+    let slow = MockCommandDescBuilder::new("slow")
+        .with_statuses(running_then(CommandStatus::Finished(0), 2))
+        .build();
+    let mut commands = quiet(vec![slow]);
+    commands.summarize_all();
+    assert_eq!(*command(&commands, "slow").status(), CommandStatus::Running);
+    assert!(!commands.all_done());
+    commands.summarize_all();
+    commands.summarize_all();
+    assert_eq!(
+        *command(&commands, "slow").status(),
+        CommandStatus::Finished(0)
+    );
+    assert!(commands.all_done());
+    assert!(commands.all_succeeded());
+}
+
+#[test]
+fn failures_and_errors_fail_the_run() {
+    let failed = MockCommandDescBuilder::new("failed")
+        .with_statuses([CommandStatus::Finished(3)])
+        .build();
+    let errored = MockCommandDescBuilder::new("errored")
+        .with_statuses([CommandStatus::Error("lost it".to_string())])
+        .build();
+    let passed = MockCommandDescBuilder::new("passed").build();
+    let commands = run_to_end(quiet(vec![failed, errored, passed]));
+    assert_eq!(
+        *command(&commands, "failed").status(),
+        CommandStatus::Finished(3)
+    );
+    assert!(command(&commands, "errored").status().is_error());
+    assert!(command(&commands, "passed").status().is_success());
+    let result = commands.run_result();
+    assert!(!result.succeeded);
+    assert_eq!(result.failed, vec!["failed", "errored"]);
+}
+
+#[test]
+fn keeps_the_mocked_output() {
+    let noisy = MockCommandDescBuilder::new("noisy")
+        .with_stdout("out\n")
+        .with_stderr("err\n")
+        .build();
+    let commands = run_to_end(quiet(vec![noisy]));
+    let output = command(&commands, "noisy").output().expect("it ran");
+    assert_eq!(output.stdout, "out\n");
+    assert_eq!(output.stderr, "err\n");
+}
+
+#[test]
+fn runs_until_the_start_latency_is_up() {
+    let latent = MockCommandDescBuilder::new("latent")
+        .with_start_latency(Duration::from_secs(5))
+        .build();
+    let mut commands = quiet(vec![latent]);
+    commands.summarize_all();
+    commands.summarize_all();
+    assert_eq!(
+        *command(&commands, "latent").status(),
+        CommandStatus::Running
+    );
+}
+
+#[test]
+fn waits_for_dependencies_to_pass() {
+    let build = MockCommandDescBuilder::new("build")
+        .with_statuses(running_then(CommandStatus::Finished(0), 2))
+        .build();
+    let mut test = MockCommandDescBuilder::new("test").build();
+    test.depends_on(&["build"]);
+    let mut commands = quiet(vec![build, test]);
+    commands.summarize_all();
+    let waiting = command(&commands, "test");
+    assert_eq!(*waiting.status(), CommandStatus::Unstarted);
+    assert_eq!(
+        waiting.wait_reason(),
+        Some(&WaitReason::Dependencies(vec!["build".to_string()]))
+    );
+
+    let commands = run_to_end(commands);
+    assert!(commands.all_succeeded());
+    let (build, test) = (command(&commands, "build"), command(&commands, "test"));
+    assert!(test.started_at() >= build.finished_at());
+}
+
+#[test]
+fn skips_commands_whose_dependencies_failed() {
+    let build = MockCommandDescBuilder::new("build")
+        .with_statuses([CommandStatus::Finished(1)])
+        .build();
+    let mut test = MockCommandDescBuilder::new("test").build();
+    test.depends_on(&["build"]);
+    let commands = run_to_end(quiet(vec![build, test]));
+    assert!(matches!(
+        command(&commands, "test").status(),
+        CommandStatus::Skipped(_)
+    ));
+    assert_eq!(command(&commands, "test").started_at(), None);
+}
+
+#[test]
+fn waits_until_ready() {
+    // a port that nothing is listening on, at least until the listener below
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("a free port")
+        .to_string();
+    let client = MockCommandDescBuilder::new("client")
+        .with_wait_for(&[&address])
+        .build();
+    let mut commands = quiet(vec![client]);
+    commands.summarize_all();
+    let waiting = command(&commands, "client");
+    assert_eq!(*waiting.status(), CommandStatus::Unstarted);
+    assert_eq!(
+        waiting.wait_reason(),
+        Some(&WaitReason::Readiness(address.clone()))
+    );
+
+    let _listener = TcpListener::bind(&address).expect("the port is still free");
+    let commands = run_to_end(commands);
+    assert!(commands.all_succeeded());
+}
+
+#[test]
+fn fail_fast_stops_the_rest() {
+    let failing = MockCommandDescBuilder::new("failing")
+        .with_statuses([CommandStatus::Finished(1)])
+        .build();
+    let endless = MockCommandDescBuilder::new("endless")
+        .with_statuses([CommandStatus::Running])
+        .build();
+    let commands = run_to_end(quiet(vec![failing, endless]).with_fail_fast(true));
+    assert!(commands.all_done());
+    assert!(!command(&commands, "endless").status().is_success());
+}
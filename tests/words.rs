@@ -0,0 +1,72 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::{quote_word, split_words};
+use std::process::Command;
+
+/// Words with every kind of character that needs quoting.
+const AWKWARD: &[&str] = &[
+    "plain",
+    "",
+    "two words",
+    "it's",
+    "'quoted'",
+    "\"double\"",
+    "back\\slash",
+    "$HOME",
+    "`date`",
+    "a;b|c&d",
+    "*.rs",
+    "tab\there",
+    "new\nline",
+    "ünïcödé",
+    "-_./=:,+@%",
+];
+
+#[test]
+fn splitting_quoted_words_gives_them_back() {
+    for word in AWKWARD {
+        assert_eq!(
+            split_words(&quote_word(word)).unwrap(),
+            [*word],
+            "{:?}",
+            word
+        );
+    }
+    let line: Vec<String> = AWKWARD.iter().map(|w| quote_word(w)).collect();
+    assert_eq!(split_words(&line.join(" ")).unwrap(), AWKWARD);
+}
+
+#[test]
+fn the_shell_reads_quoted_words_back_as_they_were() {
+    let line: Vec<String> = AWKWARD.iter().map(|w| quote_word(w)).collect();
+    let script = format!("printf '%s\\0' {}", line.join(" "));
+    let output = Command::new("sh").arg("-c").arg(&script).output().unwrap();
+    let printed = String::from_utf8(output.stdout).unwrap();
+    let words: Vec<&str> = printed.strip_suffix('\0').unwrap().split('\0').collect();
+    assert_eq!(words, AWKWARD);
+}
+
+#[test]
+fn plain_words_are_left_unquoted() {
+    assert_eq!(quote_word("cargo"), "cargo");
+    assert_eq!(quote_word("--jobs=4"), "--jobs=4");
+    assert_eq!(quote_word(""), "''");
+    assert_eq!(quote_word("it's"), r"'it'\''s'");
+}
+
+#[test]
+fn quotes_and_escapes_are_removed_as_the_shell_would() {
+    assert_eq!(
+        split_words(r#"a 'b c' "d \"e\" \$f \g" h\ i"#).unwrap(),
+        ["a", "b c", r#"d "e" $f \g"#, "h i"]
+    );
+    assert_eq!(split_words("  spaced   out  ").unwrap(), ["spaced", "out"]);
+    assert_eq!(split_words("x''y \"\"").unwrap(), ["xy", ""]);
+}
+
+#[test]
+fn unclosed_quotes_and_trailing_backslashes_are_errors() {
+    assert!(split_words("'open").is_err());
+    assert!(split_words("\"open").is_err());
+    assert!(split_words("trailing\\").is_err());
+}
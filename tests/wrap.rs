@@ -0,0 +1,69 @@
+#![allow(clippy::needless_return)]
+
+use multichecks::wrap_line;
+
+#[test]
+fn short_lines_are_left_alone() {
+    assert_eq!(wrap_line("fits", 10, 8), ["fits"]);
+    assert_eq!(wrap_line("", 10, 8), [""]);
+}
+
+#[test]
+fn lines_wrap_at_the_last_whitespace_that_fits() {
+    assert_eq!(
+        wrap_line("the quick brown fox jumps", 10, 10),
+        ["the quick", "brown fox", "jumps"]
+    );
+}
+
+#[test]
+fn continuation_rows_use_the_rest_width() {
+    assert_eq!(
+        wrap_line("aaaa bbbb cccc dddd", 9, 4),
+        ["aaaa bbbb", "cccc", "dddd"]
+    );
+}
+
+#[test]
+fn a_word_that_exactly_fills_a_row_stays_on_it() {
+    assert_eq!(wrap_line("abcd efgh", 4, 4), ["abcd", "efgh"]);
+    assert_eq!(wrap_line("abcd   efgh  ij", 4, 4), ["abcd", "efgh", "ij"]);
+}
+
+#[test]
+fn words_longer_than_a_row_are_split() {
+    assert_eq!(wrap_line("abcdefghij", 4, 4), ["abcd", "efgh", "ij"]);
+    assert_eq!(wrap_line("ab cdefghij", 4, 4), ["ab", "cdef", "ghij"]);
+}
+
+#[test]
+fn escape_sequences_dont_count_toward_the_width() {
+    assert_eq!(
+        wrap_line("\x1b[1mab\x1b[22m cd", 5, 5),
+        ["\x1b[1mab\x1b[22m cd"]
+    );
+}
+
+#[test]
+fn the_active_color_is_re_emitted_on_each_continuation_row() {
+    assert_eq!(
+        wrap_line("\x1b[31merror: one two three\x1b[0m", 10, 10),
+        ["\x1b[31merror: one\x1b[0m", "\x1b[31mtwo three\x1b[0m",]
+    );
+}
+
+#[test]
+fn a_reset_color_isnt_carried_over() {
+    assert_eq!(
+        wrap_line("\x1b[32mok\x1b[0m and then some", 10, 10),
+        ["\x1b[32mok\x1b[0m and\x1b[0m", "then some"]
+    );
+}
+
+#[test]
+fn the_color_changed_mid_row_is_the_one_carried_over() {
+    assert_eq!(
+        wrap_line("\x1b[31mred \x1b[34mblue blue", 9, 9),
+        ["\x1b[31mred \x1b[34mblue\x1b[0m", "\x1b[34mblue"]
+    );
+}